@@ -0,0 +1,199 @@
+use crate::display_logic::table_style::MIN_TABLE_WIDTH;
+use crate::errors::DiceError;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+// points at a config file to use instead of the default one
+pub const CONFIG_ENV: &str = "DICE_ROLLER_CONFIG";
+
+// what init writes, every setting commented out
+pub const CONFIG_TEMPLATE: &str = "\
+# dice-roller settings, a flag always wins over the same setting here
+
+# table width in columns, instead of the terminal's
+# width = 100
+
+# draw tables with +, - and | instead of box drawing characters, the same as --ascii
+# ascii = true
+";
+
+// settings read from config.toml, a flag always wins over the same setting here
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub width: Option<u16>,  // table width in columns, instead of the terminal's
+    pub ascii: Option<bool>, // draw tables with +, - and | instead of box drawing characters
+}
+
+impl Config {
+    pub fn from_toml(text: &str) -> Result<Self, DiceError> {
+        let config: Self = toml::from_str(text)
+            .map_err(|error| DiceError::InvalidConfig(error.message().to_string()))?;
+        // the same limit as --width
+        if let Some(width) = config.width.filter(|&width| width < MIN_TABLE_WIDTH) {
+            return Err(DiceError::InvalidConfig(format!(
+                "width {} is too narrow, it has to be at least {}",
+                width, MIN_TABLE_WIDTH
+            )));
+        }
+        Ok(config)
+    }
+
+    // a config file that doesn't exist is the same as an empty one
+    pub fn load() -> Result<Self, DiceError> {
+        match config_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, DiceError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text).map_err(|error| match error {
+                DiceError::InvalidConfig(message) => {
+                    DiceError::InvalidConfig(format!("{} in {}", message, path.display()))
+                }
+                error => error,
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(DiceError::InvalidConfig(format!(
+                "can't read {}: {}",
+                path.display(),
+                error
+            ))),
+        }
+    }
+
+    // writes the template, a config file that's already there is only replaced when forced
+    pub fn init(path: &Path, force: bool) -> Result<(), DiceError> {
+        let write_error = |error: std::io::Error| {
+            DiceError::InvalidConfig(format!("can't write {}: {}", path.display(), error))
+        };
+        if path.exists() && !force {
+            return Err(DiceError::InvalidConfig(format!(
+                "{} is already there, use --force to replace it",
+                path.display()
+            )));
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        std::fs::write(path, CONFIG_TEMPLATE).map_err(write_error)
+    }
+}
+
+// $DICE_ROLLER_CONFIG, then $XDG_CONFIG_HOME/dice-roller/config.toml,
+// then ~/.config/dice-roller/config.toml, then %APPDATA%\dice-roller\config.toml
+pub fn config_path() -> Option<PathBuf> {
+    config_path_from(|name| std::env::var_os(name))
+}
+
+// the repl's line history lives next to the config file
+pub fn history_path() -> Option<PathBuf> {
+    config_path().map(|path| path.with_file_name("repl_history"))
+}
+
+fn config_path_from(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let set = |name: &str| var(name).filter(|value| !value.is_empty());
+    if let Some(path) = set(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = set("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| set("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| set("APPDATA").map(PathBuf::from))?;
+    Some(config_dir.join("dice-roller").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        assert_eq!(
+            Config::from_toml("width = 100\nascii = true").unwrap(),
+            Config {
+                width: Some(100),
+                ascii: Some(true),
+            }
+        );
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+        assert!(matches!(
+            Config::from_toml("colour = true"),
+            Err(DiceError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("width = \"wide\""),
+            Err(DiceError::InvalidConfig(_))
+        ));
+        assert_eq!(
+            Config::from_toml("width = 5"),
+            Err(DiceError::InvalidConfig(
+                "width 5 is too narrow, it has to be at least 20".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_from_names_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("dice-roller-bad-{}.toml", std::process::id()));
+        std::fs::write(&path, "width = 5").unwrap();
+        let error = Config::load_from(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "config: width 5 is too narrow, it has to be at least 20 in {}",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_missing_config_file_is_empty() {
+        let path = std::env::temp_dir().join("dice-roller-no-such-config.toml");
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_init_writes_a_template_that_loads() {
+        let path = std::env::temp_dir()
+            .join(format!("dice-roller-init-{}", std::process::id()))
+            .join("config.toml");
+        Config::init(&path, false).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+        assert!(matches!(
+            Config::init(&path, false),
+            Err(DiceError::InvalidConfig(_))
+        ));
+        assert!(Config::init(&path, true).is_ok());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_config_path_from() {
+        let vars = |set: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                set.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        assert_eq!(
+            config_path_from(vars(&[(CONFIG_ENV, "/tmp/dice.toml"), ("HOME", "/home/a")])),
+            Some(PathBuf::from("/tmp/dice.toml"))
+        );
+        assert_eq!(
+            config_path_from(vars(&[("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/a")])),
+            Some(PathBuf::from("/xdg/dice-roller/config.toml"))
+        );
+        assert_eq!(
+            config_path_from(vars(&[(CONFIG_ENV, ""), ("HOME", "/home/a")])),
+            Some(PathBuf::from("/home/a/.config/dice-roller/config.toml"))
+        );
+        assert_eq!(config_path_from(vars(&[])), None);
+    }
+}
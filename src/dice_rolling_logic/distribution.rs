@@ -0,0 +1,863 @@
+use crate::dice_rolling_logic::result_keeping_rules::{pool_rules, ResultKeepingRules};
+use crate::dice_rolling_logic::roll_command::DiceRollCommand;
+use crate::enums::Operator;
+use crate::errors::DiceError;
+use crate::parsing_logic::ast::{DiceGroup, RollExpression};
+use crate::parsing_logic::evaluation::evaluate;
+use crate::parsing_logic::fraction::Fraction;
+use crate::utils::apply_operator;
+use std::collections::{BTreeMap, HashMap};
+
+// chances smaller than this are left out while working a distribution out,
+// eg: a die exploding 30 times in a row, they'd never show up in the output anyway
+pub const NEGLIGIBLE_CHANCE: f64 = 1e-15;
+
+// how many pairs of outcomes may be combined before a dice roll counts as too complex
+pub const MAX_WORK: u64 = 20_000_000;
+
+// the chance of every total, success count and failure count a dice roll can come to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distribution {
+    pub totals: BTreeMap<i64, f64>,
+    pub successes: BTreeMap<u32, f64>,
+    pub failures: BTreeMap<u32, f64>,
+    pub trials: Option<u32>, // how many rolls it was simulated from, None when worked out exactly
+}
+
+impl Distribution {
+    pub fn min(&self) -> i64 {
+        self.totals.keys().next().copied().unwrap_or(0)
+    }
+
+    pub fn max(&self) -> i64 {
+        self.totals.keys().next_back().copied().unwrap_or(0)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.totals
+            .iter()
+            .map(|(&total, chance)| total as f64 * chance)
+            .sum()
+    }
+
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.totals
+            .iter()
+            .map(|(&total, chance)| (total as f64 - mean).powi(2) * chance)
+            .sum()
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    // the lowest total that at least percent of the rolls come to or stay under
+    pub fn percentile(&self, percent: f64) -> i64 {
+        let mut cumulative = 0.0;
+        for (&total, chance) in &self.totals {
+            cumulative += chance;
+            // a little slack, so 50% isn't missed by rounding in the chances
+            if cumulative >= percent / 100.0 - 1e-9 {
+                return total;
+            }
+        }
+        self.max()
+    }
+
+    pub fn chance_at_least(&self, total: i64) -> f64 {
+        // folded from 0.0, an empty sum of floats is -0.0 and would show as -0.00%
+        self.totals
+            .range(total..)
+            .fold(0.0, |sum, (_, chance)| sum + chance)
+    }
+
+    pub fn mean_successes(&self) -> f64 {
+        self.successes
+            .iter()
+            .map(|(&successes, chance)| successes as f64 * chance)
+            .sum()
+    }
+
+    pub fn mean_failures(&self) -> f64 {
+        self.failures
+            .iter()
+            .map(|(&failures, chance)| failures as f64 * chance)
+            .sum()
+    }
+
+    pub fn chance_of_successes(&self, at_least: u32) -> f64 {
+        self.successes
+            .range(at_least..)
+            .fold(0.0, |sum, (_, chance)| sum + chance)
+    }
+
+    // how far the total gets past the target on average when it meets it,
+    // None when it never does
+    pub fn margin_on_hit(&self, target: i64) -> Option<f64> {
+        let hits = self.chance_at_least(target);
+        let margins: f64 = self
+            .totals
+            .range(target..)
+            .map(|(&total, chance)| (total - target) as f64 * chance)
+            .sum();
+        (hits > 0.0).then(|| margins / hits)
+    }
+
+    // whether any die was counted as a success or failure, eg: with cs or cf
+    pub fn counts_successes(&self) -> bool {
+        self.successes.keys().any(|&successes| successes > 0)
+            || self.failures.keys().any(|&failures| failures > 0)
+    }
+
+    // how often this dice roll's total comes out higher than, the same as or lower than the other's
+    pub fn against(&self, other: &Distribution) -> Matchup {
+        let mut matchup = Matchup::default();
+        for (&total, &chance) in &self.totals {
+            let lower: f64 = other.totals.range(..total).map(|(_, chance)| chance).sum();
+            let same = other.totals.get(&total).copied().unwrap_or(0.0);
+            let higher: f64 = other
+                .totals
+                .range(total + 1..)
+                .map(|(_, chance)| chance)
+                .sum();
+            matchup.wins += chance * lower;
+            matchup.ties += chance * same;
+            matchup.losses += chance * higher;
+        }
+        matchup
+    }
+}
+
+// one dice roll against another, eg: 2d6+3 wins 45.14% of the time against 1d12+4
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Matchup {
+    pub wins: f64,
+    pub ties: f64,
+    pub losses: f64,
+}
+
+// works out the chance of every total by convolution instead of rolling,
+// following the same re-roll, explode, keep/drop, min/max and counting rules as roll_commands
+pub fn exact_distribution(
+    expression: &RollExpression,
+    commands: &[DiceRollCommand],
+    margin_of_success: u32,
+) -> Result<Distribution, DiceError> {
+    let mut work = Work::new(expression);
+    let mut units = vec![];
+    for (unit, keeping) in keeping_units(commands)? {
+        let chances = unit_chances(&unit, keeping, &mut work)?;
+        units.push((unit, chances));
+    }
+
+    // values holds the total before anything is taken off for failures or the margin of success
+    let outcomes = if expression.is_additive() {
+        let modifier: i64 = expression
+            .flat_modifiers()
+            .iter()
+            .map(|modifier| modifier.value as i64)
+            .sum();
+        let mut sums = Chances::from([(
+            Tally {
+                values: vec![modifier],
+                ..Tally::zero(0)
+            },
+            1.0,
+        )]);
+        for (unit, chances) in &units {
+            let signed = map_values(chances, |values| {
+                vec![unit
+                    .iter()
+                    .zip(values)
+                    .map(|(command, value)| command.sign as i64 * value)
+                    .sum()]
+            });
+            sums = combine(&sums, &signed, false, &mut work)?;
+        }
+        sums
+    } else {
+        // every group's subtotal is needed on its own to put into the expression
+        let mut groups = vec![];
+        let mut product = Chances::from([(Tally::zero(0), 1.0)]);
+        for (unit, chances) in &units {
+            groups.extend(unit.iter().map(|command| command.group));
+            product = combine(&product, chances, true, &mut work)?;
+        }
+        let mut outcomes = Chances::new();
+        for (tally, chance) in product {
+            let group_value = |dice: &DiceGroup| {
+                let index = groups.iter().position(|&group| group == dice.group);
+                Fraction::integer(index.map_or(0, |index| tally.values[index]))
+            };
+            let (value, _) = evaluate(expression, &group_value)?;
+            let tally = Tally {
+                values: vec![value.floor()],
+                ..tally
+            };
+            *outcomes.entry(tally).or_insert(0.0) += chance;
+        }
+        outcomes
+    };
+
+    let mut distribution = Distribution::default();
+    for (tally, chance) in outcomes {
+        let total = tally.values[0] - tally.penalty - margin_of_success as i64;
+        *distribution.totals.entry(total).or_insert(0.0) += chance;
+        *distribution.successes.entry(tally.successes).or_insert(0.0) += chance;
+        *distribution.failures.entry(tally.failures).or_insert(0.0) += chance;
+    }
+    Ok(distribution)
+}
+
+// what a set of dice adds up to so far
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Tally {
+    values: Vec<i64>, // one subtotal per dice group, while they're still needed apart
+    successes: u32,
+    failures: u32,
+    penalty: i64, // deductions and subtractions from failure, taken off the total at the end
+}
+
+impl Tally {
+    fn zero(width: usize) -> Self {
+        Self {
+            values: vec![0; width],
+            successes: 0,
+            failures: 0,
+            penalty: 0,
+        }
+    }
+}
+
+type Chances = HashMap<Tally, f64>;
+
+// counts the combining done so far, to give up on dice rolls that would take too long
+struct Work {
+    done: u64,
+    expression: String,
+}
+
+impl Work {
+    fn new(expression: &RollExpression) -> Self {
+        Self {
+            done: 0,
+            expression: expression.source.trim().to_string(),
+        }
+    }
+
+    fn add(&mut self, amount: usize) -> Result<(), DiceError> {
+        self.done += amount as u64;
+        if self.done > MAX_WORK {
+            return Err(DiceError::TooComplex(format!(
+                "{} has too many outcomes to work out exactly, simulate it instead, eg: --simulate 100000",
+                self.expression
+            )));
+        }
+        Ok(())
+    }
+}
+
+// every outcome of a next to every outcome of b, either adding the subtotals
+// of the same dice groups or putting b's dice groups after a's
+fn combine(a: &Chances, b: &Chances, append: bool, work: &mut Work) -> Result<Chances, DiceError> {
+    work.add(a.len() * b.len())?;
+    let mut combined = Chances::new();
+    for (x, x_chance) in a {
+        for (y, y_chance) in b {
+            let chance = x_chance * y_chance;
+            if chance < NEGLIGIBLE_CHANCE {
+                continue;
+            }
+            let values = if append {
+                x.values.iter().chain(&y.values).copied().collect()
+            } else {
+                x.values.iter().zip(&y.values).map(|(x, y)| x + y).collect()
+            };
+            let tally = Tally {
+                values,
+                successes: x.successes + y.successes,
+                failures: x.failures + y.failures,
+                penalty: x.penalty + y.penalty,
+            };
+            *combined.entry(tally).or_insert(0.0) += chance;
+        }
+    }
+    Ok(combined)
+}
+
+fn map_values(chances: &Chances, map: impl Fn(&[i64]) -> Vec<i64>) -> Chances {
+    let mut mapped = Chances::new();
+    for (tally, chance) in chances {
+        let tally = Tally {
+            values: map(&tally.values),
+            ..tally.clone()
+        };
+        *mapped.entry(tally).or_insert(0.0) += chance;
+    }
+    mapped
+}
+
+fn scale(chances: &Chances, by: f64) -> Chances {
+    chances
+        .iter()
+        .map(|(tally, chance)| (tally.clone(), chance * by))
+        .collect()
+}
+
+fn add_into(into: &mut Chances, chances: Chances) {
+    for (tally, chance) in chances {
+        *into.entry(tally).or_insert(0.0) += chance;
+    }
+}
+
+// dice groups that keep/drop among themselves, with the rule they keep/drop by, if any
+type KeepingUnit<'a> = (Vec<&'a DiceRollCommand>, Option<ResultKeepingRules>);
+
+// each dice group is its own unit unless it's pooled
+fn keeping_units(commands: &[DiceRollCommand]) -> Result<Vec<KeepingUnit<'_>>, DiceError> {
+    let keeping = |rules: ResultKeepingRules| (rules.keep_or_drop_count > 0).then_some(rules);
+    let mut units: Vec<KeepingUnit> = commands
+        .iter()
+        .filter(|command| !command.result_keeping_rules.pool)
+        .map(|command| (vec![command], keeping(command.result_keeping_rules)))
+        .collect();
+    let mut pooled: Vec<&DiceRollCommand> = commands
+        .iter()
+        .filter(|command| command.result_keeping_rules.pool)
+        .collect();
+    pooled.sort_by_key(|command| command.group);
+    let rules = pool_rules(pooled.iter().map(|command| command.result_keeping_rules))?;
+    if let Some(rules) = rules {
+        units.push((pooled, keeping(rules)));
+    }
+    Ok(units)
+}
+
+// the chance of each face (index 0 is a 1) once re-rolling is done
+fn face_chances(command: &DiceRollCommand) -> Vec<f64> {
+    let size = command.dice_size as usize;
+    let uniform = 1.0 / size as f64;
+    let Some(target) = command.re_roll else {
+        return vec![uniform; size];
+    };
+    let re_rolls = if command.re_roll_recursively {
+        command.limits.max_re_rolls
+    } else {
+        1
+    };
+    let mut chances = vec![0.0; size];
+    let mut reached = 1.0; // the chance of getting to this roll at all
+    for roll in 0..=re_rolls {
+        let mut re_rolled = 0.0;
+        for face in 1..=size {
+            if roll < re_rolls && apply_operator(target, &(face as u32)) {
+                re_rolled += reached * uniform;
+            } else {
+                chances[face - 1] += reached * uniform;
+            }
+        }
+        reached = re_rolled;
+        if reached < NEGLIGIBLE_CHANCE {
+            break;
+        }
+    }
+    chances
+}
+
+// the chance of each sum (the index) of the dice added by a die that explodes
+fn explosion_chances(
+    command: &DiceRollCommand,
+    target: Operator,
+    work: &mut Work,
+) -> Result<Vec<f64>, DiceError> {
+    let size = command.dice_size as usize;
+    let uniform = 1.0 / size as f64;
+    let explosions = if command.explode_once {
+        1
+    } else {
+        command.limits.max_explosions
+    };
+    // worked out from the last explosion allowed back to the first
+    let mut chances = vec![1.0];
+    for _ in 0..explosions {
+        work.add(chances.len() * size)?;
+        let mut next = vec![0.0; chances.len() + size];
+        for face in 1..=size {
+            if apply_operator(target, &(face as u32)) {
+                for (sum, chance) in chances.iter().enumerate() {
+                    next[face + sum] += uniform * chance;
+                }
+            } else {
+                next[face] += uniform;
+            }
+        }
+        while next
+            .last()
+            .is_some_and(|&chance| chance < NEGLIGIBLE_CHANCE)
+        {
+            next.pop();
+        }
+        chances = next;
+    }
+    Ok(chances)
+}
+
+// what a kept die that rolled face adds, at index in a unit of width dice groups
+fn kept_die_chances(
+    command: &DiceRollCommand,
+    face: u32,
+    explosions: Option<&[f64]>,
+    index: usize,
+    width: usize,
+) -> Chances {
+    let keeping = command.result_keeping_rules;
+    let counting = command.success_counting_rules;
+    let final_roll = keeping.replacement_roll(face).unwrap_or(face);
+    let success = counting.check_success(final_roll);
+    let failure = counting.check_failure(final_roll);
+    let (subtracted, deducted) = counting.calculate_deductions(final_roll, success, failure);
+    // the same as SuccessCountingRulesApplied::adds_to_total for a kept die
+    let adds_to_total =
+        success == Some(true) || failure == Some(false) || (success.is_none() && failure.is_none());
+    let tally = |value: i64| {
+        let mut values = vec![0; width];
+        values[index] = if adds_to_total { value } else { 0 };
+        Tally {
+            values,
+            successes: (success == Some(true)) as u32 + (failure == Some(false)) as u32,
+            failures: (success == Some(false)) as u32 + (failure == Some(true)) as u32,
+            penalty: subtracted as i64 + deducted as i64,
+        }
+    };
+
+    let mut chances = Chances::new();
+    // explosions go off on the face rolled, before min/max replaces it
+    match explosions.filter(|_| {
+        command
+            .explode
+            .is_some_and(|target| apply_operator(target, &face))
+    }) {
+        Some(explosions) if adds_to_total => {
+            for (sum, &chance) in explosions.iter().enumerate() {
+                if chance > 0.0 {
+                    *chances
+                        .entry(tally(final_roll as i64 + sum as i64))
+                        .or_insert(0.0) += chance;
+                }
+            }
+        }
+        _ => {
+            chances.insert(tally(final_roll as i64), 1.0);
+        }
+    }
+    chances
+}
+
+// every dice group of a unit, with its face chances and what a kept die adds for each face
+struct GroupDice<'a> {
+    command: &'a DiceRollCommand,
+    faces: Vec<f64>,
+    kept: Vec<Chances>, // index 0 is a 1
+}
+
+fn unit_chances(
+    unit: &[&DiceRollCommand],
+    keeping: Option<ResultKeepingRules>,
+    work: &mut Work,
+) -> Result<Chances, DiceError> {
+    let width = unit.len();
+    let mut groups = vec![];
+    for (index, command) in unit.iter().enumerate() {
+        // every face gets its own chances, so huge dice give up here before allocating
+        work.add(command.dice_size as usize)?;
+        let explosions = command
+            .explode
+            .map(|target| explosion_chances(command, target, work))
+            .transpose()?;
+        let kept = (1..=command.dice_size)
+            .map(|face| kept_die_chances(command, face, explosions.as_deref(), index, width))
+            .collect();
+        groups.push(GroupDice {
+            command,
+            faces: face_chances(command),
+            kept,
+        });
+    }
+    match keeping {
+        None => every_die_kept(&groups, width, work),
+        Some(rules) => keep_or_drop(&groups, rules, width, work),
+    }
+}
+
+// with nothing dropped, every die adds the same mix of outcomes
+fn every_die_kept(
+    groups: &[GroupDice],
+    width: usize,
+    work: &mut Work,
+) -> Result<Chances, DiceError> {
+    let mut chances = Chances::from([(Tally::zero(width), 1.0)]);
+    for group in groups {
+        let mut die = Chances::new();
+        for (face_chance, kept) in group.faces.iter().zip(&group.kept) {
+            add_into(&mut die, scale(kept, *face_chance));
+        }
+        for _ in 0..group.command.dice_count {
+            chances = combine(&chances, &die, false, work)?;
+        }
+    }
+    Ok(chances)
+}
+
+// goes through the faces in the order keep/drop sorts them, placing how many dice of each
+// group rolled each face, the dice placed first are the ones kept (or dropped)
+fn keep_or_drop(
+    groups: &[GroupDice],
+    rules: ResultKeepingRules,
+    width: usize,
+    work: &mut Work,
+) -> Result<Chances, DiceError> {
+    // ties between dice groups go to the lower group, the same as the stable sort in process_pool
+    let mut slots: Vec<(u32, usize)> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(index, group)| (1..=group.command.dice_size).map(move |face| (face, index)))
+        .collect();
+    slots.sort_by_key(|&(face, index)| {
+        (
+            if rules.high {
+                -(face as i64)
+            } else {
+                face as i64
+            },
+            index,
+        )
+    });
+    let max_dice = groups
+        .iter()
+        .map(|group| group.command.dice_count as usize)
+        .max()
+        .unwrap_or(0);
+    let ln_factorials: Vec<f64> = (0..=max_dice)
+        .scan(0.0, |ln_factorial, n| {
+            if n > 0 {
+                *ln_factorial += (n as f64).ln();
+            }
+            Some(*ln_factorial)
+        })
+        .collect();
+    let binomial = |n: usize, k: usize, p: f64| -> f64 {
+        if p <= 0.0 {
+            return if k == 0 { 1.0 } else { 0.0 };
+        }
+        if p >= 1.0 {
+            return if k == n { 1.0 } else { 0.0 };
+        }
+        (ln_factorials[n] - ln_factorials[k] - ln_factorials[n - k]
+            + k as f64 * p.ln()
+            + (n - k) as f64 * (1.0 - p).ln())
+        .exp()
+    };
+    let kept_count = |placed: usize, count: usize| {
+        let limit = rules.keep_or_drop_count as usize;
+        let in_limit = (placed + count).min(limit) - placed.min(limit);
+        if rules.keep {
+            in_limit
+        } else {
+            count - in_limit
+        }
+    };
+
+    // the chance of the faces not placed yet, per dice group
+    let mut remaining: Vec<f64> = groups
+        .iter()
+        .map(|group| group.faces.iter().sum())
+        .collect();
+    let mut faces_left: Vec<u32> = groups.iter().map(|group| group.command.dice_size).collect();
+    // the outcomes so far for how many dice of each group have been placed
+    let mut states: HashMap<Vec<usize>, Chances> = HashMap::from([(
+        vec![0; groups.len()],
+        Chances::from([(Tally::zero(width), 1.0)]),
+    )]);
+    let mut powers: HashMap<(usize, u32, usize), Chances> = HashMap::new();
+
+    for (face, index) in slots {
+        let group = &groups[index];
+        let face_chance = group.faces[face as usize - 1];
+        faces_left[index] -= 1;
+        // the chance a die shows this face, given it shows one of the faces left
+        let chance = if faces_left[index] == 0 || remaining[index] <= 0.0 {
+            1.0
+        } else {
+            (face_chance / remaining[index]).min(1.0)
+        };
+        remaining[index] -= face_chance;
+
+        let mut next: HashMap<Vec<usize>, Chances> = HashMap::new();
+        for (placed, chances) in states {
+            let total_placed: usize = placed.iter().sum();
+            let left = group.command.dice_count as usize - placed[index];
+            for count in 0..=left {
+                let weight = binomial(left, count, chance);
+                if weight < NEGLIGIBLE_CHANCE {
+                    continue;
+                }
+                let kept = kept_count(total_placed, count);
+                let power = kept_power(&mut powers, group, index, face, kept, width, work)?;
+                let combined = combine(&scale(&chances, weight), power, false, work)?;
+                let mut placed = placed.clone();
+                placed[index] += count;
+                add_into(next.entry(placed).or_default(), combined);
+            }
+        }
+        states = next;
+    }
+
+    let mut chances = Chances::new();
+    for (_, state_chances) in states {
+        add_into(&mut chances, state_chances);
+    }
+    Ok(chances)
+}
+
+// what count kept dice of the same group that all rolled face add together
+fn kept_power<'a>(
+    powers: &'a mut HashMap<(usize, u32, usize), Chances>,
+    group: &GroupDice,
+    index: usize,
+    face: u32,
+    count: usize,
+    width: usize,
+    work: &mut Work,
+) -> Result<&'a Chances, DiceError> {
+    if !powers.contains_key(&(index, face, count)) {
+        let power = if count == 0 {
+            Chances::from([(Tally::zero(width), 1.0)])
+        } else {
+            let fewer = kept_power(powers, group, index, face, count - 1, width, work)?.clone();
+            combine(&fewer, &group.kept[face as usize - 1], false, work)?
+        };
+        powers.insert((index, face, count), power);
+    }
+    Ok(&powers[&(index, face, count)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_command::RollLimits;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRules;
+    use crate::display_logic::builders::{build_dice_roll_commands, build_margin_of_success};
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_commands;
+
+    fn commands_for(
+        expression: &RollExpression,
+        keeping: ResultKeepingRules,
+    ) -> Vec<DiceRollCommand> {
+        build_dice_roll_commands(
+            expression,
+            None,
+            None,
+            None,
+            None,
+            keeping,
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap()
+    }
+
+    fn distribution(input: &str) -> Distribution {
+        let expression = parse_expression(input).unwrap();
+        let commands = commands_for(&expression, ResultKeepingRules::default());
+        let margin = build_margin_of_success(&expression, None).unwrap();
+        exact_distribution(&expression, &commands, margin).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    // rolls every possible sequence of faces through the roller itself, for dice rolls
+    // that always roll the same number of dice
+    fn enumerated(expression: &RollExpression, commands: &[DiceRollCommand]) -> Distribution {
+        let sizes: Vec<u32> = commands
+            .iter()
+            .flat_map(|command| vec![command.dice_size; command.dice_count as usize])
+            .collect();
+        let combinations: u32 = sizes.iter().product();
+        let mut expected = Distribution::default();
+        for mut index in 0..combinations {
+            let mut rolls = vec![];
+            for size in &sizes {
+                rolls.push(index % size + 1);
+                index /= size;
+            }
+            let mut source = ScriptedRollSource::new(rolls);
+            let result = roll_commands(expression, commands, 0, &mut source).unwrap();
+            let chance = 1.0 / combinations as f64;
+            *expected.totals.entry(result.total as i64).or_insert(0.0) += chance;
+            *expected.successes.entry(result.successes).or_insert(0.0) += chance;
+            *expected.failures.entry(result.failures).or_insert(0.0) += chance;
+        }
+        expected
+    }
+
+    fn assert_matches_roller(input: &str, keeping: ResultKeepingRules) {
+        let expression = parse_expression(input).unwrap();
+        let commands = commands_for(&expression, keeping);
+        let exact = exact_distribution(&expression, &commands, 0).unwrap();
+        let expected = enumerated(&expression, &commands);
+        let keys = |distribution: &Distribution| {
+            let mut keys: Vec<i64> = distribution
+                .totals
+                .iter()
+                .filter(|(_, &chance)| chance > 1e-12)
+                .map(|(&total, _)| total)
+                .collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&exact), keys(&expected), "{}", input);
+        for (total, chance) in &expected.totals {
+            assert_close(exact.totals[total], *chance);
+        }
+        for (successes, chance) in &expected.successes {
+            assert_close(exact.successes[successes], *chance);
+        }
+    }
+
+    #[test]
+    fn test_two_dice() {
+        let two_d6 = distribution("2d6");
+        assert_eq!((two_d6.min(), two_d6.max()), (2, 12));
+        assert_close(two_d6.totals[&7], 6.0 / 36.0);
+        assert_close(two_d6.mean(), 7.0);
+        assert_close(two_d6.variance(), 35.0 / 6.0);
+        assert_eq!(two_d6.percentile(50.0), 7);
+        assert_close(two_d6.chance_at_least(11), 3.0 / 36.0);
+    }
+
+    #[test]
+    fn test_against() {
+        let matchup = distribution("1d6").against(&distribution("1d4"));
+        assert_close(matchup.wins, 3.5 / 6.0);
+        assert_close(matchup.ties, 1.0 / 6.0);
+        assert_close(matchup.losses, 1.5 / 6.0);
+        let same = distribution("2d6").against(&distribution("2d6"));
+        assert_close(same.wins, same.losses);
+    }
+
+    #[test]
+    fn test_keep_and_drop() {
+        let ability = distribution("4d6dl1");
+        assert_close(ability.mean(), 15869.0 / 1296.0);
+        assert_close(ability.totals[&18], 21.0 / 1296.0);
+        let advantage = distribution("2d20kh1");
+        assert_close(advantage.totals[&20], 39.0 / 400.0);
+        assert_close(advantage.totals[&1], 1.0 / 400.0);
+    }
+
+    #[test]
+    fn test_matches_the_roller() {
+        for input in [
+            "3d4kh2+1d6-2",
+            "4d4dh1",
+            "3d6kl1",
+            "5d4cs>=3",
+            "4d6cf<=2df1",
+            "3d6sf<3",
+            "3d6min3",
+            "3d6max4",
+            "(2d4kh1+1)*3",
+            "floor(3d4/2) - 1d4",
+            "4d4even",
+        ] {
+            assert_matches_roller(input, ResultKeepingRules::default());
+        }
+        // keeping the highest 2 across both groups
+        let pool = ResultKeepingRules::new(true, true, 2, None, false, true);
+        assert_matches_roller("2d6+2d4", pool);
+        assert_matches_roller("1d4*(2d6)", pool);
+    }
+
+    #[test]
+    fn test_re_rolls_and_explosions() {
+        let re_roll = distribution("1d6r1");
+        assert_close(re_roll.totals[&1], 1.0 / 36.0);
+        assert_close(re_roll.totals[&4], 7.0 / 36.0);
+        let recursive = distribution("1d4rr1");
+        assert_close(recursive.totals[&2], 1.0 / 3.0);
+        assert!(!recursive.totals.contains_key(&1));
+
+        let exploding = distribution("1d6x");
+        assert!(!exploding.totals.contains_key(&6));
+        assert_close(exploding.totals[&7], 1.0 / 36.0);
+        assert_close(exploding.mean(), 4.2);
+        let once = distribution("1d6xo");
+        assert_eq!(once.max(), 12);
+        assert_close(once.totals[&12], 1.0 / 36.0);
+    }
+
+    #[test]
+    fn test_successes_and_margin() {
+        let pool = distribution("8d10cs>=8");
+        assert_close(pool.mean_successes(), 8.0 * 0.3);
+        assert_close(pool.successes[&0], 0.7f64.powi(8));
+        assert!(pool.counts_successes());
+        assert!(!distribution("2d6").counts_successes());
+
+        let margin = distribution("1d20ms10+5");
+        assert_eq!((margin.min(), margin.max()), (-4, 15));
+    }
+
+    #[test]
+    fn test_targets() {
+        let attack = distribution("1d20+7");
+        assert_close(attack.chance_at_least(17), 11.0 / 20.0);
+        assert_close(attack.margin_on_hit(17).unwrap(), 5.0);
+        assert_eq!(attack.margin_on_hit(28), None);
+        // with a margin of success the total is already what's left over, so 0 is a hit
+        assert_close(distribution("1d20ms10+5").chance_at_least(0), 16.0 / 20.0);
+
+        let pool = distribution("8d10cs>=8");
+        assert_close(pool.chance_of_successes(1), 1.0 - 0.7f64.powi(8));
+        assert_close(pool.chance_of_successes(0), 1.0);
+        assert_close(distribution("4d6cf<=2").mean_failures(), 4.0 / 3.0);
+    }
+
+    #[test]
+    fn test_too_complex_division_by_zero_and_overflow() {
+        let expression = parse_expression("1d10000*1d10000").unwrap();
+        let commands = commands_for(&expression, ResultKeepingRules::default());
+        assert!(matches!(
+            exact_distribution(&expression, &commands, 0),
+            Err(DiceError::TooComplex(_))
+        ));
+        let expression = parse_expression("1d2000000000+1d2000000000").unwrap();
+        let commands = commands_for(&expression, ResultKeepingRules::default());
+        assert!(matches!(
+            exact_distribution(&expression, &commands, 0),
+            Err(DiceError::TooComplex(_))
+        ));
+        let expression = parse_expression("1d6/(1d4-1d4)").unwrap();
+        let commands = commands_for(&expression, ResultKeepingRules::default());
+        assert!(matches!(
+            exact_distribution(&expression, &commands, 0),
+            Err(DiceError::DivisionByZero(_))
+        ));
+        let expression = parse_expression("1d2*100000*100000*100000*100000").unwrap();
+        let commands = commands_for(&expression, ResultKeepingRules::default());
+        assert!(matches!(
+            exact_distribution(&expression, &commands, 0),
+            Err(DiceError::Overflow(_))
+        ));
+    }
+}
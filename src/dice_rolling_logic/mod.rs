@@ -1,4 +1,9 @@
+pub mod random_table;
 pub mod result_keeping_rules;
 pub mod roll_command;
+pub mod roll_source;
 pub mod success_counting_rules;
 pub mod roll_result;
+pub mod repeated_roll;
+pub mod distribution;
+pub mod simulation;
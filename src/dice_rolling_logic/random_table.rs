@@ -0,0 +1,156 @@
+use crate::dice_rolling_logic::roll_source::RollSource;
+use crate::errors::DiceError;
+use regex::Regex;
+
+// a table to roll on, eg: a d6 of random encounters
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomTable {
+    pub dice_size: u32,
+    pub entries: Vec<RandomTableEntry>,
+}
+
+// the entry a roll from low to high (both included) lands on
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomTableEntry {
+    pub low: u32,
+    pub high: u32,
+    pub text: String,
+}
+
+impl RandomTable {
+    // one entry per line, blank lines and lines starting with # are skipped,
+    // either every entry is numbered, eg: "1-3: goblins" and "4: an owlbear", or none are
+    pub fn parse(text: &str) -> Result<Self, DiceError> {
+        let numbered_regex = Regex::new(r"^(\d+)(?:\s*-\s*(\d+))?\s*[:.)]\s*(.+)$").unwrap();
+        let lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        if lines.is_empty() {
+            return Err(DiceError::InvalidTable(
+                "the table has no entries".to_string(),
+            ));
+        }
+        let numbered = numbered_regex.is_match(lines[0].1);
+
+        let mut entries = vec![];
+        for (line_number, line) in lines {
+            match (numbered, numbered_regex.captures(line)) {
+                (true, Some(captures)) => {
+                    let number = |text: &str| {
+                        text.parse::<u32>().map_err(|_| {
+                            DiceError::InvalidTable(format!(
+                                "line {}: {} is too big",
+                                line_number, text
+                            ))
+                        })
+                    };
+                    let low = number(&captures[1])?;
+                    let high = match captures.get(2) {
+                        Some(high) => number(high.as_str())?,
+                        None => low,
+                    };
+                    if high < low {
+                        return Err(DiceError::InvalidTable(format!(
+                            "line {}: {}-{} goes backwards",
+                            line_number, low, high
+                        )));
+                    }
+                    entries.push(RandomTableEntry {
+                        low,
+                        high,
+                        text: captures[3].trim().to_string(),
+                    });
+                }
+                (false, None) => {
+                    let number = entries.len() as u32 + 1;
+                    entries.push(RandomTableEntry {
+                        low: number,
+                        high: number,
+                        text: line.to_string(),
+                    });
+                }
+                (_, _) => {
+                    return Err(DiceError::InvalidTable(format!(
+                        "line {}: either every entry is numbered or none are",
+                        line_number
+                    )))
+                }
+            }
+        }
+
+        // the numbers have to run from 1 with no gaps or overlaps
+        entries.sort_by_key(|entry| entry.low);
+        let mut expected = 1;
+        for entry in &entries {
+            if entry.low != expected {
+                return Err(DiceError::InvalidTable(format!(
+                    "the entries should go on from {}, not {}",
+                    expected, entry.low
+                )));
+            }
+            expected = entry.high + 1;
+        }
+        Ok(Self {
+            dice_size: expected - 1,
+            entries,
+        })
+    }
+
+    pub fn entry_for(&self, roll: u32) -> Option<&RandomTableEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.low <= roll && roll <= entry.high)
+    }
+
+    pub fn roll(
+        &self,
+        source: &mut impl RollSource,
+    ) -> Result<(u32, &RandomTableEntry), DiceError> {
+        let roll = source.roll(self.dice_size)?;
+        // every face is covered by an entry once parsed
+        Ok((roll, self.entry_for(roll).unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+
+    #[test]
+    fn test_unnumbered_entries_are_one_face_each() {
+        let table = RandomTable::parse("# weather\ngood\n\nrain\nstorm\n").unwrap();
+        assert_eq!(table.dice_size, 3);
+        assert_eq!(table.entry_for(2).unwrap().text, "rain");
+    }
+
+    #[test]
+    fn test_numbered_entries() {
+        let table = RandomTable::parse("4: an owlbear\n1-3: goblins\n5-6) nothing").unwrap();
+        assert_eq!(table.dice_size, 6);
+        let (roll, entry) = table.roll(&mut ScriptedRollSource::new(vec![2])).unwrap();
+        assert_eq!((roll, entry.text.as_str()), (2, "goblins"));
+        assert_eq!(table.entry_for(4).unwrap().text, "an owlbear");
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        let error = |text: &str| match RandomTable::parse(text) {
+            Err(DiceError::InvalidTable(message)) => message,
+            other => panic!("expected an invalid table, got {:?}", other),
+        };
+        assert_eq!(error("# nothing\n"), "the table has no entries");
+        assert_eq!(
+            error("1-2: goblins\nan owlbear"),
+            "line 2: either every entry is numbered or none are"
+        );
+        assert_eq!(
+            error("1-2: goblins\n4: an owlbear"),
+            "the entries should go on from 3, not 4"
+        );
+        assert_eq!(error("3-1: goblins"), "line 1: 3-1 goes backwards");
+    }
+}
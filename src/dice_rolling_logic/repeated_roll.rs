@@ -0,0 +1,155 @@
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::errors::DiceError;
+
+// the most times one dice roll can be repeated, eg: 1000x4d6dl1
+pub const MAX_REPEAT: u32 = 1000;
+
+// the most times a whole set is rolled again before giving up on its rules
+pub const MAX_SET_ATTEMPTS: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+// what a set of repeated rolls has to meet, or else the whole set is rolled again
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SetRules {
+    pub min_sum: Option<i64>,     // the totals have to add up to at least this
+    pub min_highest: Option<i32>, // at least one total has to reach this
+    pub sort: Option<SortOrder>,
+}
+
+impl SetRules {
+    fn accepts(&self, totals: &[i32]) -> bool {
+        let sum: i64 = totals.iter().map(|&total| total as i64).sum();
+        let highest = totals.iter().max().copied().unwrap_or(i32::MIN);
+        self.min_sum.is_none_or(|min_sum| sum >= min_sum)
+            && self
+                .min_highest
+                .is_none_or(|min_highest| highest >= min_highest)
+    }
+}
+
+// a dice roll rolled count times, eg: 6x4d6dl1 for ability scores
+#[derive(Clone, Debug, PartialEq)]
+pub struct RepeatedRoll {
+    pub totals: Vec<i32>, // in the order rolled, unless sorted
+    pub sets_rolled: u32, // more than 1 when a set didn't meet the rules
+}
+
+impl RepeatedRoll {
+    pub fn sum(&self) -> i64 {
+        self.totals.iter().map(|&total| total as i64).sum()
+    }
+}
+
+// what one dice roll from -d came to, rolled once or repeated
+#[derive(Clone, Debug)]
+pub enum RollOutcome {
+    Single(SuccessCountingAfterResultKeeping),
+    Repeated(RepeatedRoll),
+}
+
+impl RollOutcome {
+    // a repeated roll counts as the sum of its totals
+    pub fn total(&self) -> i64 {
+        match self {
+            RollOutcome::Single(result) => result.total as i64,
+            RollOutcome::Repeated(repeated) => repeated.sum(),
+        }
+    }
+}
+
+// rolls the set count times over, until it meets the rules
+pub fn roll_repeated(
+    count: u32,
+    rules: SetRules,
+    mut roll_once: impl FnMut() -> Result<SuccessCountingAfterResultKeeping, DiceError>,
+) -> Result<RepeatedRoll, DiceError> {
+    for sets_rolled in 1..=MAX_SET_ATTEMPTS {
+        let mut totals = (0..count)
+            .map(|_| roll_once().map(|result| result.total))
+            .collect::<Result<Vec<i32>, DiceError>>()?;
+        if rules.accepts(&totals) {
+            match rules.sort {
+                Some(SortOrder::Ascending) => totals.sort(),
+                Some(SortOrder::Descending) => totals.sort_by(|a, b| b.cmp(a)),
+                None => {}
+            }
+            return Ok(RepeatedRoll {
+                totals,
+                sets_rolled,
+            });
+        }
+    }
+    Err(DiceError::NeverStops(format!(
+        "no set met the rules in {} tries",
+        MAX_SET_ATTEMPTS
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::roll_with;
+
+    fn repeat(
+        expression: &str,
+        count: u32,
+        rules: SetRules,
+        rolls: Vec<u32>,
+    ) -> Result<RepeatedRoll, DiceError> {
+        let mut source = ScriptedRollSource::new(rolls);
+        roll_repeated(count, rules, || roll_with(expression, &mut source))
+    }
+
+    #[test]
+    fn test_repeats_in_order_or_sorted() {
+        let rolls = vec![3, 6, 1];
+        let repeated = repeat("1d6", 3, SetRules::default(), rolls.clone()).unwrap();
+        assert_eq!(repeated.totals, vec![3, 6, 1]);
+        assert_eq!(repeated.sum(), 10);
+        let rules = SetRules {
+            sort: Some(SortOrder::Descending),
+            ..Default::default()
+        };
+        assert_eq!(
+            repeat("1d6", 3, rules, rolls).unwrap().totals,
+            vec![6, 3, 1]
+        );
+    }
+
+    #[test]
+    fn test_sets_below_the_rules_are_rolled_again() {
+        let rules = SetRules {
+            min_sum: Some(8),
+            ..Default::default()
+        };
+        let repeated = repeat("1d6", 2, rules, vec![1, 2, 3, 3, 4, 5]).unwrap();
+        assert_eq!(repeated.totals, vec![4, 5]);
+        assert_eq!(repeated.sets_rolled, 3);
+
+        let rules = SetRules {
+            min_highest: Some(6),
+            ..Default::default()
+        };
+        let repeated = repeat("1d6", 2, rules, vec![5, 5, 6, 1]).unwrap();
+        assert_eq!(repeated.totals, vec![6, 1]);
+    }
+
+    #[test]
+    fn test_rules_no_set_can_meet() {
+        let rules = SetRules {
+            min_sum: Some(13),
+            ..Default::default()
+        };
+        let mut rng = rand::rng();
+        assert!(matches!(
+            roll_repeated(2, rules, || roll_with("1d6", &mut rng)),
+            Err(DiceError::NeverStops(_))
+        ));
+    }
+}
@@ -1,12 +1,15 @@
 use crate::dice_rolling_logic::roll_command::InitialDiceRollResult;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRules;
+use crate::errors::DiceError;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Default)]
 pub struct ResultKeepingRules {
     pub keep: bool,
     pub high: bool,
     pub keep_or_drop_count: u32,
     pub be_replaced_with: Option<u32>,
     pub min: bool,
+    pub pool: bool, // keep/drop across every pooled dice group instead of within this one
 }
 
 // keep = true, high = true -> keep the highest n roll(s)
@@ -21,6 +24,7 @@ impl ResultKeepingRules {
         keep_or_drop_count: u32,
         be_replaced_with: Option<u32>,
         min: bool,
+        pool: bool,
     ) -> Self {
         Self {
             keep,
@@ -28,19 +32,20 @@ impl ResultKeepingRules {
             keep_or_drop_count,
             be_replaced_with,
             min,
+            pool,
         }
     }
 
     pub fn process_results(
         &self,
         dice_rolls: &mut [InitialDiceRollResult],
-    ) -> Vec<ResultKeepingRulesApplied> {
+    ) -> Result<Vec<ResultKeepingRulesApplied>, DiceError> {
         if self.high {
             // Sort descending by final_roll for easier access to highest
-            dice_rolls.sort_by(|a, b| b.final_roll.cmp(&a.final_roll));
+            dice_rolls.sort_by_key(|roll| std::cmp::Reverse(roll.final_roll));
         } else {
             // Sort ascending by final_roll for easier access to lowest
-            dice_rolls.sort_by(|a, b| a.final_roll.cmp(&b.final_roll));
+            dice_rolls.sort_by_key(|roll| roll.final_roll);
         }
 
         dice_rolls
@@ -51,10 +56,13 @@ impl ResultKeepingRules {
                 let replacement_roll = self.replacement_roll(roll.final_roll);
 
                 let final_roll = replacement_roll.unwrap_or(roll.final_roll);
-                let subtotal = roll.subtotal - roll.final_roll as i32 + final_roll as i32;
-                let replaced_roll = replacement_roll.is_some().then(|| roll.final_roll);
+                // a min on top of explosions can go past what an i32 holds
+                let subtotal = roll.subtotal as i64 - roll.final_roll as i64 + final_roll as i64;
+                let subtotal = i32::try_from(subtotal)
+                    .map_err(|_| DiceError::Overflow(format!("a d{} roll", roll.dice_size)))?;
+                let replaced_roll = replacement_roll.is_some().then_some(roll.final_roll);
 
-                ResultKeepingRulesApplied::new(
+                Ok(ResultKeepingRulesApplied::new(
                     roll.group,
                     roll.sign,
                     roll.roll_number,
@@ -63,15 +71,17 @@ impl ResultKeepingRules {
                     roll.discarded_rolls.clone(),
                     roll.exploded_rolls.clone(),
                     subtotal,
+                    roll.capped,
                     should_keep,
                     replaced_roll,
-                )
+                    roll.success_counting_rules,
+                ))
             })
             .collect()
     }
 
     fn should_keep_roll(&self, index: usize) -> bool {
-        if self.keep_or_drop_count == 0 {
+        if self.keep_or_drop_count == 0 || self.pool {
             return true; // Default to keeping if no specific count is set
         }
 
@@ -82,7 +92,7 @@ impl ResultKeepingRules {
         }
     }
 
-    fn replacement_roll(&self, roll_value: u32) -> Option<u32> {
+    pub fn replacement_roll(&self, roll_value: u32) -> Option<u32> {
         if let Some(replacement) = self.be_replaced_with {
             if self.min && roll_value < replacement || !self.min && roll_value > replacement {
                 return Some(replacement);
@@ -90,9 +100,74 @@ impl ResultKeepingRules {
         }
         None
     }
+
+    // keeps or drops among the rolls of every pooled dice group at once
+    fn process_pool(&self, results: &mut [ResultKeepingRulesApplied], pooled_groups: &[i32]) {
+        let mut pooled: Vec<&mut ResultKeepingRulesApplied> = results
+            .iter_mut()
+            .filter(|roll| roll.kept && pooled_groups.contains(&roll.group))
+            .collect();
+        if self.high {
+            pooled.sort_by_key(|roll| std::cmp::Reverse(roll.final_roll));
+        } else {
+            pooled.sort_by_key(|roll| roll.final_roll);
+        }
+
+        let rules = Self {
+            pool: false,
+            ..*self
+        };
+        for (i, roll) in pooled.into_iter().enumerate() {
+            roll.kept = rules.should_keep_roll(i);
+        }
+    }
+}
+
+// every dice group is kept, dropped and replaced with the rules it was rolled with,
+// groups that opted into the pool then keep/drop across all of them together
+pub fn process_groups(
+    dice_rolls: &mut [InitialDiceRollResult],
+) -> Result<Vec<ResultKeepingRulesApplied>, DiceError> {
+    dice_rolls.sort_by_key(|roll| roll.group);
+    let mut results: Vec<ResultKeepingRulesApplied> = vec![];
+    for group in dice_rolls.chunk_by_mut(|a, b| a.group == b.group) {
+        let rules = group[0].result_keeping_rules;
+        results.append(&mut rules.process_results(group)?);
+    }
+
+    let pool_rules = pool_rules(dice_rolls.iter().map(|roll| roll.result_keeping_rules))?;
+    if let Some(rules) = pool_rules {
+        let mut pooled_groups: Vec<i32> = dice_rolls
+            .iter()
+            .filter(|roll| roll.result_keeping_rules.pool)
+            .map(|roll| roll.group)
+            .collect();
+        pooled_groups.dedup();
+        rules.process_pool(&mut results, &pooled_groups);
+    }
+    Ok(results)
 }
 
-#[derive(Clone)]
+// the pool is kept/dropped as one, so every pooled dice group has to keep/drop the same way,
+// min/max still apply to each group on its own
+pub fn pool_rules(
+    rules: impl Iterator<Item = ResultKeepingRules>,
+) -> Result<Option<ResultKeepingRules>, DiceError> {
+    let mut pooled = rules.filter(|rules| rules.pool);
+    let first = match pooled.next() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    let keeping = |rules: ResultKeepingRules| (rules.keep, rules.high, rules.keep_or_drop_count);
+    if pooled.any(|rules| keeping(rules) != keeping(first)) {
+        return Err(DiceError::ConflictingOptions(
+            "every pooled dice group has to keep/drop the same way".to_string(),
+        ));
+    }
+    Ok(Some(first))
+}
+
+#[derive(Clone, Debug)]
 pub struct ResultKeepingRulesApplied {
     pub group: i32,
     pub sign: i32,
@@ -102,11 +177,14 @@ pub struct ResultKeepingRulesApplied {
     pub discarded_rolls: Vec<u32>,
     pub exploded_rolls: Vec<u32>,
     pub subtotal: i32,
+    pub capped: bool,
     pub kept: bool,
     pub replaced_roll: Option<u32>,
+    pub success_counting_rules: SuccessCountingRules,
 }
 
 impl ResultKeepingRulesApplied {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         group: i32,
         sign: i32,
@@ -116,8 +194,10 @@ impl ResultKeepingRulesApplied {
         discarded_rolls: Vec<u32>,
         exploded_rolls: Vec<u32>,
         subtotal: i32,
+        capped: bool,
         kept: bool,
         replaced_roll: Option<u32>,
+        success_counting_rules: SuccessCountingRules,
     ) -> Self {
         Self {
             group,
@@ -128,8 +208,10 @@ impl ResultKeepingRulesApplied {
             discarded_rolls,
             exploded_rolls,
             subtotal,
+            capped,
             kept,
             replaced_roll,
+            success_counting_rules,
         }
     }
 }
@@ -158,6 +240,9 @@ mod tests {
             discarded_rolls: discarded,
             exploded_rolls: exploded,
             subtotal,
+            capped: false,
+            result_keeping_rules: ResultKeepingRules::default(),
+            success_counting_rules: SuccessCountingRules::default(),
         }
     }
 
@@ -170,8 +255,8 @@ mod tests {
             mock_roll(1, 1, 4, 6, 6, vec![], vec![]),
         ];
 
-        let rules = ResultKeepingRules::new(true, true, 2, None, false);
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(true, true, 2, None, false, false);
+        let results = rules.process_results(&mut rolls).unwrap();
 
         // Should sort descending: [6,5,3,2]
         // Keep first 2 → kept: 6,5; drop: 3,2
@@ -194,15 +279,15 @@ mod tests {
             mock_roll(1, 1, 3, 6, 6, vec![], vec![]),
         ];
 
-        let rules = ResultKeepingRules::new(false, false, 1, None, false); // drop lowest 1
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(false, false, 1, None, false, false); // drop lowest 1
+        let results = rules.process_results(&mut rolls).unwrap();
 
         // Sorted ascending: [1,4,6] → drop index 0 → keep 4,6
         let kept: Vec<u32> = results.iter().filter(|r| r.kept).map(|r| r.final_roll).collect();
         assert_eq!(kept.len(), 2);
         assert!(kept.contains(&4));
         assert!(kept.contains(&6));
-        assert_eq!(results.iter().find(|r| r.final_roll == 1).unwrap().kept, false);
+        assert!(!results.iter().find(|r| r.final_roll == 1).unwrap().kept);
     }
 
     #[test]
@@ -214,8 +299,8 @@ mod tests {
             mock_roll(1, 1, 4, 6, 1, vec![], vec![]),
         ];
 
-        let rules = ResultKeepingRules::new(true, false, 2, None, false); // keep lowest 2
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(true, false, 2, None, false, false); // keep lowest 2
+        let results = rules.process_results(&mut rolls).unwrap();
 
         // Sorted ascending: [1,2,5,6] → keep first 2
         let kept: Vec<u32> = results.iter().filter(|r| r.kept).map(|r| r.final_roll).collect();
@@ -233,15 +318,15 @@ mod tests {
             mock_roll(1, 1, 3, 6, 4, vec![], vec![]),
         ];
 
-        let rules = ResultKeepingRules::new(false, true, 1, None, false); // drop highest 1
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(false, true, 1, None, false, false); // drop highest 1
+        let results = rules.process_results(&mut rolls).unwrap();
 
         // Sorted desc: [6,4,3] → drop index 0 (6)
         let kept: Vec<u32> = results.iter().filter(|r| r.kept).map(|r| r.final_roll).collect();
         assert_eq!(kept.len(), 2);
         assert!(kept.contains(&3));
         assert!(kept.contains(&4));
-        assert_eq!(results.iter().find(|r| r.final_roll == 6).unwrap().kept, false);
+        assert!(!results.iter().find(|r| r.final_roll == 6).unwrap().kept);
     }
 
     #[test]
@@ -251,8 +336,8 @@ mod tests {
             mock_roll(1, 1, 2, 6, 5, vec![], vec![]),
         ];
 
-        let rules = ResultKeepingRules::new(true, true, 0, None, false);
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(true, true, 0, None, false, false);
+        let results = rules.process_results(&mut rolls).unwrap();
 
         assert!(results.iter().all(|r| r.kept));
     }
@@ -264,8 +349,8 @@ mod tests {
             mock_roll(1, 1, 2, 6, 4, vec![], vec![]), // roll_number = 2
         ];
 
-        let rules = ResultKeepingRules::new(false, false, 0, Some(3), true);
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(false, false, 0, Some(3), true, false);
+        let results = rules.process_results(&mut rolls).unwrap();
 
         let roll_with_2 = results.iter().find(|r| r.roll_number == 1).unwrap();
         let roll_with_4 = results.iter().find(|r| r.roll_number == 2).unwrap();
@@ -286,8 +371,8 @@ mod tests {
             mock_roll(1, 1, 2, 6, 3, vec![], vec![]), // roll_number = 2
         ];
 
-        let rules = ResultKeepingRules::new(false, false, 0, Some(4), false);
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(false, false, 0, Some(4), false, false);
+        let results = rules.process_results(&mut rolls).unwrap();
 
         // Find result by roll_number
         let roll_with_5 = results.iter().find(|r| r.roll_number == 1).unwrap();
@@ -314,8 +399,8 @@ mod tests {
         ];
 
         // Replace any roll < 2 with 2, then keep highest 2
-        let rules = ResultKeepingRules::new(true, true, 2, Some(2), true);
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(true, true, 2, Some(2), true, false);
+        let results = rules.process_results(&mut rolls).unwrap();
 
         // After replacement: [2,6,3,2] → sorted desc: [6,3,2,2] → keep 6 and 3
         let kept: Vec<u32> = results.iter().filter(|r| r.kept).map(|r| r.final_roll).collect();
@@ -336,10 +421,94 @@ mod tests {
         ];
 
         // Try to keep top 5 of 2 rolls → should keep both
-        let rules = ResultKeepingRules::new(true, true, 5, None, false);
-        let results = rules.process_results(&mut rolls);
+        let rules = ResultKeepingRules::new(true, true, 5, None, false, false);
+        let results = rules.process_results(&mut rolls).unwrap();
 
         assert_eq!(results.len(), 2);
         assert!(results.iter().all(|r| r.kept));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_process_groups_uses_each_groups_rules() {
+        let mut rolls = vec![
+            mock_roll(1, 1, 1, 20, 7, vec![], vec![]),
+            mock_roll(2, 1, 2, 6, 1, vec![], vec![]),
+            mock_roll(1, 1, 3, 20, 15, vec![], vec![]),
+            mock_roll(2, 1, 4, 6, 5, vec![], vec![]),
+        ];
+        for roll in rolls.iter_mut() {
+            roll.result_keeping_rules = match roll.group {
+                1 => ResultKeepingRules::new(true, true, 1, None, false, false), // kh1
+                _ => ResultKeepingRules::new(false, false, 0, Some(3), true, false), // min3
+            };
+        }
+
+        let results = process_groups(&mut rolls).unwrap();
+
+        let kept: Vec<(i32, u32)> = results
+            .iter()
+            .filter(|r| r.kept)
+            .map(|r| (r.group, r.final_roll))
+            .collect();
+        assert_eq!(kept, vec![(1, 15), (2, 3), (2, 5)]);
+    }
+
+    #[test]
+    fn test_keep_within_each_group_by_default() {
+        let mut rolls = vec![
+            mock_roll(1, 1, 1, 20, 3, vec![], vec![]),
+            mock_roll(1, 1, 2, 20, 2, vec![], vec![]),
+            mock_roll(2, 1, 1, 4, 4, vec![], vec![]),
+        ];
+        for roll in rolls.iter_mut() {
+            roll.result_keeping_rules = ResultKeepingRules::new(true, true, 1, None, false, false);
+        }
+
+        let results = process_groups(&mut rolls).unwrap();
+
+        // the d4 can't push both d20s out
+        let kept: Vec<(i32, u32)> = results.iter().filter(|r| r.kept).map(|r| (r.group, r.final_roll)).collect();
+        assert_eq!(kept, vec![(1, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn test_keep_across_the_pool_when_opted_in() {
+        let mut rolls = vec![
+            mock_roll(1, 1, 1, 20, 3, vec![], vec![]),
+            mock_roll(1, 1, 2, 20, 2, vec![], vec![]),
+            mock_roll(2, 1, 1, 4, 4, vec![], vec![]),
+            mock_roll(3, 1, 1, 6, 6, vec![], vec![]),
+            mock_roll(3, 1, 2, 6, 1, vec![], vec![]),
+        ];
+        for roll in rolls.iter_mut() {
+            roll.result_keeping_rules = match roll.group {
+                3 => ResultKeepingRules::new(false, false, 1, None, false, false), // own dl1
+                _ => ResultKeepingRules::new(true, true, 1, None, false, true),    // pooled kh1
+            };
+        }
+
+        let results = process_groups(&mut rolls).unwrap();
+
+        let kept: Vec<(i32, u32)> = results.iter().filter(|r| r.kept).map(|r| (r.group, r.final_roll)).collect();
+        assert_eq!(kept, vec![(2, 4), (3, 6)]);
+    }
+
+    #[test]
+    fn test_pool_rejects_groups_keeping_differently() {
+        let mut rolls = vec![
+            mock_roll(1, 1, 1, 6, 3, vec![], vec![]),
+            mock_roll(2, 1, 2, 8, 5, vec![], vec![]),
+        ];
+        rolls[0].result_keeping_rules = ResultKeepingRules::new(true, true, 3, None, false, true); // kh3
+        rolls[1].result_keeping_rules = ResultKeepingRules::new(true, false, 1, None, false, true); // kl1
+
+        assert!(matches!(
+            process_groups(&mut rolls),
+            Err(DiceError::ConflictingOptions(_))
+        ));
+
+        // min/max are per dice group, so they can still differ
+        rolls[1].result_keeping_rules = ResultKeepingRules::new(true, true, 3, Some(2), true, true);
+        assert!(process_groups(&mut rolls).is_ok());
+    }
+}
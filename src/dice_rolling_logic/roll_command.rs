@@ -1,7 +1,35 @@
+use crate::dice_rolling_logic::result_keeping_rules::ResultKeepingRules;
+use crate::dice_rolling_logic::roll_source::RollSource;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRules;
 use crate::enums::Operator;
-use rand::Rng;
+use crate::errors::DiceError;
 use crate::utils::apply_operator;
 
+pub const DEFAULT_MAX_RE_ROLLS: u32 = 100;
+pub const DEFAULT_MAX_EXPLOSIONS: u32 = 100;
+
+// the most times a single die may be re-rolled or explode before it is cut off
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RollLimits {
+    pub max_re_rolls: u32,
+    pub max_explosions: u32,
+}
+
+impl RollLimits {
+    pub fn new(max_re_rolls: u32, max_explosions: u32) -> Self {
+        Self {
+            max_re_rolls,
+            max_explosions,
+        }
+    }
+}
+
+impl Default for RollLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RE_ROLLS, DEFAULT_MAX_EXPLOSIONS)
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct DiceRollCommand {
     pub group: i32,
@@ -12,9 +40,13 @@ pub struct DiceRollCommand {
     pub re_roll_recursively: bool,
     pub explode: Option<Operator>,
     pub explode_once: bool,
+    pub result_keeping_rules: ResultKeepingRules,
+    pub success_counting_rules: SuccessCountingRules,
+    pub limits: RollLimits,
 }
 
 impl DiceRollCommand {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         group: i32,
         sign: i32,
@@ -24,6 +56,9 @@ impl DiceRollCommand {
         re_roll_recursively: bool,
         explode: Option<Operator>,
         explode_once: bool,
+        result_keeping_rules: ResultKeepingRules,
+        success_counting_rules: SuccessCountingRules,
+        limits: RollLimits,
     ) -> Self {
         Self {
             group,
@@ -34,27 +69,37 @@ impl DiceRollCommand {
             re_roll_recursively,
             explode,
             explode_once,
+            result_keeping_rules,
+            success_counting_rules,
+            limits,
         }
     }
 
-    pub fn roll_dice(&self) -> Vec<InitialDiceRollResult> {
+    pub fn roll_dice(
+        &self,
+        source: &mut impl RollSource,
+    ) -> Result<Vec<InitialDiceRollResult>, DiceError> {
         (1..=self.dice_count)
-            .map(|roll_number| self.roll_single_dice(roll_number))
+            .map(|roll_number| self.roll_single_dice(source, roll_number))
             .collect()
     }
 
-    fn roll_single_dice(&self, roll_number: u32) -> InitialDiceRollResult {
-        let mut rng = rand::thread_rng();
+    fn roll_single_dice(
+        &self,
+        source: &mut impl RollSource,
+        roll_number: u32,
+    ) -> Result<InitialDiceRollResult, DiceError> {
         let mut discarded_rolls = vec![];
         let mut exploded_rolls = vec![];
-        let mut roll = rng.gen_range(1..=self.dice_size);
+        let mut roll = source.roll(self.dice_size)?;
+        let mut capped = false;
 
         if let Some(target) = &self.re_roll {
-            roll = self.apply_re_rolls(&mut rng, roll, target, &mut discarded_rolls);
+            (roll, capped) = self.apply_re_rolls(source, roll, target, &mut discarded_rolls)?;
         }
 
         if let Some(target) = &self.explode {
-            self.apply_explosions(&mut rng, roll, target, &mut exploded_rolls);
+            capped |= self.apply_explosions(source, roll, target, &mut exploded_rolls)?;
         }
 
         InitialDiceRollResult::new(
@@ -65,48 +110,58 @@ impl DiceRollCommand {
             roll,
             discarded_rolls,
             exploded_rolls,
+            capped,
+            self.result_keeping_rules,
+            self.success_counting_rules,
         )
     }
 
+    // returns the final roll and whether re-rolling was cut off by the limit
     fn apply_re_rolls(
         &self,
-        rng: &mut impl Rng,
+        source: &mut impl RollSource,
         initial_roll: u32,
         target: &Operator,
         discarded_rolls: &mut Vec<u32>,
-    ) -> u32 {
+    ) -> Result<(u32, bool), DiceError> {
         let mut roll = initial_roll;
         while apply_operator(*target, &roll) {
+            if discarded_rolls.len() as u32 >= self.limits.max_re_rolls {
+                return Ok((roll, true));
+            }
             discarded_rolls.push(roll);
-            roll = rng.gen_range(1..=self.dice_size);
+            roll = source.roll(self.dice_size)?;
             if !self.re_roll_recursively {
                 break;
             }
         }
-        roll
+        Ok((roll, false))
     }
 
+    // returns whether exploding was cut off by the limit
     fn apply_explosions(
         &self,
-        rng: &mut impl Rng,
+        source: &mut impl RollSource,
         initial_roll: u32,
         target: &Operator,
         exploded_rolls: &mut Vec<u32>,
-    ) {
-        if apply_operator(*target, &initial_roll) {
-            let mut roll = rng.gen_range(1..=self.dice_size);
+    ) -> Result<bool, DiceError> {
+        let mut roll = initial_roll;
+        while apply_operator(*target, &roll) {
+            if exploded_rolls.len() as u32 >= self.limits.max_explosions {
+                return Ok(true);
+            }
+            roll = source.roll(self.dice_size)?;
             exploded_rolls.push(roll);
-            if !self.explode_once {
-                while apply_operator(*target, &roll) {
-                    roll = rng.gen_range(1..=self.dice_size);
-                    exploded_rolls.push(roll);
-                }
+            if self.explode_once {
+                break;
             }
         }
+        Ok(false)
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct InitialDiceRollResult {
     pub group: i32,
     pub sign: i32,
@@ -116,9 +171,13 @@ pub struct InitialDiceRollResult {
     pub discarded_rolls: Vec<u32>,
     pub exploded_rolls: Vec<u32>,
     pub subtotal: i32,
+    pub capped: bool, // re-rolling or exploding hit the limit
+    pub result_keeping_rules: ResultKeepingRules,
+    pub success_counting_rules: SuccessCountingRules,
 }
 
 impl InitialDiceRollResult {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         group: i32,
         sign: i32,
@@ -127,9 +186,15 @@ impl InitialDiceRollResult {
         final_roll: u32,
         discarded_rolls: Vec<u32>,
         exploded_rolls: Vec<u32>,
-    ) -> InitialDiceRollResult {
-        let subtotal = exploded_rolls.iter().map(|x| *x as i32).sum::<i32>() + final_roll as i32;
-        Self {
+        capped: bool,
+        result_keeping_rules: ResultKeepingRules,
+        success_counting_rules: SuccessCountingRules,
+    ) -> Result<InitialDiceRollResult, DiceError> {
+        // explosions can add up past what an i32 holds, eg: 1d2000000000x
+        let subtotal = exploded_rolls.iter().map(|x| *x as i64).sum::<i64>() + final_roll as i64;
+        let subtotal = i32::try_from(subtotal)
+            .map_err(|_| DiceError::Overflow(format!("a d{} roll", dice_size)))?;
+        Ok(Self {
             group,
             sign,
             roll_number,
@@ -138,19 +203,52 @@ impl InitialDiceRollResult {
             discarded_rolls,
             exploded_rolls,
             subtotal,
-        }
+            capped,
+            result_keeping_rules,
+            success_counting_rules,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
     use crate::enums::Operator;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn command(
+        dice_count: u32,
+        dice_size: u32,
+        re_roll: Option<Operator>,
+        re_roll_recursively: bool,
+        explode: Option<Operator>,
+        explode_once: bool,
+    ) -> DiceRollCommand {
+        DiceRollCommand::new(
+            1,
+            1,
+            dice_count,
+            dice_size,
+            re_roll,
+            re_roll_recursively,
+            explode,
+            explode_once,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+    }
+
+    fn scripted(rolls: &[u32]) -> ScriptedRollSource {
+        ScriptedRollSource::new(rolls.to_vec())
+    }
 
     #[test]
     fn test_basic_roll_no_reroll_no_explode() {
-        let cmd = DiceRollCommand::new(1, 1, 3, 6, None, false, None, false);
-        let results = cmd.roll_dice();
+        let cmd = command(3, 6, None, false, None, false);
+        let results = cmd.roll_dice(&mut ChaCha8Rng::seed_from_u64(42)).unwrap();
         assert_eq!(results.len(), 3);
         for result in results {
             assert!(result.final_roll >= 1 && result.final_roll <= 6);
@@ -162,127 +260,78 @@ mod tests {
 
     #[test]
     fn test_reroll_once_on_1() {
-        // Roll 1d6, reroll 1s once
-        let cmd = DiceRollCommand::new(1, 1, 1, 6, Some(Operator::Eq(1)), false, None, false);
-        let results = cmd.roll_dice();
-        assert_eq!(results.len(), 1);
-        let result = &results[0];
+        // Roll 1d6, reroll 1s once, a second 1 is kept
+        let cmd = command(2, 6, Some(Operator::Eq(1)), false, None, false);
+        let mut source = scripted(&[1, 1, 4]);
+        let results = cmd.roll_dice(&mut source).unwrap();
 
-        // Either:
-        // - first roll ≠ 1 → final_roll ≠ 1, no discarded
-        // - first roll = 1 → final_roll ≠ 1, one discarded = 1
-        if result.discarded_rolls.is_empty() {
-            assert_ne!(result.final_roll, 1);
-        } else {
-            assert_eq!(result.discarded_rolls, vec![1]);
-            assert_ne!(result.final_roll, 1);
-        }
+        assert_eq!(results[0].discarded_rolls, vec![1]);
+        assert_eq!(results[0].final_roll, 1);
+        assert!(results[1].discarded_rolls.is_empty());
+        assert_eq!(results[1].final_roll, 4);
     }
 
     #[test]
     fn test_reroll_recursive_on_1() {
-        let cmd = DiceRollCommand::new(1, 1, 1, 6, Some(Operator::Eq(1)), true, None, false);
-        let results = cmd.roll_dice();
+        let cmd = command(1, 6, Some(Operator::Eq(1)), true, None, false);
+        let results = cmd.roll_dice(&mut scripted(&[1, 1, 1, 5])).unwrap();
         let result = &results[0];
 
-        // Final roll must not be 1
-        assert_ne!(result.final_roll, 1);
-        // All discarded rolls (if any) must be 1
-        for &discarded in &result.discarded_rolls {
-            assert_eq!(discarded, 1);
-        }
+        assert_eq!(result.discarded_rolls, vec![1, 1, 1]);
+        assert_eq!(result.final_roll, 5);
+        assert_eq!(result.subtotal, 5);
     }
 
     #[test]
     fn test_explode_once_on_max() {
-        let cmd = DiceRollCommand::new(1, 1, 1, 6, None, false, Some(Operator::Eq(6)), true);
-        let results = cmd.roll_dice();
-        let result = &results[0];
+        let cmd = command(2, 6, None, false, Some(Operator::Eq(6)), true);
+        let mut source = scripted(&[6, 6, 3]);
+        let results = cmd.roll_dice(&mut source).unwrap();
 
-        if result.final_roll == 6 {
-            // Should have exactly one exploded roll
-            assert_eq!(result.exploded_rolls.len(), 1);
-            let exploded = result.exploded_rolls[0];
-            assert!(exploded >= 1 && exploded <= 6);
-        } else {
-            assert!(result.exploded_rolls.is_empty());
-        }
-        // Subtotal = final + sum(exploded)
-        let expected_subtotal = result.final_roll as i32 + result.exploded_rolls.iter().map(|&x| x as i32).sum::<i32>();
-        assert_eq!(result.subtotal, expected_subtotal);
+        // the exploded 6 doesn't explode again
+        assert_eq!(results[0].final_roll, 6);
+        assert_eq!(results[0].exploded_rolls, vec![6]);
+        assert_eq!(results[0].subtotal, 12);
+        assert!(results[1].exploded_rolls.is_empty());
+        assert_eq!(results[1].subtotal, 3);
     }
 
     #[test]
     fn test_explode_recursive_on_max() {
-        let cmd = DiceRollCommand::new(1, 1, 1, 6, None, false, Some(Operator::Eq(6)), false);
-        let results = cmd.roll_dice();
+        let cmd = command(1, 6, None, false, Some(Operator::Eq(6)), false);
+        let results = cmd.roll_dice(&mut scripted(&[6, 6, 6, 2])).unwrap();
         let result = &results[0];
 
-        // If final roll is 6, there must be at least one explosion
-        // And no explosion roll should be 6 unless followed by another
-        // But easier: just verify that no exploded roll triggers further explosion *unless* it's the last one
-        // Instead, we check: all exploded rolls except possibly the last must be 6
-        let exploded = &result.exploded_rolls;
-        if result.final_roll == 6 {
-            assert!(!exploded.is_empty());
-            // All but the last exploded roll must be 6
-            for &roll in exploded.iter().take(exploded.len().saturating_sub(1)) {
-                assert_eq!(roll, 6);
-            }
-            // The last exploded roll can be anything (1–6)
-            if let Some(&last) = exploded.last() {
-                assert!(last >= 1 && last <= 6);
-            }
-        } else {
-            assert!(exploded.is_empty());
-        }
-
-        let expected_subtotal = result.final_roll as i32 + exploded.iter().map(|&x| x as i32).sum::<i32>();
-        assert_eq!(result.subtotal, expected_subtotal);
+        assert_eq!(result.final_roll, 6);
+        assert_eq!(result.exploded_rolls, vec![6, 6, 2]);
+        assert_eq!(result.subtotal, 20);
     }
 
     #[test]
     fn test_reroll_and_explode_together() {
         // Reroll 1s (once), explode on 6s (once)
-        let cmd = DiceRollCommand::new(
-            1, 1, 1, 6,
-            Some(Operator::Eq(1)), false,
-            Some(Operator::Eq(6)), true,
-        );
-        let results = cmd.roll_dice();
+        let cmd = command(1, 6, Some(Operator::Eq(1)), false, Some(Operator::Eq(6)), true);
+        let results = cmd.roll_dice(&mut scripted(&[1, 6, 4])).unwrap();
         let result = &results[0];
 
-        // Final roll cannot be 1 (due to reroll)
-        assert_ne!(result.final_roll, 1);
-
-        // Discarded rolls (if any) must be [1]
-        if !result.discarded_rolls.is_empty() {
-            assert_eq!(result.discarded_rolls, vec![1]);
-        }
-
-        // Explode only if final_roll == 6
-        if result.final_roll == 6 {
-            assert_eq!(result.exploded_rolls.len(), 1);
-        } else {
-            assert!(result.exploded_rolls.is_empty());
-        }
-
-        let expected_subtotal = result.final_roll as i32 + result.exploded_rolls.iter().map(|&x| x as i32).sum::<i32>();
-        assert_eq!(result.subtotal, expected_subtotal);
+        assert_eq!(result.discarded_rolls, vec![1]);
+        assert_eq!(result.final_roll, 6);
+        assert_eq!(result.exploded_rolls, vec![4]);
+        assert_eq!(result.subtotal, 10);
     }
 
     #[test]
     fn test_zero_dice_count() {
-        let cmd = DiceRollCommand::new(1, 1, 0, 6, None, false, None, false);
-        let results = cmd.roll_dice();
+        let cmd = command(0, 6, None, false, None, false);
+        let results = cmd.roll_dice(&mut scripted(&[])).unwrap();
         assert_eq!(results.len(), 0);
     }
 
     #[test]
     fn test_dice_size_one() {
         // d1 always rolls 1
-        let cmd = DiceRollCommand::new(1, 1, 2, 1, None, false, None, false);
-        let results = cmd.roll_dice();
+        let cmd = command(2, 1, None, false, None, false);
+        let results = cmd.roll_dice(&mut ChaCha8Rng::seed_from_u64(42)).unwrap();
         assert_eq!(results.len(), 2);
         for r in results {
             assert_eq!(r.final_roll, 1);
@@ -293,12 +342,78 @@ mod tests {
     #[test]
     fn test_reroll_always_condition() {
         // Reroll if <= 6 on d6 → always reroll, but non-recursive → only one reroll
-        let cmd = DiceRollCommand::new(1, 1, 1, 6, Some(Operator::Lte(6)), false, None, false);
-        let results = cmd.roll_dice();
+        let cmd = command(1, 6, Some(Operator::Lte(6)), false, None, false);
+        let results = cmd.roll_dice(&mut scripted(&[3, 5])).unwrap();
         let result = &results[0];
-        // Should have exactly one discarded roll (the first 1–6), and one final roll (also 1–6)
-        assert_eq!(result.discarded_rolls.len(), 1);
-        assert!(result.discarded_rolls[0] >= 1 && result.discarded_rolls[0] <= 6);
-        assert!(result.final_roll >= 1 && result.final_roll <= 6);
+        assert_eq!(result.discarded_rolls, vec![3]);
+        assert_eq!(result.final_roll, 5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_same_seed_gives_same_rolls() {
+        let cmd = command(20, 6, Some(Operator::Eq(1)), true, Some(Operator::Eq(6)), false);
+        let first = cmd.roll_dice(&mut ChaCha8Rng::seed_from_u64(7)).unwrap();
+        let second = cmd.roll_dice(&mut ChaCha8Rng::seed_from_u64(7)).unwrap();
+
+        let rolls = |results: &[InitialDiceRollResult]| -> Vec<(u32, Vec<u32>, Vec<u32>)> {
+            results
+                .iter()
+                .map(|r| (r.final_roll, r.discarded_rolls.clone(), r.exploded_rolls.clone()))
+                .collect()
+        };
+        assert_eq!(rolls(&first), rolls(&second));
+    }
+
+    #[test]
+    fn test_seeds_roll_the_same_dice_on_every_version() {
+        // the generator is pinned, so a printed seed keeps replaying after an upgrade
+        let cmd = command(4, 6, None, false, None, false);
+        let results = cmd.roll_dice(&mut ChaCha8Rng::seed_from_u64(1234)).unwrap();
+        let faces: Vec<u32> = results.iter().map(|r| r.final_roll).collect();
+        assert_eq!(faces, vec![5, 3, 1, 6]);
+    }
+
+    #[test]
+    fn test_recursive_re_roll_is_capped() {
+        let mut cmd = command(1, 6, Some(Operator::Lte(3)), true, None, false);
+        cmd.limits = RollLimits::new(2, 100);
+        let results = cmd.roll_dice(&mut scripted(&[1, 2, 3])).unwrap();
+        let result = &results[0];
+
+        assert_eq!(result.discarded_rolls, vec![1, 2]);
+        assert_eq!(result.final_roll, 3);
+        assert!(result.capped);
+    }
+
+    #[test]
+    fn test_explosions_are_capped() {
+        let mut cmd = command(2, 6, None, false, Some(Operator::Eq(6)), false);
+        cmd.limits = RollLimits::new(100, 3);
+        let results = cmd.roll_dice(&mut scripted(&[6, 6, 6, 6, 5])).unwrap();
+
+        assert_eq!(results[0].exploded_rolls, vec![6, 6, 6]);
+        assert_eq!(results[0].subtotal, 24);
+        assert!(results[0].capped);
+        // the cap is per die, the next die starts over
+        assert_eq!(results[1].final_roll, 5);
+        assert!(results[1].exploded_rolls.is_empty());
+        assert!(!results[1].capped);
+    }
+
+    #[test]
+    fn test_reaching_the_limit_without_matching_is_not_capped() {
+        let mut cmd = command(1, 6, None, false, Some(Operator::Eq(6)), false);
+        cmd.limits = RollLimits::new(100, 2);
+        let results = cmd.roll_dice(&mut scripted(&[6, 6, 1])).unwrap();
+
+        assert_eq!(results[0].exploded_rolls, vec![6, 1]);
+        assert!(!results[0].capped);
+    }
+
+    #[test]
+    fn test_running_out_of_scripted_rolls_mid_explosion_is_an_error() {
+        let cmd = command(1, 6, None, false, Some(Operator::Eq(6)), false);
+        let error = cmd.roll_dice(&mut scripted(&[6, 6])).err();
+        assert_eq!(error, Some(DiceError::ScriptedRollsExhausted(6)));
+    }
+}
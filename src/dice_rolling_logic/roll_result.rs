@@ -1,15 +1,17 @@
 use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
-use crate::utils::{AbridgedTableDisplay, VerboseTableDisplay};
-use comfy_table::presets::UTF8_FULL;
-use comfy_table::{Cell, ContentArrangement, Table};
+use crate::errors::DiceError;
+use crate::parsing_logic::ast::{DiceGroup, FlatModifier, RollExpression};
+use crate::parsing_logic::evaluation::{evaluate, EvaluationStep};
+use crate::parsing_logic::fraction::Fraction;
 use std::collections::HashMap;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct SuccessCountingAfterResultKeeping {
     pub rolls: Vec<SuccessCountingRulesApplied>,
     pub deductions_from_failure: u32,
     pub subtractions_from_failure: u32,
     pub margin_of_success: u32,
+    pub flat_modifiers: Vec<FlatModifier>,
     pub initial_modifier: i32,
     pub final_modifier: i32,
     pub grouped_subtotals: HashMap<i32, i32>,
@@ -17,198 +19,154 @@ pub struct SuccessCountingAfterResultKeeping {
     pub total: i32,
     pub doubled: i32,
     pub halved: f32,
-    pub(crate) successes: u32,
+    pub successes: u32,
     pub failures: u32,
     pub evens: u32,
     pub odds: u32,
+    pub steps: Vec<EvaluationStep>,
 }
 
 impl SuccessCountingAfterResultKeeping {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         rolls: Vec<SuccessCountingRulesApplied>,
         deductions_from_failure: u32,
         subtractions_from_failure: u32,
         margin_of_success: u32,
-        initial_modifier: i32,
+        flat_modifiers: Vec<FlatModifier>,
         successes: u32,
         failures: u32,
         evens: u32,
         odds: u32,
-    ) -> Self {
+    ) -> Result<Self, DiceError> {
         let kept_successes = rolls
             .iter()
-            .filter(|x| x.kept)
-            .filter(|x| {
-                (x.success == Some(true) || x.failure == Some(false))
-                    || (x.success.is_none() && x.failure.is_none())
-            })
+            .filter(|x| x.adds_to_total())
             .collect::<Vec<&SuccessCountingRulesApplied>>();
 
-        let mut grouped_subtotals: HashMap<i32, i32> = HashMap::new();
-
+        // added up wide, then anything that does not fit back in an i32 is an error
+        let mut wide_subtotals: HashMap<i32, i64> = HashMap::new();
         for successes in kept_successes {
-            let group_subtotal = *grouped_subtotals.entry(successes.group).or_insert(0);
-            let to_add = successes.subtotal * successes.sign;
-            if group_subtotal.eq(&0) {
-                grouped_subtotals.insert(successes.group, to_add);
-            } else {
-                grouped_subtotals.insert(successes.group, group_subtotal + to_add);
-            }
+            *wide_subtotals.entry(successes.group).or_insert(0) +=
+                successes.subtotal as i64 * successes.sign as i64;
         }
 
+        let initial_modifier = flat_modifiers
+            .iter()
+            .map(|modifier| modifier.value as i64)
+            .sum::<i64>();
         let final_modifier = initial_modifier
-            - deductions_from_failure as i32
-            - subtractions_from_failure as i32
-            - margin_of_success as i32;
+            - deductions_from_failure as i64
+            - subtractions_from_failure as i64
+            - margin_of_success as i64;
 
-        let total_before_modifier = grouped_subtotals.values().sum::<i32>();
+        let total_before_modifier = wide_subtotals.values().sum::<i64>();
         let total = total_before_modifier + final_modifier;
 
-        Self {
+        let fits = |value: i64| {
+            i32::try_from(value).map_err(|_| DiceError::Overflow("the total".to_string()))
+        };
+        let mut grouped_subtotals: HashMap<i32, i32> = HashMap::new();
+        for (group, subtotal) in wide_subtotals {
+            grouped_subtotals.insert(group, fits(subtotal)?);
+        }
+
+        Ok(Self {
             rolls,
             deductions_from_failure,
             subtractions_from_failure,
             margin_of_success,
-            initial_modifier,
-            final_modifier,
+            flat_modifiers,
+            initial_modifier: fits(initial_modifier)?,
+            final_modifier: fits(final_modifier)?,
             grouped_subtotals,
-            total_before_modifier,
-            total,
-            doubled: total * 2,
+            total_before_modifier: fits(total_before_modifier)?,
+            total: fits(total)?,
+            doubled: fits(total * 2)?,
             halved: total as f32 / 2.0,
             successes,
             failures,
             evens,
             odds,
-        }
-    }
-}
-
-fn format_modifier(modifier: i32) -> String {
-    if modifier >= 0 {
-        format!("+{}", modifier)
-    } else {
-        format!("{}", modifier)
+            steps: vec![],
+        })
     }
-}
 
-impl VerboseTableDisplay for SuccessCountingAfterResultKeeping {
-    fn verbose_display(mut self) {
-        self.rolls.sort_by(|a, b| a.group.cmp(&b.group));
-        self.rolls.iter().for_each(|x| x.clone().verbose_display());
-        let mut header = vec![
-            Cell::new("Total Before Modifier"),
-            Cell::new("Total"),
-            Cell::new("Doubled"),
-            Cell::new("Halved"),
-        ];
-        let mut row = vec![
-            Cell::new(self.total_before_modifier),
-            Cell::new(self.total),
-            Cell::new(self.doubled),
-            Cell::new(self.halved),
-        ];
-
-        if self.initial_modifier != self.final_modifier {
-            header.push(Cell::new("Initial Modifier"));
-            row.push(Cell::new(format_modifier(self.initial_modifier)));
-            header.push(Cell::new("Final Modifier"));
-            row.push(Cell::new(format_modifier(self.final_modifier)));
-        } else {
-            header.push(Cell::new("Modifier"));
-            row.push(Cell::new(format_modifier(self.final_modifier)));
-        }
-        if self.deductions_from_failure > 0 {
-            header.push(Cell::new("Deductions From Failure"));
-            row.push(Cell::new(self.deductions_from_failure));
-        }
-        if self.subtractions_from_failure > 0 {
-            header.push(Cell::new("Subtractions From Failure"));
-            row.push(Cell::new(self.subtractions_from_failure));
-        }
-        if self.margin_of_success > 0 {
-            header.push(Cell::new("Margin of Success"));
-            row.push(Cell::new(self.margin_of_success));
-        }
-        if self.successes > 0 {
-            header.push(Cell::new("Successes"));
-            row.push(Cell::new(self.successes));
-        }
-        if self.failures > 0 {
-            header.push(Cell::new("Failures"));
-            row.push(Cell::new(self.failures));
-        }
-        if self.evens > 0 {
-            header.push(Cell::new("Evens"));
-            row.push(Cell::new(self.evens));
-        }
-        if self.odds > 0 {
-            header.push(Cell::new("Odds"));
-            row.push(Cell::new(self.odds));
+    // works the total out from the full expression, so multiplication, division and
+    // rounding are applied on top of each dice group's subtotal
+    pub fn apply_expression(&mut self, expression: &RollExpression) -> Result<(), DiceError> {
+        let group_value = |dice: &DiceGroup| {
+            let subtotal = self
+                .rolls
+                .iter()
+                .filter(|roll| roll.group == dice.group && roll.adds_to_total())
+                .map(|roll| roll.subtotal as i64)
+                .sum::<i64>();
+            Fraction::integer(subtotal)
+        };
+        let (value, steps) = evaluate(expression, &group_value)?;
+
+        // worked out wide so that only a total too large to show is an error,
+        // the flat modifiers outside any multiplication are still added last,
+        // so the total before them is whatever the rest of the expression came to
+        let value = value.floor() as i128;
+        let total_before_modifier = value - self.initial_modifier as i128;
+        let total = value
+            - self.deductions_from_failure as i128
+            - self.subtractions_from_failure as i128
+            - self.margin_of_success as i128;
+        let too_large = || DiceError::Overflow(expression.source.clone());
+        self.total_before_modifier =
+            i32::try_from(total_before_modifier).map_err(|_| too_large())?;
+        self.total = i32::try_from(total).map_err(|_| too_large())?;
+        self.doubled = i32::try_from(total * 2).map_err(|_| too_large())?;
+        self.halved = self.total as f32 / 2.0;
+        if !expression.is_additive() {
+            self.steps = steps;
         }
+        Ok(())
+    }
 
-        let mut main_result = Table::new();
-        main_result
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_width(160)
-            .set_header(header)
-            .add_row(row);
-        println!("{main_result}");
-
-        let mut keys: Vec<_> = self.grouped_subtotals.keys().collect();
-        if keys.len() > 1 {
-            keys.sort();
-            let mut group_headers: Vec<String> = vec![];
-            let mut groups: Vec<i32> = vec![];
-            for key in &keys {
-                if let Some(value) = self.grouped_subtotals.get(*key) {
-                    group_headers.push(format!("dice group {}", key));
-                    groups.push(*value)
-                }
+    // the rolls keep/drop threw away, listed under the dice group they came from
+    pub fn dropped_by_group(&self) -> Vec<(i32, u32, Vec<u32>)> {
+        let mut dropped: Vec<(i32, u32, Vec<u32>)> = vec![];
+        let mut rolls: Vec<&SuccessCountingRulesApplied> =
+            self.rolls.iter().filter(|roll| !roll.kept).collect();
+        rolls.sort_by_key(|roll| (roll.group, roll.roll_number));
+        for roll in rolls {
+            match dropped.last_mut() {
+                Some((group, _, values)) if *group == roll.group => values.push(roll.final_roll),
+                _ => dropped.push((roll.group, roll.dice_size, vec![roll.final_roll])),
             }
-            let mut group_subtotals = Table::new();
-            group_subtotals
-                .load_preset(UTF8_FULL)
-                .set_content_arrangement(ContentArrangement::Dynamic)
-                .set_width(160)
-                .set_header(group_headers)
-                .add_row(groups);
-            println!("{group_subtotals}");
         }
+        dropped
     }
-}
 
-impl AbridgedTableDisplay for SuccessCountingAfterResultKeeping {
-    fn abridged_display(mut self) {
-        self.rolls.sort_by(|a, b| a.group.cmp(&b.group));
-        let mut current_group = 1;
-        for i in 0..self.rolls.len() {
-            let curr = &self.rolls[i];
-            if curr.group != current_group {
-                current_group = curr.group;
-                println!()
-            }
-            if curr.kept
-                && ((curr.success == Some(true) || curr.failure == Some(false))
-                    || (curr.success.is_none() && curr.failure.is_none()))
-            {
-                print!("{}/{}, ", curr.final_roll, curr.dice_size)
-            }
+    // dice group subtotals and flat modifiers in the order they were written,
+    // modifiers written before any dice come first
+    pub fn terms(&self) -> Vec<(String, i32)> {
+        let mut groups: Vec<_> = self.grouped_subtotals.iter().collect();
+        groups.sort();
+        let modifiers_after = |group: i32| {
+            self.flat_modifiers
+                .iter()
+                .filter(move |modifier| modifier.group == group)
+                .map(|modifier| ("modifier".to_string(), modifier.value))
+        };
+
+        let mut terms: Vec<(String, i32)> = modifiers_after(0).collect();
+        for (group, subtotal) in groups {
+            terms.push((format!("dice group {}", group), *subtotal));
+            terms.extend(modifiers_after(*group));
         }
-        println!();
-        println!(
-            "Modifier: {}, Total: {}",
-            format_modifier(self.final_modifier),
-            self.total
-        );
+        terms
     }
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsing_logic::parser::parse_expression;
     use std::collections::HashMap;
 
     fn mock_applied_roll(
@@ -229,6 +187,7 @@ mod tests {
             vec![],
             vec![],
             final_roll as i32, // subtotal = final_roll (no explosions)
+            false,             // capped
             kept,
             None, // replaced_roll
             success,
@@ -251,9 +210,9 @@ mod tests {
             0, // deductions
             0, // subtractions
             0, // margin
-            2, // initial modifier
+            vec![FlatModifier::new(2, 5, 1)],
             0, 0, 0, 0, // successes, etc.
-        );
+        ).unwrap();
 
         let expected_grouped = HashMap::from([(1, 9), (2, 3)]);
         assert_eq!(result.grouped_subtotals, expected_grouped);
@@ -273,9 +232,9 @@ mod tests {
 
         let result = SuccessCountingAfterResultKeeping::new(
             rolls,
+            0, 0, 0, vec![],
             0, 0, 0, 0,
-            0, 0, 0, 0,
-        );
+        ).unwrap();
 
         let expected_grouped = HashMap::from([(1, 6), (2, -4)]);
         assert_eq!(result.grouped_subtotals, expected_grouped);
@@ -291,9 +250,9 @@ mod tests {
 
         let result = SuccessCountingAfterResultKeeping::new(
             rolls,
+            0, 0, 0, vec![],
             0, 0, 0, 0,
-            0, 0, 0, 0,
-        );
+        ).unwrap();
 
         assert_eq!(result.total_before_modifier, 4);
     }
@@ -308,9 +267,9 @@ mod tests {
 
         let result = SuccessCountingAfterResultKeeping::new(
             rolls,
-            0, 0, 0, 0,
+            0, 0, 0, vec![],
             1, 1, 0, 0, // successes=1, failures=1 (but not used in filtering)
-        );
+        ).unwrap();
 
         // Only roll 1 and 3 are included? But in real usage, roll 3 wouldn't be neutral.
         // However, per your filter:
@@ -331,9 +290,9 @@ mod tests {
 
         let result = SuccessCountingAfterResultKeeping::new(
             rolls,
-            0, 0, 0, 0,
+            0, 0, 0, vec![],
             1, 2, 0, 0,
-        );
+        ).unwrap();
 
         // Only the success (6) is included
         assert_eq!(result.total_before_modifier, 6);
@@ -348,9 +307,9 @@ mod tests {
             2, // deductions
             3, // subtractions
             1, // margin_of_success
-            10, // initial modifier
+            vec![FlatModifier::new(6, 5, 1), FlatModifier::new(4, 7, 1)],
             0, 0, 0, 0,
-        );
+        ).unwrap();
 
         // final_modifier = 10 - 2 - 3 - 1 = 4
         assert_eq!(result.initial_modifier, 10);
@@ -365,9 +324,9 @@ mod tests {
 
         let result = SuccessCountingAfterResultKeeping::new(
             rolls,
-            0, 0, 0, 5,
+            0, 0, 0, vec![FlatModifier::new(5, 0, 0)],
             0, 0, 0, 0,
-        );
+        ).unwrap();
 
         assert!(result.grouped_subtotals.is_empty());
         assert_eq!(result.total_before_modifier, 0);
@@ -375,4 +334,131 @@ mod tests {
         assert_eq!(result.doubled, 10);
         assert_eq!(result.halved, 2.5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_apply_expression_multiplies_group_subtotals() {
+        let rolls = vec![mock_applied_roll(1, 1, 1, 5, true, None, None)];
+        let mut result = SuccessCountingAfterResultKeeping::new(rolls, 0, 0, 0, vec![], 0, 0, 0, 0).unwrap();
+
+        result
+            .apply_expression(&parse_expression("(1d8+4)*2").unwrap())
+            .unwrap();
+
+        assert_eq!(result.total, 18);
+        assert_eq!(result.doubled, 36);
+        assert_eq!(result.steps.len(), 3);
+        assert_eq!(result.steps[2].expression, "(1d8+4)*2");
+    }
+
+    #[test]
+    fn test_apply_expression_keeps_totals_adding_up_when_not_additive() {
+        let expression = parse_expression("(1d8+4)*2+3").unwrap();
+        let rolls = vec![mock_applied_roll(1, 1, 1, 5, true, None, None)];
+        let mut result = SuccessCountingAfterResultKeeping::new(rolls, 1, 0, 0, expression.flat_modifiers(), 0, 0, 0, 0).unwrap();
+
+        result.apply_expression(&expression).unwrap();
+
+        // only the +3 is added on top, the +4 is part of what gets doubled
+        assert_eq!(result.total_before_modifier, 18);
+        assert_eq!(result.initial_modifier, 3);
+        assert_eq!(result.final_modifier, 2);
+        assert_eq!(result.total, 20);
+        assert_eq!(result.total, result.total_before_modifier + result.final_modifier);
+    }
+
+    #[test]
+    fn test_apply_expression_floors_division_unless_rounded() {
+        let rolls = vec![
+            mock_applied_roll(1, 1, 1, 3, true, None, None),
+            mock_applied_roll(1, 1, 2, 4, true, None, None),
+        ];
+        let mut result = SuccessCountingAfterResultKeeping::new(rolls, 0, 0, 0, vec![], 0, 0, 0, 0).unwrap();
+
+        result.apply_expression(&parse_expression("2d6/2").unwrap()).unwrap();
+        assert_eq!(result.total, 3);
+
+        result.apply_expression(&parse_expression("ceil(2d6/2)").unwrap()).unwrap();
+        assert_eq!(result.total, 4);
+    }
+
+    #[test]
+    fn test_apply_expression_rejects_totals_too_large() {
+        let rolls = vec![mock_applied_roll(1, 1, 1, 2, true, None, None)];
+        let mut result = SuccessCountingAfterResultKeeping::new(rolls, 0, 0, 0, vec![], 0, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            result.apply_expression(&parse_expression("1d2*100000*100000").unwrap()),
+            Err(DiceError::Overflow("1d2*100000*100000".to_string()))
+        );
+        // fits in a total, but not once doubled
+        assert_eq!(
+            result.apply_expression(&parse_expression("1d2*1000000000").unwrap()),
+            Err(DiceError::Overflow("1d2*1000000000".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_apply_expression_keeps_additive_totals() {
+        let rolls = vec![
+            mock_applied_roll(1, 1, 1, 6, true, None, None),
+            mock_applied_roll(2, -1, 2, 4, true, None, None),
+        ];
+        let mut result = SuccessCountingAfterResultKeeping::new(rolls, 1, 0, 0, vec![FlatModifier::new(3, 8, 2)], 0, 0, 0, 0).unwrap();
+        let total = result.total;
+
+        result
+            .apply_expression(&parse_expression("1d6-1d4+3").unwrap())
+            .unwrap();
+
+        assert_eq!(result.total, total);
+        assert_eq!(result.total, 6 - 4 + 3 - 1);
+        assert!(result.steps.is_empty());
+    }
+
+    #[test]
+    fn test_terms_list_every_flat_modifier_after_its_group() {
+        let rolls = vec![
+            mock_applied_roll(1, 1, 1, 12, true, None, None),
+            mock_applied_roll(2, -1, 2, 3, true, None, None),
+        ];
+        let expression = parse_expression("2+1d20+5-1d4+3").unwrap();
+        let result = SuccessCountingAfterResultKeeping::new(
+            rolls,
+            0, 0, 0, expression.flat_modifiers(),
+            0, 0, 0, 0,
+        ).unwrap();
+
+        assert_eq!(result.initial_modifier, 10);
+        assert_eq!(result.total, 12 - 3 + 10);
+        assert_eq!(
+            result.terms(),
+            vec![
+                ("modifier".to_string(), 2),
+                ("dice group 1".to_string(), 12),
+                ("modifier".to_string(), 5),
+                ("dice group 2".to_string(), -3),
+                ("modifier".to_string(), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dropped_rolls_listed_by_group() {
+        let rolls = vec![
+            mock_applied_roll(2, 1, 1, 1, false, None, None),
+            mock_applied_roll(1, 1, 2, 2, false, None, None),
+            mock_applied_roll(1, 1, 1, 3, false, None, None),
+            mock_applied_roll(1, 1, 3, 6, true, None, None),
+        ];
+        let result = SuccessCountingAfterResultKeeping::new(
+            rolls,
+            0, 0, 0, vec![],
+            0, 0, 0, 0,
+        ).unwrap();
+
+        assert_eq!(
+            result.dropped_by_group(),
+            vec![(1, 6, vec![3, 2]), (2, 6, vec![1])]
+        );
+    }
+}
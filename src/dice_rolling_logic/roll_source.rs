@@ -0,0 +1,115 @@
+use crate::errors::DiceError;
+use rand::Rng;
+use std::collections::VecDeque;
+
+// where the face of every single die comes from
+pub trait RollSource {
+    // a face between 1 and dice_size
+    fn roll(&mut self, dice_size: u32) -> Result<u32, DiceError>;
+}
+
+// any random number generator can roll dice, seeded or not
+impl<R: Rng + ?Sized> RollSource for R {
+    fn roll(&mut self, dice_size: u32) -> Result<u32, DiceError> {
+        Ok(self.random_range(1..=dice_size))
+    }
+}
+
+// hands out a fixed sequence of faces in order, eg: the dice that were rolled at the table
+#[derive(Clone, Debug)]
+pub struct ScriptedRollSource {
+    rolls: VecDeque<u32>,
+}
+
+impl ScriptedRollSource {
+    pub fn new(rolls: Vec<u32>) -> Self {
+        Self {
+            rolls: rolls.into(),
+        }
+    }
+}
+
+impl RollSource for ScriptedRollSource {
+    fn roll(&mut self, dice_size: u32) -> Result<u32, DiceError> {
+        let value = self
+            .rolls
+            .pop_front()
+            .ok_or(DiceError::ScriptedRollsExhausted(dice_size))?;
+        if !(1..=dice_size).contains(&value) {
+            return Err(DiceError::ScriptedRollOutOfRange(value, dice_size));
+        }
+        Ok(value)
+    }
+}
+
+// passes rolls through from another source while keeping a log of every face,
+// the log can be played back with a ScriptedRollSource
+pub struct RecordingRollSource<S: RollSource> {
+    source: S,
+    pub recording: Vec<u32>,
+}
+
+impl<S: RollSource> RecordingRollSource<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            recording: vec![],
+        }
+    }
+}
+
+impl<S: RollSource> RollSource for RecordingRollSource<S> {
+    fn roll(&mut self, dice_size: u32) -> Result<u32, DiceError> {
+        let value = self.source.roll(dice_size)?;
+        self.recording.push(value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_scripted_rolls_come_out_in_order() {
+        let mut source = ScriptedRollSource::new(vec![6, 6, 2]);
+        assert_eq!(source.roll(6), Ok(6));
+        assert_eq!(source.roll(6), Ok(6));
+        assert_eq!(source.roll(6), Ok(2));
+    }
+
+    #[test]
+    fn test_scripted_rolls_run_out() {
+        assert_eq!(
+            ScriptedRollSource::new(vec![]).roll(6),
+            Err(DiceError::ScriptedRollsExhausted(6))
+        );
+    }
+
+    #[test]
+    fn test_scripted_roll_larger_than_dice() {
+        assert_eq!(
+            ScriptedRollSource::new(vec![7]).roll(6),
+            Err(DiceError::ScriptedRollOutOfRange(7, 6))
+        );
+    }
+
+    #[test]
+    fn test_recording_replays_exactly() {
+        let mut source = RecordingRollSource::new(ChaCha8Rng::seed_from_u64(3));
+        let rolled: Vec<u32> = [20, 6, 6, 4]
+            .iter()
+            .map(|&size| source.roll(size).unwrap())
+            .collect();
+
+        let mut replay = ScriptedRollSource::new(source.recording.clone());
+        let replayed: Vec<u32> = [20, 6, 6, 4]
+            .iter()
+            .map(|&size| replay.roll(size).unwrap())
+            .collect();
+        assert_eq!(rolled, replayed);
+        assert_eq!(source.recording, rolled);
+    }
+}
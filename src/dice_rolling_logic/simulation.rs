@@ -0,0 +1,82 @@
+use crate::dice_rolling_logic::distribution::Distribution;
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::errors::DiceError;
+
+// the most times a dice roll can be simulated, eg: --simulate 10000000
+pub const MAX_SIMULATIONS: u32 = 10_000_000;
+
+// rolls the dice roll trials times, counting how often each total, success count and
+// failure count came up, progress is told how many rolls are done after each one
+pub fn simulate(
+    trials: u32,
+    mut roll_once: impl FnMut() -> Result<SuccessCountingAfterResultKeeping, DiceError>,
+    mut progress: impl FnMut(u32),
+) -> Result<Distribution, DiceError> {
+    let mut distribution = Distribution {
+        trials: Some(trials),
+        ..Default::default()
+    };
+    for done in 1..=trials {
+        let result = roll_once()?;
+        *distribution
+            .totals
+            .entry(result.total as i64)
+            .or_insert(0.0) += 1.0;
+        *distribution
+            .successes
+            .entry(result.successes)
+            .or_insert(0.0) += 1.0;
+        *distribution.failures.entry(result.failures).or_insert(0.0) += 1.0;
+        progress(done);
+    }
+    // the counts become chances
+    for chance in distribution
+        .totals
+        .values_mut()
+        .chain(distribution.successes.values_mut())
+        .chain(distribution.failures.values_mut())
+    {
+        *chance /= trials as f64;
+    }
+    Ok(distribution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::roll_with;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_counts_every_roll() {
+        let mut source = ScriptedRollSource::new(vec![9, 8, 8, 3, 1, 2]);
+        let mut progress = vec![];
+        let distribution = simulate(
+            3,
+            || roll_with("2d10cs>=8", &mut source),
+            |done| progress.push(done),
+        )
+        .unwrap();
+        assert_eq!(progress, vec![1, 2, 3]);
+        assert_eq!(distribution.trials, Some(3));
+        assert_eq!(distribution.totals.get(&17), Some(&(1.0 / 3.0)));
+        assert_eq!(distribution.totals.get(&8), Some(&(1.0 / 3.0)));
+        assert_eq!(distribution.totals.get(&0), Some(&(1.0 / 3.0)));
+        assert_eq!(distribution.successes.get(&2), Some(&(1.0 / 3.0)));
+        assert!((distribution.mean_successes() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_the_same_seed_simulates_the_same() {
+        let run = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            simulate(2000, || roll_with("3d6x", &mut rng), |_| {}).unwrap()
+        };
+        let distribution = run(42);
+        assert_eq!(distribution, run(42));
+        // close to the exact 12.6
+        assert!((distribution.mean() - 12.6).abs() < 0.5);
+    }
+}
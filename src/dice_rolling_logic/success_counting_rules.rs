@@ -1,11 +1,11 @@
 use crate::dice_rolling_logic::result_keeping_rules::ResultKeepingRulesApplied;
 use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::parsing_logic::ast::FlatModifier;
 use crate::enums::Operator;
-use crate::utils::{apply_operator, VerboseTableDisplay};
-use comfy_table::presets::UTF8_FULL;
-use comfy_table::{Cell, ContentArrangement, Table};
+use crate::errors::DiceError;
+use crate::utils::apply_operator;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Default)]
 pub struct SuccessCountingRules {
     pub count_success: Option<Operator>,
     pub count_failure: Option<Operator>,
@@ -13,7 +13,6 @@ pub struct SuccessCountingRules {
     pub count_odd: bool,
     pub deduct_failure: Option<u32>, // deduct each by failure n
     pub subtract_failure: bool,      // deduct the entire dice roll
-    pub margin_of_success: u32,
 }
 
 impl SuccessCountingRules {
@@ -24,111 +23,34 @@ impl SuccessCountingRules {
         count_odd: bool,
         deduct_failure: Option<u32>,
         subtract_failure: bool,
-        margin_of_success: u32,
-    ) -> Self {
-        // panic when
-        // count success and count failure are both some
-        // have to be either or
-        assert!(!(count_success.is_some() && count_failure.is_some()));
-        SuccessCountingRules {
+    ) -> Result<Self, DiceError> {
+        // count success and count failure have to be either or
+        if count_success.is_some() && count_failure.is_some() {
+            return Err(DiceError::ConflictingOptions(
+                "successes and failures can't both be counted".to_string(),
+            ));
+        }
+        Ok(SuccessCountingRules {
             count_success,
             count_failure,
             count_even,
             count_odd,
             deduct_failure,
             subtract_failure,
-            margin_of_success,
-        }
+        })
     }
 
-    pub fn count_successes(
-        &self,
-        result_keeping_rules_applied: &mut [ResultKeepingRulesApplied],
-        modifier: i32,
-    ) -> SuccessCountingAfterResultKeeping {
-        let mut success_counting_rules_applied: Vec<SuccessCountingRulesApplied> = vec![];
-        let mut evens = 0;
-        let mut odds = 0;
-        let mut total_subtracted = 0;
-        let mut total_deducted = 0;
-        let mut successes = 0;
-        let mut failures = 0;
-
-        for roll in result_keeping_rules_applied {
-            let success = self.check_success(roll.final_roll);
-            let failure = self.check_failure(roll.final_roll);
-            let (subtracted, deduction) =
-                self.calculate_deductions(roll.final_roll, success, failure);
-
-            if roll.kept {
-                if let Some(true) = success {
-                    successes += 1;
-                } else if let Some(false) = success {
-                    failures += 1;
-                }
-
-                if let Some(true) = failure {
-                    failures += 1;
-                } else if let Some(false) = failure {
-                    successes += 1;
-                }
-
-                total_subtracted += subtracted;
-                total_deducted += deduction;
-
-                // Count even and odd rolls
-                if self.count_even && roll.final_roll % 2 == 0 {
-                    evens += 1;
-                }
-                if self.count_odd && roll.final_roll % 2 != 0 {
-                    odds += 1;
-                }
-            }
-
-            success_counting_rules_applied.push(SuccessCountingRulesApplied::new(
-                roll.group,
-                roll.sign,
-                roll.roll_number,
-                roll.dice_size,
-                roll.final_roll,
-                roll.discarded_rolls.clone(),
-                roll.exploded_rolls.clone(),
-                roll.subtotal,
-                roll.kept,
-                roll.replaced_roll,
-                success,
-                failure,
-                subtracted > 0,
-                deduction,
-            ));
-        }
-
-        success_counting_rules_applied.sort_by(|a, b| a.roll_number.cmp(&b.roll_number));
-
-        SuccessCountingAfterResultKeeping::new(
-            success_counting_rules_applied,
-            total_deducted,
-            total_subtracted,
-            self.margin_of_success,
-            modifier,
-            successes,
-            failures,
-            evens,
-            odds,
-        )
-    }
-
-    fn check_success(&self, roll_value: u32) -> Option<bool> {
+    pub fn check_success(&self, roll_value: u32) -> Option<bool> {
         self.count_success
             .map(|operator| apply_operator(operator, &roll_value))
     }
 
-    fn check_failure(&self, roll_value: u32) -> Option<bool> {
+    pub fn check_failure(&self, roll_value: u32) -> Option<bool> {
         self.count_failure
             .map(|operator| apply_operator(operator, &roll_value))
     }
 
-    fn calculate_deductions(
+    pub fn calculate_deductions(
         &self,
         roll_value: u32,
         success: Option<bool>,
@@ -137,11 +59,10 @@ impl SuccessCountingRules {
         let mut total_subtracted = 0;
         let mut total_deducted = 0;
 
-        if self.subtract_failure {
-            if matches!(success, Some(false)) || matches!(failure, Some(true)) {
+        if self.subtract_failure
+            && (matches!(success, Some(false)) || matches!(failure, Some(true))) {
                 total_subtracted += roll_value;
             }
-        }
 
         if let Some(value) = self.deduct_failure {
             if matches!(success, Some(false)) || matches!(failure, Some(true)) {
@@ -153,7 +74,91 @@ impl SuccessCountingRules {
     }
 }
 
-#[derive(Clone)]
+// every roll is counted with the success rules of its own dice group,
+// the margin of success applies once to the whole roll
+pub fn count_successes(
+    result_keeping_rules_applied: &mut [ResultKeepingRulesApplied],
+    flat_modifiers: Vec<FlatModifier>,
+    margin_of_success: u32,
+) -> Result<SuccessCountingAfterResultKeeping, DiceError> {
+    let mut success_counting_rules_applied: Vec<SuccessCountingRulesApplied> = vec![];
+    let mut evens = 0;
+    let mut odds = 0;
+    let mut total_subtracted = 0;
+    let mut total_deducted = 0;
+    let mut successes = 0;
+    let mut failures = 0;
+
+    for roll in result_keeping_rules_applied {
+        let rules = roll.success_counting_rules;
+        let success = rules.check_success(roll.final_roll);
+        let failure = rules.check_failure(roll.final_roll);
+        let (subtracted, deduction) =
+            rules.calculate_deductions(roll.final_roll, success, failure);
+
+        if roll.kept {
+            if let Some(true) = success {
+                successes += 1;
+            } else if let Some(false) = success {
+                failures += 1;
+            }
+
+            if let Some(true) = failure {
+                failures += 1;
+            } else if let Some(false) = failure {
+                successes += 1;
+            }
+
+            // subtracting failures on large dice can add up past a u32
+            let too_large = || DiceError::Overflow("the total".to_string());
+            total_subtracted =
+                u32::checked_add(total_subtracted, subtracted).ok_or_else(too_large)?;
+            total_deducted = u32::checked_add(total_deducted, deduction).ok_or_else(too_large)?;
+
+            // Count even and odd rolls
+            if rules.count_even && roll.final_roll % 2 == 0 {
+                evens += 1;
+            }
+            if rules.count_odd && roll.final_roll % 2 != 0 {
+                odds += 1;
+            }
+        }
+
+        success_counting_rules_applied.push(SuccessCountingRulesApplied::new(
+            roll.group,
+            roll.sign,
+            roll.roll_number,
+            roll.dice_size,
+            roll.final_roll,
+            roll.discarded_rolls.clone(),
+            roll.exploded_rolls.clone(),
+            roll.subtotal,
+            roll.capped,
+            roll.kept,
+            roll.replaced_roll,
+            success,
+            failure,
+            subtracted > 0,
+            deduction,
+        ));
+    }
+
+    success_counting_rules_applied.sort_by_key(|roll| roll.roll_number);
+
+    SuccessCountingAfterResultKeeping::new(
+        success_counting_rules_applied,
+        total_deducted,
+        total_subtracted,
+        margin_of_success,
+        flat_modifiers,
+        successes,
+        failures,
+        evens,
+        odds,
+    )
+}
+
+#[derive(Clone, Debug)]
 pub struct SuccessCountingRulesApplied {
     pub group: i32,
     pub sign: i32,
@@ -163,6 +168,7 @@ pub struct SuccessCountingRulesApplied {
     pub discarded_rolls: Vec<u32>,
     pub exploded_rolls: Vec<u32>,
     pub subtotal: i32,
+    pub capped: bool,
     pub kept: bool,
     pub replaced_roll: Option<u32>,
     pub success: Option<bool>,
@@ -172,6 +178,7 @@ pub struct SuccessCountingRulesApplied {
 }
 
 impl SuccessCountingRulesApplied {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         group: i32,
         sign: i32,
@@ -181,6 +188,7 @@ impl SuccessCountingRulesApplied {
         discarded_rolls: Vec<u32>,
         exploded_rolls: Vec<u32>,
         subtotal: i32,
+        capped: bool,
         kept: bool,
         replaced_roll: Option<u32>,
         success: Option<bool>,
@@ -197,6 +205,7 @@ impl SuccessCountingRulesApplied {
             discarded_rolls,
             exploded_rolls,
             subtotal,
+            capped,
             kept,
             replaced_roll,
             success,
@@ -207,83 +216,17 @@ impl SuccessCountingRulesApplied {
     }
 }
 
-impl VerboseTableDisplay for SuccessCountingRulesApplied {
-    fn verbose_display(self) {
-        let mut table1 = Table::new();
-
-        let added = self.sign > 0;
-
-        let mut header = vec![
-            Cell::new("Dice group"),
-            Cell::new("Dice"),
-            Cell::new("Added"),
-            Cell::new("Roll Number"),
-            Cell::new("Final roll"),
-        ];
-
-        let mut row = vec![
-            Cell::new(self.group),
-            Cell::new(self.dice_size),
-            Cell::new(added),
-            Cell::new(self.roll_number),
-            Cell::new(self.final_roll),
-        ];
-
-        if !self.discarded_rolls.is_empty() {
-            header.push(Cell::new("Discarded rolls from re-rolling"));
-            row.push(Cell::new(format!("{:?}", self.discarded_rolls)));
-        }
-
-        if !self.exploded_rolls.is_empty() {
-            header.push(Cell::new("Exploded rolls"));
-            row.push(Cell::new(format!("{:?}", self.exploded_rolls)));
-        }
-
-        if let Some(target) = &self.replaced_roll {
-            header.push(Cell::new("Replaced"));
-            row.push(Cell::new(format!(
-                "{} has been replaced with {}",
-                target, self.final_roll
-            )));
-        }
-
-        if !self.kept {
-            header.push(Cell::new("Kept"));
-            row.push(Cell::new(self.kept));
-        }
-
-        let success: Option<&str>;
-        match (self.success, self.failure) {
-            (Some(true), Some(false)) => success = Some("true"),
-            (Some(false), Some(true)) => success = Some("false"),
-            (_, _) => success = None,
-        }
-
-        if let Some(target) = success {
-            header.push(Cell::new("Success"));
-            row.push(Cell::new(target));
-        }
-
-        if self.deductions > 0 {
-            header.push(Cell::new("Deductions from final roll"));
-            row.push(Cell::new(self.deductions));
-        }
-
-        if self.subtracted {
-            header.push(Cell::new("Subtracted from final roll"));
-            row.push(Cell::new(self.subtracted));
-        }
-
-        header.push(Cell::new("Subtotal"));
-        row.push(Cell::new(self.subtotal));
+impl SuccessCountingRulesApplied {
+    // kept rolls that succeeded, or that had no success rule to pass, count towards the total
+    pub fn adds_to_total(&self) -> bool {
+        self.kept
+            && ((self.success == Some(true) || self.failure == Some(false))
+                || (self.success.is_none() && self.failure.is_none()))
+    }
 
-        table1
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_width(160)
-            .set_header(header)
-            .add_row(row);
-        println!("{table1}");
+    // whether the roll passed its success or failure rule, None when there's neither
+    pub fn succeeded(&self) -> Option<bool> {
+        self.success.or(self.failure.map(|failure| !failure))
     }
 }
 
@@ -306,14 +249,26 @@ mod tests {
             vec![],          // discarded
             vec![],          // exploded
             final_roll as i32, // subtotal
+            false,           // capped
             kept,
             None,            // replaced_roll
+            SuccessCountingRules::default(),
         )
     }
 
+    fn with_rules(
+        mut rolls: Vec<ResultKeepingRulesApplied>,
+        rules: SuccessCountingRules,
+    ) -> Vec<ResultKeepingRulesApplied> {
+        for roll in rolls.iter_mut() {
+            roll.success_counting_rules = rules;
+        }
+        rolls
+    }
+
     #[test]
     fn test_count_success_gte_5() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 6, true),
             mock_kept_roll(2, 4, true),
             mock_kept_roll(3, 5, true),
@@ -327,10 +282,9 @@ mod tests {
             false,
             None,
             false,
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.successes, 2); // 6 and 5
         assert_eq!(result.failures, 1);  // 4 is <5 → failure
         assert_eq!(result.evens, 0);
@@ -339,7 +293,7 @@ mod tests {
 
     #[test]
     fn test_count_failure_lte_2() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 1, true),
             mock_kept_roll(2, 3, true),
             mock_kept_roll(3, 2, true),
@@ -353,17 +307,16 @@ mod tests {
             false,
             None,
             false,
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.failures, 2); // 1 and 2
         assert_eq!(result.successes, 1); // 3 is >2 → success
     }
 
     #[test]
     fn test_count_even_and_odd() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 2, true), // even
             mock_kept_roll(2, 3, true), // odd
             mock_kept_roll(3, 4, true), // even
@@ -377,10 +330,9 @@ mod tests {
             true,  // count odd
             None,
             false,
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.evens, 2); // 2,4
         assert_eq!(result.odds, 1);  // 3
         assert_eq!(result.successes, 0);
@@ -389,7 +341,7 @@ mod tests {
 
     #[test]
     fn test_deduct_per_failure() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 1, true), // failure (if success = >=5)
             mock_kept_roll(2, 6, true), // success
             mock_kept_roll(3, 3, true), // failure
@@ -402,10 +354,9 @@ mod tests {
             false,
             Some(2), // deduct 2 per failure
             false,
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.successes, 1);
         assert_eq!(result.failures, 2);
         assert_eq!(result.deductions_from_failure, 4); // 2 failures × 2
@@ -414,7 +365,7 @@ mod tests {
 
     #[test]
     fn test_subtract_entire_roll_on_failure() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 1, true), // failure → subtract 1
             mock_kept_roll(2, 6, true), // success → no subtract
             mock_kept_roll(3, 3, true), // failure → subtract 3
@@ -427,17 +378,16 @@ mod tests {
             false,
             None,
             true, // subtract entire roll on failure
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.subtractions_from_failure, 1 + 3); // 4
         assert_eq!(result.deductions_from_failure, 0);
     }
 
     #[test]
     fn test_only_kept_rolls_counted() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 6, false), // not kept → should not count
             mock_kept_roll(2, 2, true),  // kept, but failure
         ];
@@ -449,17 +399,16 @@ mod tests {
             false,
             None,
             false,
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.successes, 0);
         assert_eq!(result.failures, 1); // only the kept 2
     }
 
     #[test]
     fn test_no_rules_active() {
-        let mut rolls = vec![
+        let rolls = vec![
             mock_kept_roll(1, 4, true),
             mock_kept_roll(2, 5, true),
         ];
@@ -471,10 +420,9 @@ mod tests {
             false,
             None,
             false,
-            0,
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, 0);
+        let result = count_successes(&mut with_rules(rolls, rules), vec![], 0).unwrap();
         assert_eq!(result.successes, 0);
         assert_eq!(result.failures, 0);
         assert_eq!(result.evens, 0);
@@ -485,7 +433,7 @@ mod tests {
 
     #[test]
     fn test_margin_and_modifier_passed_through() {
-        let mut rolls = vec![mock_kept_roll(1, 6, true)];
+        let rolls = vec![mock_kept_roll(1, 6, true)];
 
         let rules = SuccessCountingRules::new(
             Some(Operator::Gte(5)),
@@ -494,25 +442,45 @@ mod tests {
             false,
             None,
             false,
-            10, // margin_of_success = 10
-        );
+        ).unwrap();
 
-        let result = rules.count_successes(&mut rolls, -2); // modifier = -2
+        let result = count_successes(
+            &mut with_rules(rolls, rules),
+            vec![FlatModifier::new(-2, 5, 1)],
+            10, // margin_of_success = 10
+        ).unwrap();
         assert_eq!(result.margin_of_success, 10);
         assert_eq!(result.initial_modifier, -2);
     }
 
     #[test]
-    #[should_panic(expected = "assertion failed")]
+    fn test_each_group_counted_with_its_own_rules() {
+        let mut d20 = mock_kept_roll(1, 15, true);
+        d20.success_counting_rules =
+            SuccessCountingRules::new(Some(Operator::Gte(10)), None, false, false, None, false).unwrap();
+        let mut d6 = mock_kept_roll(2, 2, true);
+        d6.group = 2;
+        d6.success_counting_rules =
+            SuccessCountingRules::new(None, None, true, false, None, false).unwrap();
+
+        let result = count_successes(&mut [d20, d6], vec![], 0).unwrap();
+        assert_eq!(result.successes, 1);
+        assert_eq!(result.failures, 0);
+        assert_eq!(result.evens, 1);
+        assert!(result.rolls.iter().all(|roll| roll.adds_to_total()));
+    }
+
+    #[test]
     fn test_cannot_have_both_success_and_failure_rules() {
-        SuccessCountingRules::new(
+        let error = SuccessCountingRules::new(
             Some(Operator::Gte(5)),
             Some(Operator::Lte(2)),
             false,
             false,
             None,
             false,
-            0,
-        );
+        )
+        .unwrap_err();
+        assert!(matches!(error, DiceError::ConflictingOptions(_)));
     }
 }
\ No newline at end of file
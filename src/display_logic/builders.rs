@@ -1,89 +1,175 @@
+use crate::dice_rolling_logic::repeated_roll::{SetRules, SortOrder};
 use crate::dice_rolling_logic::result_keeping_rules::ResultKeepingRules;
-use crate::dice_rolling_logic::roll_command::DiceRollCommand;
+use crate::dice_rolling_logic::roll_command::{
+    DiceRollCommand, RollLimits, DEFAULT_MAX_EXPLOSIONS, DEFAULT_MAX_RE_ROLLS,
+};
 use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRules;
 use crate::enums::Operator;
-use crate::utils::{parse_number, parse_operator, yn_tf_to_bool};
-use regex::Regex;
-
-pub fn parse_dice(dice_expr: &str) -> (u32, u32) {
-    let re = Regex::new(r"(\d+)d(\d+)").unwrap();
-    if let Some(caps) = re.captures(dice_expr) {
-        let count = caps.get(1).unwrap().as_str().parse::<u32>().unwrap();
-        let sides = caps.get(2).unwrap().as_str().parse::<u32>().unwrap();
-        (count, sides)
-    } else {
-        panic!("Invalid dice expression: {}", dice_expr);
-    }
-}
+use crate::errors::DiceError;
+use crate::parsing_logic::ast::{DiceGroup, ModifierKind, RollExpression};
+use crate::parsing_logic::parse_error::ParseError;
+use crate::utils::{apply_operator, parse_operator, yn_tf_to_bool};
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_dice_roll_commands(
-    dice_roll: String,
+    expression: &RollExpression,
     re_roll: Option<String>,
     re_roll_recursively: Option<String>,
     explode: Option<String>,
     explode_once: Option<String>,
-) -> (Vec<DiceRollCommand>, i32) {
-    let re_roll_number: u32;
-    let re_roll_input = match re_roll {
-        None => {
-            re_roll_number = 0;
-            None
-        }
-        Some(input) => {
-            re_roll_number = parse_number(&input);
-            parse_operator(&input)
-        }
+    result_keeping_rules: ResultKeepingRules,
+    success_counting_rules: SuccessCountingRules,
+    limits: RollLimits,
+) -> Result<Vec<DiceRollCommand>, DiceError> {
+    let re_roll_input = re_roll.map(|input| parse_operator(&input)).transpose()?;
+    let re_roll_recursively_input = yn_tf_to_bool(re_roll_recursively)?;
+    let explode_input = explode.map(|input| parse_operator(&input)).transpose()?;
+    let explode_once_input = yn_tf_to_bool(explode_once)?;
+
+    // the CLI flags only apply to the dice groups that can actually roll their target
+    let groups = expression.dice_groups();
+    let fits_any_group = |input: Option<Operator>| {
+        input.is_none_or(|target| {
+            groups
+                .iter()
+                .any(|(_, dice)| target.target() <= dice.dice_size)
+        })
     };
+    if !fits_any_group(re_roll_input) {
+        return Err(DiceError::TargetOutOfRange(
+            "re-roll number exceeds maximum dice size".to_string(),
+        ));
+    }
+    if !fits_any_group(explode_input) {
+        return Err(DiceError::TargetOutOfRange(
+            "explode number exceeds maximum dice size".to_string(),
+        ));
+    }
 
-    let re_roll_recursively_input = yn_tf_to_bool(re_roll_recursively);
+    let mut result = vec![];
+    for (sign, dice) in groups.iter().copied() {
+        let fits_group = |target: &Operator| target.target() <= dice.dice_size;
+        let mut re_roll_group = re_roll_input.filter(fits_group);
+        let mut re_roll_recursively_group = re_roll_recursively_input;
+        let mut explode_group = explode_input.filter(fits_group);
+        let mut explode_once_group = explode_once_input;
+
+        // inline r/rr and x/xo replace the matching CLI flags for this dice group
+        let mut inline_re_roll = None;
+        let mut inline_explode = None;
+        for modifier in &dice.modifiers {
+            let (target, position) = match modifier.kind {
+                ModifierKind::ReRoll(target) | ModifierKind::ReRollRecursively(target) => {
+                    if inline_re_roll.replace(modifier.position).is_none() {
+                        re_roll_group = Some(Operator::Eq(1));
+                        re_roll_recursively_group = false;
+                    }
+                    if let Some(target) = target {
+                        re_roll_group = Some(target);
+                    }
+                    if matches!(modifier.kind, ModifierKind::ReRollRecursively(_)) {
+                        re_roll_recursively_group = true;
+                    }
+                    (re_roll_group, modifier.position)
+                }
+                ModifierKind::Explode(target) | ModifierKind::ExplodeOnce(target) => {
+                    if inline_explode.replace(modifier.position).is_none() {
+                        explode_group = Some(Operator::Eq(dice.dice_size));
+                        explode_once_group = false;
+                    }
+                    if let Some(target) = target {
+                        explode_group = Some(target);
+                    }
+                    if matches!(modifier.kind, ModifierKind::ExplodeOnce(_)) {
+                        explode_once_group = true;
+                    }
+                    (explode_group, modifier.position)
+                }
+                _ => continue,
+            };
+            if target.is_some_and(|target| target.target() > dice.dice_size) {
+                return Err(ParseError::new(
+                    position,
+                    format!("target exceeds maximum dice size of d{}", dice.dice_size),
+                )
+                .into());
+            }
+        }
 
-    let explode_number: u32;
-    let explode_input = match explode {
-        None => {
-            explode_number = 0;
-            None
+        // conditions every face meets would re-roll or explode forever
+        let never_stops = |target: Option<Operator>, keeps_going: bool| {
+            keeps_going
+                && target.is_some_and(|target| {
+                    (1..=dice.dice_size).all(|face| apply_operator(target, &face))
+                })
+        };
+        if never_stops(re_roll_group, re_roll_recursively_group) {
+            let message = format!(
+                "every face of a d{} re-rolls, so re-rolling recursively would never stop",
+                dice.dice_size
+            );
+            return Err(match inline_re_roll {
+                Some(position) => ParseError::new(position, message).into(),
+                None => DiceError::NeverStops(message),
+            });
         }
-        Some(input) => {
-            explode_number = parse_number(&input);
-            parse_operator(&input)
+        if never_stops(explode_group, !explode_once_group) {
+            let message = format!(
+                "every face of a d{} explodes, so exploding would never stop",
+                dice.dice_size
+            );
+            return Err(match inline_explode {
+                Some(position) => ParseError::new(position, message).into(),
+                None => DiceError::NeverStops(message),
+            });
         }
-    };
 
-    let explode_once_input = yn_tf_to_bool(explode_once);
+        result.push(DiceRollCommand::new(
+            dice.group,
+            sign,
+            dice.dice_count,
+            dice.dice_size,
+            re_roll_group,
+            re_roll_recursively_group,
+            explode_group,
+            explode_once_group,
+            group_result_keeping_rules(dice, result_keeping_rules)?,
+            group_success_counting_rules(dice, success_counting_rules)?,
+            limits,
+        ));
+    }
+    Ok(result)
+}
 
-    // This regex matches both dice expressions and numeric modifiers
-    let re = Regex::new(r"([+-]?\d+d\d+)|([+-]?\d+)").unwrap();
-    let mut result = vec![];
-    let mut modifier: i32 = 0;
-    let mut group: i32 = 1;
-    for caps in re.captures_iter(&dice_roll.to_lowercase()) {
-        let token = caps.get(0).unwrap().as_str();
-        if token.contains('d') {
-            let sign = if token.starts_with('-') { -1 } else { 1 };
-            let clean_token = token.trim_start_matches(['+', '-']);
-            let count_and_sides = parse_dice(clean_token);
-            if re_roll_number > count_and_sides.1 {
-                panic!("re-roll number exceeds maximum dice size")
-            }
-            if explode_number > count_and_sides.1 {
-                panic!("explode number exceeds maximum dice size")
-            }
-            result.push(DiceRollCommand::new(
-                group,
-                sign,
-                count_and_sides.0,
-                count_and_sides.1,
-                re_roll_input,
-                re_roll_recursively_input,
-                explode_input,
-                explode_once_input,
-            ));
-            group += 1;
-        } else {
-            modifier = token.parse::<i32>().unwrap()
+pub fn build_roll_limits(max_re_rolls: Option<u32>, max_explosions: Option<u32>) -> RollLimits {
+    RollLimits::new(
+        max_re_rolls.unwrap_or(DEFAULT_MAX_RE_ROLLS),
+        max_explosions.unwrap_or(DEFAULT_MAX_EXPLOSIONS),
+    )
+}
+
+// what a repeated dice roll's set has to meet, and how its totals are sorted
+pub fn build_set_rules(
+    sort: Option<String>,
+    min_sum: Option<i64>,
+    min_highest: Option<i32>,
+) -> Result<SetRules, DiceError> {
+    let sort = match sort.map(|sort| sort.to_lowercase()).as_deref() {
+        None => None,
+        Some("asc") => Some(SortOrder::Ascending),
+        Some("desc") => Some(SortOrder::Descending),
+        Some(other) => {
+            return Err(DiceError::InvalidOption(format!(
+                "'{}' isn't a sort order, use asc or desc",
+                other
+            )))
         }
-    }
-    (result, modifier)
+    };
+    Ok(SetRules {
+        min_sum,
+        min_highest,
+        sort,
+    })
 }
 
 pub fn build_result_keeping_rules(
@@ -93,7 +179,8 @@ pub fn build_result_keeping_rules(
     drop_low: Option<u32>,
     max: Option<u32>,
     min: Option<u32>,
-) -> ResultKeepingRules {
+    pool: Option<String>,
+) -> Result<ResultKeepingRules, DiceError> {
     let count_keeping_options = [
         keep_high.is_some(),
         keep_low.is_some(),
@@ -103,75 +190,114 @@ pub fn build_result_keeping_rules(
     .iter()
     .filter(|&&x| x)
     .count();
-    assert!(
-        count_keeping_options <= 1,
-        "Only one of keep_high, keep_low, drop_high, drop_low can be used"
-    );
+    if count_keeping_options > 1 {
+        return Err(DiceError::ConflictingOptions(
+            "Only one of keep_high, keep_low, drop_high, drop_low can be used".to_string(),
+        ));
+    }
 
     let count_min_max_options = [max.is_some(), min.is_some()]
         .iter()
         .filter(|&&x| x)
         .count();
-    assert!(
-        count_min_max_options <= 1,
-        "Only one of max or min can be used"
-    );
+    if count_min_max_options > 1 {
+        return Err(DiceError::ConflictingOptions(
+            "Only one of max or min can be used".to_string(),
+        ));
+    }
 
     let keeping_rule = keep_high
         .map(|value| ("keep_high", value))
         .or_else(|| keep_low.map(|value| ("keep_low", value)))
         .or_else(|| drop_high.map(|value| ("drop_high", value)))
-        .or_else(|| drop_low.map(|value| ("drop_low", value)))
-        .or(None);
-
-    let keep_input: bool;
-    let high_input: bool;
-    let keep_or_drop_count_input: u32;
-
-    if let Some((rule, value)) = keeping_rule {
-        if rule.contains("keep") {
-            keep_input = true;
-        } else {
-            keep_input = false;
-        }
-        if rule.contains("high") {
-            high_input = true;
-        } else {
-            high_input = false;
-        }
-        keep_or_drop_count_input = value
-    } else {
-        keep_input = false;
-        high_input = false;
-        keep_or_drop_count_input = 0;
-    }
-
-    let be_replaced_with_input: Option<u32>;
-    let min_input: bool;
+        .or_else(|| drop_low.map(|value| ("drop_low", value)));
 
     let min_max_rule = min
         .map(|value| ("min", value))
-        .or_else(|| max.map(|value| ("max", value)))
-        .or(None);
-
-    if let Some((rule, value)) = min_max_rule {
-        if rule == "min" {
-            min_input = true;
-        } else {
-            min_input = false;
+        .or_else(|| max.map(|value| ("max", value)));
+
+    let mut rules = result_keeping_rules_from(keeping_rule, min_max_rule);
+    rules.pool = yn_tf_to_bool(pool)?;
+    // inline keep/drop takes a group out of the pool, so pool would silently do nothing
+    if rules.pool && keeping_rule.is_none() {
+        return Err(DiceError::InvalidOption(
+            "pool needs one of keep_high, keep_low, drop_high or drop_low to keep/drop by"
+                .to_string(),
+        ));
+    }
+    Ok(rules)
+}
+
+// inline keep/drop and min/max replace the CLI rules for this dice group,
+// each of them can only be written once per group
+fn group_result_keeping_rules(
+    dice: &DiceGroup,
+    defaults: ResultKeepingRules,
+) -> Result<ResultKeepingRules, ParseError> {
+    let mut inline_keeping_rule = None;
+    let mut inline_min_max_rule = None;
+    for modifier in &dice.modifiers {
+        let (slot, rule, description) = match modifier.kind {
+            ModifierKind::KeepHigh(value) => {
+                (&mut inline_keeping_rule, ("keep_high", value), "keep/drop")
+            }
+            ModifierKind::KeepLow(value) => {
+                (&mut inline_keeping_rule, ("keep_low", value), "keep/drop")
+            }
+            ModifierKind::DropHigh(value) => {
+                (&mut inline_keeping_rule, ("drop_high", value), "keep/drop")
+            }
+            ModifierKind::DropLow(value) => {
+                (&mut inline_keeping_rule, ("drop_low", value), "keep/drop")
+            }
+            ModifierKind::Min(value) => (&mut inline_min_max_rule, ("min", value), "min/max"),
+            ModifierKind::Max(value) => (&mut inline_min_max_rule, ("max", value), "min/max"),
+            _ => continue,
+        };
+        if slot.replace(rule).is_some() {
+            return Err(ParseError::new(
+                modifier.position,
+                format!("only one {} modifier can be used per dice group", description),
+            ));
         }
-        be_replaced_with_input = Some(value)
-    } else {
-        be_replaced_with_input = None;
-        min_input = false;
     }
 
+    let inline = result_keeping_rules_from(inline_keeping_rule, inline_min_max_rule);
+    let mut rules = defaults;
+    if inline_keeping_rule.is_some() {
+        rules.keep = inline.keep;
+        rules.high = inline.high;
+        rules.keep_or_drop_count = inline.keep_or_drop_count;
+        rules.pool = false;
+    }
+    if inline_min_max_rule.is_some() {
+        rules.be_replaced_with = inline.be_replaced_with;
+        rules.min = inline.min;
+    }
+    Ok(rules)
+}
+
+fn result_keeping_rules_from(
+    keeping_rule: Option<(&str, u32)>,
+    min_max_rule: Option<(&str, u32)>,
+) -> ResultKeepingRules {
+    let (keep_input, high_input, keep_or_drop_count_input) = match keeping_rule {
+        Some((rule, value)) => (rule.contains("keep"), rule.contains("high"), value),
+        None => (false, false, 0),
+    };
+
+    let (be_replaced_with_input, min_input) = match min_max_rule {
+        Some((rule, value)) => (Some(value), rule == "min"),
+        None => (None, false),
+    };
+
     ResultKeepingRules::new(
         keep_input,
         high_input,
         keep_or_drop_count_input,
         be_replaced_with_input,
         min_input,
+        false,
     )
 }
 
@@ -182,8 +308,7 @@ pub fn build_success_counting_rules(
     odd: Option<String>,
     deduct_failure: Option<u32>,
     subtract_failures: Option<String>,
-    margin_of_success: Option<u32>,
-) -> SuccessCountingRules {
+) -> Result<SuccessCountingRules, DiceError> {
     let count_success_or_failure_options = [
         count_success.is_some(),
         count_failure.is_some(),
@@ -192,96 +317,149 @@ pub fn build_success_counting_rules(
     .iter()
     .filter(|&&x| x)
     .count();
-    assert!(
-        count_success_or_failure_options <= 1,
-        "Only one of count_success, subtract_failures or count_failure can be used"
-    );
-
-    let count_success_input: Option<Operator>;
-    let count_failure_input: Option<Operator>;
-    let subtract_failures_input: bool;
+    if count_success_or_failure_options > 1 {
+        return Err(DiceError::ConflictingOptions(
+            "Only one of count_success, subtract_failures or count_failure can be used"
+                .to_string(),
+        ));
+    }
 
     let count_success_rule = count_success
         .map(|value| ("count_success", value))
         .or_else(|| count_failure.map(|value| ("count_failure", value)))
         .or_else(|| subtract_failures.map(|value| ("subtract_failure", value)))
-        .or_else(|| None);
-
-    if let Some((rule, value)) = count_success_rule {
-        if rule == "count_success" {
-            count_success_input = parse_operator(&value);
-            count_failure_input = None;
-            subtract_failures_input = false;
-        } else if rule == "count_failure" {
-            count_success_input = None;
-            count_failure_input = parse_operator(&value);
-            subtract_failures_input = false;
-        } else {
-            count_success_input = None;
-            count_failure_input = parse_operator(&value);
-            subtract_failures_input = true;
-        }
-    } else {
-        count_success_input = None;
-        count_failure_input = None;
-        subtract_failures_input = false;
-    }
-
-    let even_input = yn_tf_to_bool(even);
+        .map(|(rule, value)| parse_operator(&value).map(|operator| (rule, operator)))
+        .transpose()?;
 
-    let odd_input = yn_tf_to_bool(odd);
+    let (count_success_input, count_failure_input, subtract_failures_input) =
+        success_rule_from(count_success_rule);
 
     SuccessCountingRules::new(
         count_success_input,
         count_failure_input,
-        even_input,
-        odd_input,
+        yn_tf_to_bool(even)?,
+        yn_tf_to_bool(odd)?,
         deduct_failure,
         subtract_failures_input,
-        margin_of_success.unwrap_or(0u32),
     )
 }
 
+// inline cs/cf/sf, even/odd and df replace the CLI rules for this dice group
+fn group_success_counting_rules(
+    dice: &DiceGroup,
+    defaults: SuccessCountingRules,
+) -> Result<SuccessCountingRules, ParseError> {
+    let mut rules = defaults;
+    let mut inline_count_success_rule = None;
+    for modifier in &dice.modifiers {
+        let rule = match modifier.kind {
+            ModifierKind::CountSuccess(operator) => ("count_success", operator),
+            ModifierKind::CountFailure(operator) => ("count_failure", operator),
+            ModifierKind::SubtractFailure(operator) => ("subtract_failure", operator),
+            ModifierKind::Even => {
+                rules.count_even = true;
+                continue;
+            }
+            ModifierKind::Odd => {
+                rules.count_odd = true;
+                continue;
+            }
+            ModifierKind::DeductFailure(value) => {
+                rules.deduct_failure = Some(value);
+                continue;
+            }
+            _ => continue,
+        };
+        if inline_count_success_rule.replace(rule).is_some() {
+            return Err(ParseError::new(
+                modifier.position,
+                "only one of cs, cf or sf can be used per dice group",
+            ));
+        }
+    }
+    if inline_count_success_rule.is_some() {
+        (rules.count_success, rules.count_failure, rules.subtract_failure) =
+            success_rule_from(inline_count_success_rule);
+    }
+    Ok(rules)
+}
+
+fn success_rule_from(rule: Option<(&str, Operator)>) -> (Option<Operator>, Option<Operator>, bool) {
+    match rule {
+        Some(("count_success", operator)) => (Some(operator), None, false),
+        Some(("count_failure", operator)) => (None, Some(operator), false),
+        Some((_, operator)) => (None, Some(operator), true),
+        None => (None, None, false),
+    }
+}
+
+// the margin of success is taken off the total once, so it can only be written once per roll
+pub fn build_margin_of_success(
+    expression: &RollExpression,
+    margin_of_success: Option<u32>,
+) -> Result<u32, DiceError> {
+    let mut inline_margin_of_success = None;
+    for modifier in expression.modifiers() {
+        if let ModifierKind::MarginOfSuccess(value) = modifier.kind {
+            if inline_margin_of_success.replace(value).is_some() {
+                return Err(ParseError::new(
+                    modifier.position,
+                    "only one ms modifier can be used per roll",
+                )
+                .into());
+            }
+        }
+    }
+    Ok(inline_margin_of_success
+        .or(margin_of_success)
+        .unwrap_or(0u32))
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::enums::Operator;
+    use crate::parsing_logic::parser::parse_expression;
 
-    // --- parse_dice tests ---
-
-    #[test]
-    fn test_parse_dice_simple() {
-        assert_eq!(parse_dice("1d6"), (1, 6));
-        assert_eq!(parse_dice("10d20"), (10, 20));
+    fn expression(input: &str) -> RollExpression {
+        parse_expression(input).unwrap()
     }
 
-    #[test]
-    fn test_parse_dice_large_numbers() {
-        assert_eq!(parse_dice("100d1000"), (100, 1000));
+    fn commands(expression: &RollExpression) -> Result<Vec<DiceRollCommand>, DiceError> {
+        build_dice_roll_commands(
+            expression,
+            None,
+            None,
+            None,
+            None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
     }
 
-    #[test]
-    #[should_panic] // Regex unwrap will panic on non-matching strings if not handled gracefully
-    fn test_parse_dice_invalid_format() {
-        // The regex expects digits 'd' digits.
-        parse_dice("1d");
+    fn parse_error<T: std::fmt::Debug>(result: Result<T, DiceError>) -> ParseError {
+        match result.unwrap_err() {
+            DiceError::InvalidExpression(error) => error,
+            error => panic!("expected an invalid expression, got {:?}", error),
+        }
     }
 
-    #[test]
-    #[should_panic]
-    fn test_parse_dice_no_d() {
-        parse_dice("6");
+    fn modifier(expression: &RollExpression) -> i32 {
+        expression
+            .flat_modifiers()
+            .iter()
+            .map(|modifier| modifier.value)
+            .sum()
     }
 
     // --- build_dice_roll_commands tests ---
 
     #[test]
     fn test_build_dice_roll_commands_single_group_no_mods() {
-        let (commands, modifier) = build_dice_roll_commands(
-            "1d6".to_string(),
-            None, None, None, None
-        );
+        let expression = expression("1d6");
+        let commands = commands(&expression).unwrap();
+        let modifier = modifier(&expression);
         assert_eq!(modifier, 0);
         assert_eq!(commands.len(), 1);
         assert_eq!(commands[0].dice_count, 1);
@@ -292,10 +470,9 @@ mod tests {
 
     #[test]
     fn test_build_dice_roll_commands_single_group_with_modifier() {
-        let (commands, modifier) = build_dice_roll_commands(
-            "2d10+5".to_string(),
-            None, None, None, None
-        );
+        let expression = expression("2d10+5");
+        let commands = commands(&expression).unwrap();
+        let modifier = modifier(&expression);
         assert_eq!(modifier, 5);
         assert_eq!(commands.len(), 1);
         assert_eq!(commands[0].dice_count, 2);
@@ -305,10 +482,9 @@ mod tests {
     #[test]
     fn test_build_dice_roll_commands_negative_group_and_mod() {
         // "-2d6-3"
-        let (commands, modifier) = build_dice_roll_commands(
-            "-2d6-3".to_string(),
-            None, None, None, None
-        );
+        let expression = expression("-2d6-3");
+        let commands = commands(&expression).unwrap();
+        let modifier = modifier(&expression);
         assert_eq!(modifier, -3);
         assert_eq!(commands.len(), 1);
         assert_eq!(commands[0].sign, -1);
@@ -318,10 +494,9 @@ mod tests {
     #[test]
     fn test_build_dice_roll_commands_multiple_groups() {
         // "1d6 + 2d4"
-        let (commands, modifier) = build_dice_roll_commands(
-            "1d6+2d4".to_string(),
-            None, None, None, None
-        );
+        let expression = expression("1d6+2d4");
+        let commands = commands(&expression).unwrap();
+        let modifier = modifier(&expression);
         assert_eq!(modifier, 0);
         assert_eq!(commands.len(), 2);
 
@@ -338,46 +513,65 @@ mod tests {
 
     #[test]
     fn test_build_dice_roll_commands_options_propagation() {
-        let (commands, _) = build_dice_roll_commands(
-            "1d20".to_string(),
+        let commands = build_dice_roll_commands(
+            &expression("1d20"),
             Some("lt5".to_string()),  // re_roll
             Some("y".to_string()),    // re_roll_recursively
             Some("eq20".to_string()), // explode
             Some("n".to_string()),    // explode_once
-        );
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap();
 
         let cmd = &commands[0];
         match cmd.re_roll {
             Some(Operator::Lt(5)) => {},
             _ => panic!("Expected Lt(5)"),
         }
-        assert_eq!(cmd.re_roll_recursively, true);
+        assert!(cmd.re_roll_recursively);
 
         match cmd.explode {
             Some(Operator::Eq(20)) => {},
             _ => panic!("Expected Eq(20)"),
         }
-        assert_eq!(cmd.explode_once, false);
+        assert!(!cmd.explode_once);
     }
 
     #[test]
-    #[should_panic(expected = "re-roll number exceeds maximum dice size")]
-    fn test_panic_reroll_exceeds_sides() {
-        build_dice_roll_commands(
-            "1d6".to_string(),
+    fn test_reroll_exceeds_sides() {
+        let error = build_dice_roll_commands(
+            &expression("1d6"),
             Some("gt7".to_string()),
-            None, None, None
+            None, None, None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            DiceError::TargetOutOfRange("re-roll number exceeds maximum dice size".to_string())
         );
+        assert_eq!(error.exit_code(), 3);
     }
 
     #[test]
-    #[should_panic(expected = "explode number exceeds maximum dice size")]
-    fn test_panic_explode_exceeds_sides() {
-        build_dice_roll_commands(
-            "1d6".to_string(),
+    fn test_explode_exceeds_sides() {
+        let error = build_dice_roll_commands(
+            &expression("1d6"),
             None, None,
             Some("eq7".to_string()),
-            None
+            None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            DiceError::TargetOutOfRange("explode number exceeds maximum dice size".to_string())
         );
     }
 
@@ -385,60 +579,72 @@ mod tests {
 
     #[test]
     fn test_build_result_keeping_rules_keep_high() {
-        let rules = build_result_keeping_rules(Some(3), None, None, None, None, None);
-        assert_eq!(rules.keep, true);
-        assert_eq!(rules.high, true);
+        let rules = build_result_keeping_rules(Some(3), None, None, None, None, None, None).unwrap();
+        assert!(rules.keep);
+        assert!(rules.high);
         assert_eq!(rules.keep_or_drop_count, 3);
     }
 
     #[test]
     fn test_build_result_keeping_rules_keep_low() {
-        let rules = build_result_keeping_rules(None, Some(2), None, None, None, None);
-        assert_eq!(rules.keep, true);
-        assert_eq!(rules.high, false); // keep low
+        let rules = build_result_keeping_rules(None, Some(2), None, None, None, None, None).unwrap();
+        assert!(rules.keep);
+        assert!(!rules.high); // keep low
         assert_eq!(rules.keep_or_drop_count, 2);
     }
 
     #[test]
     fn test_build_result_keeping_rules_drop_high() {
-        let rules = build_result_keeping_rules(None, None, Some(1), None, None, None);
-        assert_eq!(rules.keep, false);
-        assert_eq!(rules.high, true); // drop high
+        let rules = build_result_keeping_rules(None, None, Some(1), None, None, None, None).unwrap();
+        assert!(!rules.keep);
+        assert!(rules.high); // drop high
         assert_eq!(rules.keep_or_drop_count, 1);
     }
 
     #[test]
     fn test_build_result_keeping_rules_drop_low() {
-        let rules = build_result_keeping_rules(None, None, None, Some(4), None, None);
-        assert_eq!(rules.keep, false);
-        assert_eq!(rules.high, false); // drop low
+        let rules = build_result_keeping_rules(None, None, None, Some(4), None, None, None).unwrap();
+        assert!(!rules.keep);
+        assert!(!rules.high); // drop low
         assert_eq!(rules.keep_or_drop_count, 4);
     }
 
     #[test]
     fn test_build_result_keeping_rules_min() {
-        let rules = build_result_keeping_rules(None, None, None, None, None, Some(2));
-        assert_eq!(rules.min, true);
+        let rules = build_result_keeping_rules(None, None, None, None, None, Some(2), None).unwrap();
+        assert!(rules.min);
         assert_eq!(rules.be_replaced_with, Some(2));
     }
 
     #[test]
     fn test_build_result_keeping_rules_max() {
-        let rules = build_result_keeping_rules(None, None, None, None, Some(10), None);
-        assert_eq!(rules.min, false);
+        let rules = build_result_keeping_rules(None, None, None, None, Some(10), None, None).unwrap();
+        assert!(!rules.min);
         assert_eq!(rules.be_replaced_with, Some(10));
     }
 
     #[test]
-    #[should_panic(expected = "Only one of max or min can be used")]
     fn test_build_result_keeping_rules_min_max_conflict() {
-        build_result_keeping_rules(None, None, None, None, Some(10), Some(5));
+        let error = build_result_keeping_rules(None, None, None, None, Some(10), Some(5), None);
+        assert_eq!(
+            error.unwrap_err(),
+            DiceError::ConflictingOptions("Only one of max or min can be used".to_string())
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Only one of keep_high, keep_low, drop_high, drop_low can be used")]
     fn test_build_result_keeping_rules_keep_drop_conflict() {
-        build_result_keeping_rules(Some(1), Some(1), None, None, None, None);
+        let error = build_result_keeping_rules(Some(1), Some(1), None, None, None, None, None);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "Only one of keep_high, keep_low, drop_high, drop_low can be used"
+        );
+    }
+
+    #[test]
+    fn test_build_result_keeping_rules_bad_pool_value() {
+        let error = build_result_keeping_rules(Some(1), None, None, None, None, None, Some("x".to_string()));
+        assert!(matches!(error.unwrap_err(), DiceError::InvalidOption(_)));
     }
 
     // --- build_success_counting_rules tests ---
@@ -446,8 +652,9 @@ mod tests {
     #[test]
     fn test_build_success_counting_rules_count_success() {
         let rules = build_success_counting_rules(
-            Some("gt10".to_string()), None, None, None, None, None, None
-        );
+            Some("gt10".to_string()), None, None, None, None, None
+        )
+        .unwrap();
         match rules.count_success {
             Some(Operator::Gt(10)) => {},
             _ => panic!("Expected Gt(10)"),
@@ -458,8 +665,9 @@ mod tests {
     #[test]
     fn test_build_success_counting_rules_count_failure() {
         let rules = build_success_counting_rules(
-            None, Some("lte1".to_string()), None, None, None, None, None
-        );
+            None, Some("lte1".to_string()), None, None, None, None
+        )
+        .unwrap();
         match rules.count_failure {
             Some(Operator::Lte(1)) => {},
             _ => panic!("Expected Lte(1)"),
@@ -472,13 +680,13 @@ mod tests {
         let rules = build_success_counting_rules(
             None, None, None, None, None,
             Some("lt2".to_string()),
-            None
-        );
+        )
+        .unwrap();
         match rules.count_failure {
             Some(Operator::Lt(2)) => {},
             _ => panic!("Expected Lt(2)"),
         }
-        assert_eq!(rules.subtract_failure, true);
+        assert!(rules.subtract_failure);
         assert!(rules.count_success.is_none());
     }
 
@@ -488,10 +696,11 @@ mod tests {
             None, None,
             Some("y".to_string()), // even
             Some("y".to_string()), // odd
-            None, None, None
-        );
-        assert_eq!(rules.count_even, true);
-        assert_eq!(rules.count_odd, true);
+            None, None
+        )
+        .unwrap();
+        assert!(rules.count_even);
+        assert!(rules.count_odd);
     }
 
     #[test]
@@ -501,19 +710,239 @@ mod tests {
             None, None, None,
             Some(2), // deduct_failure
             None,
-            Some(4)  // margin_of_success
-        );
+        )
+        .unwrap();
         assert_eq!(rules.deduct_failure, Some(2));
-        assert_eq!(rules.margin_of_success, 4);
+        assert_eq!(build_margin_of_success(&expression("1d6"), Some(4)).unwrap(), 4);
     }
 
     #[test]
-    #[should_panic(expected = "Only one of count_success, subtract_failures or count_failure can be used")]
     fn test_build_success_counting_rules_conflict() {
-        build_success_counting_rules(
+        let error = build_success_counting_rules(
             Some("gt10".to_string()),
             Some("lt1".to_string()),
+            None, None, None, None
+        )
+        .unwrap_err();
+        assert!(matches!(error, DiceError::ConflictingOptions(_)));
+    }
+
+    #[test]
+    fn test_build_success_counting_rules_bad_comparison() {
+        let error = build_success_counting_rules(
+            Some("about10".to_string()),
             None, None, None, None, None
-        );
+        )
+        .unwrap_err();
+        assert!(matches!(error, DiceError::InvalidOption(_)));
+    }
+
+    // --- inline modifier tests ---
+
+    #[test]
+    fn test_inline_re_roll_and_explode_override_cli_flags() {
+        let commands = build_dice_roll_commands(
+            &expression("1d20r<3 + 3d8x8xo"),
+            Some("eq1".to_string()),
+            Some("y".to_string()),
+            None,
+            None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap();
+
+        assert_eq!(commands[0].re_roll, Some(Operator::Lt(3)));
+        assert!(!commands[0].re_roll_recursively);
+        assert_eq!(commands[0].explode, None);
+
+        assert_eq!(commands[1].re_roll, Some(Operator::Eq(1)));
+        assert!(commands[1].re_roll_recursively);
+        assert_eq!(commands[1].explode, Some(Operator::Eq(8)));
+        assert!(commands[1].explode_once);
+    }
+
+    #[test]
+    fn test_inline_explode_defaults_to_max_face() {
+        let commands =
+            commands(&expression("2d6x")).unwrap();
+        assert_eq!(commands[0].explode, Some(Operator::Eq(6)));
+        assert!(!commands[0].explode_once);
+    }
+
+    #[test]
+    fn test_inline_target_exceeding_dice_size_is_positioned() {
+        let error = parse_error(commands(&expression("1d6x7")));
+        assert_eq!(error.position, 3);
+    }
+
+    #[test]
+    fn test_inline_keep_and_min() {
+        let commands = build_dice_roll_commands(
+            &expression("4d6kh3min2 + 2d6"),
+            None, None, None, None,
+            build_result_keeping_rules(None, None, None, Some(1), None, None, None).unwrap(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap();
+        let rules = commands[0].result_keeping_rules;
+        assert!(rules.keep);
+        assert!(rules.high);
+        assert_eq!(rules.keep_or_drop_count, 3);
+        assert!(rules.min);
+        assert_eq!(rules.be_replaced_with, Some(2));
+
+        // the CLI flag still applies to the group without inline keep/drop
+        let rules = commands[1].result_keeping_rules;
+        assert!(!rules.keep);
+        assert!(!rules.high);
+        assert_eq!(rules.keep_or_drop_count, 1);
+        assert_eq!(rules.be_replaced_with, None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pool_only_for_groups_without_inline_keep_or_drop() {
+        let commands = build_dice_roll_commands(
+            &expression("2d20 + 1d4 + 4d6dl"),
+            None, None, None, None,
+            build_result_keeping_rules(Some(1), None, None, None, None, None, Some("y".to_string())).unwrap(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap();
+        assert!(commands[0].result_keeping_rules.pool);
+        assert!(commands[1].result_keeping_rules.pool);
+        assert!(!commands[2].result_keeping_rules.pool);
+        let error = build_result_keeping_rules(None, None, None, None, None, None, Some("y".to_string()));
+        assert!(matches!(error.unwrap_err(), DiceError::InvalidOption(_)));
+        assert!(!build_result_keeping_rules(None, None, None, None, None, None, Some("n".to_string())).unwrap().pool);
+    }
+
+    #[test]
+    fn test_inline_keep_is_per_dice_group() {
+        let groups = commands(&expression("2d20kh + 2d6dl")).unwrap();
+        assert!(groups[0].result_keeping_rules.keep);
+        assert!(!groups[1].result_keeping_rules.keep);
+
+        let error = parse_error(commands(&expression("2d20khdl")));
+        assert_eq!(error.position, 6);
+    }
+
+    #[test]
+    fn test_inline_success_counting() {
+        let commands = build_dice_roll_commands(
+            &expression("10d10cs>=8df2even + 1d6"),
+            None, None, None, None,
+            ResultKeepingRules::default(),
+            build_success_counting_rules(None, Some("lt2".to_string()), None, None, None, None).unwrap(),
+            RollLimits::default(),
+        )
+        .unwrap();
+        let rules = commands[0].success_counting_rules;
+        assert_eq!(rules.count_success, Some(Operator::Gte(8)));
+        assert_eq!(rules.count_failure, None);
+        assert_eq!(rules.deduct_failure, Some(2));
+        assert!(rules.count_even);
+        assert!(!rules.count_odd);
+
+        let rules = commands[1].success_counting_rules;
+        assert_eq!(rules.count_success, None);
+        assert_eq!(rules.count_failure, Some(Operator::Lt(2)));
+        assert!(!rules.count_even);
+    }
+
+    #[test]
+    fn test_inline_subtract_failure() {
+        let expression = expression("10d20sf<5ms3");
+        let rules = commands(&expression).unwrap()[0].success_counting_rules;
+        assert_eq!(rules.count_failure, Some(Operator::Lt(5)));
+        assert!(rules.subtract_failure);
+        assert_eq!(build_margin_of_success(&expression, Some(10)).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_inline_margin_of_success_only_once_per_roll() {
+        let error = parse_error(build_margin_of_success(&expression("1d20ms5 + 1d4ms2"), None));
+        assert_eq!(error.position, 13);
+    }
+
+    #[test]
+    fn test_cli_targets_skip_groups_that_cannot_roll_them() {
+        let commands = build_dice_roll_commands(
+            &expression("1d20 + 2d6"),
+            None, None,
+            Some("eq8".to_string()),
+            None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(commands[0].explode, Some(Operator::Eq(8)));
+        assert_eq!(commands[1].explode, None);
+    }
+
+    #[test]
+    fn test_inline_explode_only_on_its_own_group() {
+        let groups = commands(&expression("1d20 + 2d6x")).unwrap();
+        assert_eq!(groups[0].explode, None);
+        assert_eq!(groups[1].explode, Some(Operator::Eq(6)));
+    }
+
+    #[test]
+    fn test_inline_conditions_that_never_stop_are_rejected() {
+        let error = parse_error(commands(&expression("1d6rr<=6")));
+        assert_eq!(error.position, 3);
+        let error = parse_error(commands(&expression("1d20 + 1d1x")));
+        assert_eq!(error.position, 10);
+        assert!(error.message.contains("d1"));
+
+        // once is fine, it can only happen a single time
+        assert!(commands(&expression("1d6r<=6")).is_ok());
+        assert!(commands(&expression("1d1xo")).is_ok());
+    }
+
+    #[test]
+    fn test_cli_explode_on_every_face_is_rejected() {
+        let error = build_dice_roll_commands(
+            &expression("1d6"),
+            None, None,
+            Some("gte1".to_string()),
+            None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            RollLimits::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, DiceError::NeverStops(_)));
+        assert!(error.to_string().starts_with("every face of a d6 explodes"));
+    }
+
+    #[test]
+    fn test_roll_limits_default_and_override() {
+        assert_eq!(build_roll_limits(None, None), RollLimits::default());
+        let commands = build_dice_roll_commands(
+            &expression("1d6x"),
+            None, None, None, None,
+            ResultKeepingRules::default(),
+            SuccessCountingRules::default(),
+            build_roll_limits(Some(5), Some(3)),
+        )
+        .unwrap();
+        assert_eq!(commands[0].limits, RollLimits::new(5, 3));
+    }
+
+    #[test]
+    fn test_build_set_rules() {
+        let rules = build_set_rules(Some("DESC".to_string()), Some(75), None).unwrap();
+        assert_eq!(rules.sort, Some(SortOrder::Descending));
+        assert_eq!(rules.min_sum, Some(75));
+        assert_eq!(build_set_rules(None, None, None).unwrap(), SetRules::default());
+        assert!(matches!(
+            build_set_rules(Some("up".to_string()), None, None),
+            Err(DiceError::InvalidOption(_))
+        ));
+    }
+}
@@ -1,10 +1,37 @@
-use crate::dice_rolling_logic::roll_command::InitialDiceRollResult;
+use crate::config::{config_path, Config, CONFIG_ENV};
+use crate::dice_rolling_logic::distribution::exact_distribution;
+use crate::dice_rolling_logic::random_table::RandomTable;
+use crate::dice_rolling_logic::repeated_roll::{roll_repeated, RollOutcome, SetRules, MAX_REPEAT};
+use crate::dice_rolling_logic::roll_command::DiceRollCommand;
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::dice_rolling_logic::roll_source::{RecordingRollSource, RollSource, ScriptedRollSource};
+use crate::dice_rolling_logic::simulation::{simulate, MAX_SIMULATIONS};
 use crate::display_logic::builders::{
-    build_dice_roll_commands, build_result_keeping_rules, build_success_counting_rules,
+    build_dice_roll_commands, build_margin_of_success, build_result_keeping_rules,
+    build_roll_limits, build_set_rules, build_success_counting_rules,
 };
-use crate::utils::{yn_tf_to_bool, AbridgedTableDisplay, VerboseTableDisplay};
-use clap::Parser;
+use crate::display_logic::colors::color_enabled;
+use crate::display_logic::comparison_output::render_comparison;
+use crate::display_logic::explain::explain;
+use crate::display_logic::histogram::Histogram;
+use crate::display_logic::renderer::{
+    renderer_for, RenderContext, RolledExpression, OUTPUT_FORMATS,
+};
+use crate::display_logic::repl;
+use crate::display_logic::stats_output::{render_all_stats, Target};
+use crate::display_logic::table_style::{terminal_width, TableStyle, MIN_TABLE_WIDTH};
+use crate::errors::DiceError;
+use crate::parsing_logic::ast::RollExpression;
+use crate::parsing_logic::parse_error::ParseError;
+use crate::parsing_logic::parser::parse_expression;
+use crate::roll_commands;
+use crate::utils::yn_tf_to_bool;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use regex::Regex;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
@@ -12,16 +39,61 @@ use regex::Regex;
     about = "rolls dice",
     long_about = "this rolls dice, whaddaya want more bub"
 )]
-#[command(next_line_help = true)]
+#[command(
+    next_line_help = true,
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true
+)]
 pub struct Cli {
+    // without a subcommand the flags are the same as `roll`, eg: dice-roller -d 1d20+5
+    #[command(flatten)]
+    roll: RollArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(
+        about = "roll a dice roll, the same as leaving out the subcommand, example: dice-roller roll -d 1d20+5"
+    )]
+    Roll(RollArgs),
+    #[command(
+        about = "roll one dice roll after another, example: dice-roller repl OR dice-roller repl --cs gte8"
+    )]
+    Repl(RollArgs),
+    #[command(
+        about = "roll on a random table from a file, example: dice-roller table encounters.txt"
+    )]
+    Table(TableArgs),
+    #[command(
+        about = "write a config file with every setting commented out, example: dice-roller init"
+    )]
+    Init(InitArgs),
+    #[command(
+        about = "describe what a dice roll does without rolling it, example: dice-roller explain -d 4d6dl1"
+    )]
+    Explain(RollArgs),
+    #[command(
+        about = "work out the chance of every total without rolling, example: dice-roller stats -d 4d6dl1"
+    )]
+    Stats(StatsArgs),
+}
+
+#[derive(Args)]
+#[command(next_line_help = true)]
+pub struct RollArgs {
     #[
     arg(
             short,
+            value_name = "DICE_ROLL",
             value_parser = validate_dice_roll,
-            help = "example: dice-roller -d 1d20+7"
+            value_delimiter = ';',
+            help = "example: dice-roller -d 1d20+7 OR dice-roller -d 4d6kh3 OR dice-roller -d 'attack: 1d20+5; damage: 2d6+3' (repeat -d or separate with ; to roll several, each can start with a label) OR dice-roller -d 6x4d6dl1 (roll it 6 times)"
     )
     ]
-    dice_roll: Option<String>,
+    pub(crate) dice_rolls: Vec<LabeledRoll>,
 
     #[
     arg(
@@ -33,7 +105,37 @@ pub struct Cli {
             help = "example: dice-roller -d 2d6+9 -v"
     )
     ]
-    verbose: Option<String>, // y/n/t/f or Y/N/T/F
+    pub(crate) verbose: Option<String>, // y/n/t/f or Y/N/T/F
+
+    #[
+    arg(
+            long = "format",
+            value_parser = OUTPUT_FORMATS,
+            help = "example: dice-roller -d 4d6dl1 --format json (table, compact, json, plain or inline, defaults to table with -v and compact without)"
+    )
+    ]
+    pub(crate) format: Option<String>,
+
+    #[
+    arg(
+            long = "width",
+            value_parser = clap::value_parser!(u16).range(MIN_TABLE_WIDTH as i64..),
+            help = "example: dice-roller -d 4d6dl1 -v --width 80 (table width, defaults to the config file, then the terminal's width, then 160)"
+    )
+    ]
+    width: Option<u16>,
+
+    #[
+    arg(
+            long = "ascii",
+            value_parser = validate_yn_tf,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "y",
+            help = "example: dice-roller -d 4d6dl1 -v --ascii (draw tables with +, - and | instead of box drawing characters)"
+    )
+    ]
+    ascii: Option<String>, // y/n/t/f or Y/N/T/F
 
     #[
     arg(
@@ -56,6 +158,15 @@ pub struct Cli {
     ]
     re_roll_recursively: Option<String>, // y/n/t/f or Y/N/T/F
 
+    #[
+    arg(
+            long = "max-rerolls",
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "example: dice-roller -d 1d6rr<3 --max-rerolls 10 (default 100, a die stops re-rolling after this many)"
+    )
+    ]
+    max_re_rolls: Option<u32>,
+
     #[
     arg(
             short,
@@ -77,6 +188,15 @@ pub struct Cli {
     ]
     explode_once: Option<String>,
 
+    #[
+    arg(
+            long = "max-explosions",
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "example: dice-roller -d 3d6x --max-explosions 10 (default 100, a die stops exploding after this many)"
+    )
+    ]
+    max_explosions: Option<u32>,
+
     #[
     arg(
             long = "kh",
@@ -125,6 +245,18 @@ pub struct Cli {
     ]
     drop_low: Option<u32>,
 
+    #[
+    arg(
+            long = "pool",
+            value_parser = validate_yn_tf,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "y",
+            help = "example: dice-roller -d 2d20+1d4 --kh --pool (keep/drop across every dice group instead of within each one)"
+    )
+    ]
+    pool: Option<String>, // y/n/t/f or Y/N/T/F
+
     #[
     arg(
             long = "max",
@@ -214,19 +346,209 @@ pub struct Cli {
     )
     ]
     margin_of_success: Option<u32>,
+
+    #[
+    arg(
+            long = "seed",
+            help = "example: dice-roller -d 4d6dl1 --seed 1234 (the same seed and dice roll always give the same result)"
+    )
+    ]
+    pub(crate) seed: Option<u64>,
+
+    #[
+    arg(
+            long = "rolls",
+            value_delimiter = ',',
+            conflicts_with = "seed",
+            help = "example: dice-roller -d 3d6x --rolls 6,6,2 (use these dice instead of rolling, eg: to replay a roll or enter physical dice)"
+    )
+    ]
+    pub(crate) rolls: Option<Vec<u32>>,
+
+    #[
+    arg(
+            long = "repeat",
+            value_parser = clap::value_parser!(u32).range(1..=MAX_REPEAT as i64),
+            help = "example: dice-roller -d 4d6dl1 --repeat 6 (roll every dice roll 6 times and show the totals, the same as -d 6x4d6dl1)"
+    )
+    ]
+    pub(crate) repeat: Option<u32>,
+
+    #[
+    arg(
+            long = "sort",
+            value_parser = ["asc", "desc"],
+            help = "example: dice-roller -d 6x4d6dl1 --sort desc (sort the totals of a repeated dice roll)"
+    )
+    ]
+    sort: Option<String>,
+
+    #[
+    arg(
+            long = "min-sum",
+            allow_negative_numbers = true,
+            help = "example: dice-roller -d 6x4d6dl1 --min-sum 75 (roll the whole set again until its totals add up to at least 75)"
+    )
+    ]
+    min_sum: Option<i64>,
+
+    #[
+    arg(
+            long = "min-highest",
+            allow_negative_numbers = true,
+            help = "example: dice-roller -d 6x4d6dl1 --min-highest 15 (roll the whole set again until at least one total is 15 or more)"
+    )
+    ]
+    min_highest: Option<i32>,
 }
 
-fn validate_dice_roll(s: &str) -> Result<String, String> {
-    let dice_regex = Regex::new(r"\b\d+d\d+([+-]\d+)?\b").unwrap();
-    if dice_regex.is_match(s) {
-        Ok(s.parse::<String>().unwrap())
-    } else {
-        Err(
-            "Incorrect dice roll format. Correct examples: 1d20+5, 2d6, 1d4-1"
-                .parse()
-                .unwrap(),
+#[derive(Args)]
+struct StatsArgs {
+    #[command(flatten)]
+    roll: RollArgs,
+
+    #[
+    arg(
+            long = "simulate",
+            value_name = "ROLLS",
+            value_parser = clap::value_parser!(u32).range(1..=MAX_SIMULATIONS as i64),
+            help = "example: dice-roller stats -d 3d6x --simulate 100000 (roll it that many times instead of working it out exactly, --seed replays it)"
+    )
+    ]
+    simulate: Option<u32>,
+
+    #[
+    arg(
+            long = "compare",
+            value_name = "DICE_ROLL",
+            num_args = 2..,
+            value_parser = validate_dice_roll,
+            conflicts_with = "dice_rolls",
+            help = "example: dice-roller stats --compare 2d6+3 1d12+4 (each dice roll's mean and spread, how often each beats the others and one histogram with all of them)"
+    )
+    ]
+    compare: Vec<LabeledRoll>,
+
+    #[
+    arg(
+            long = "vs",
+            value_name = "TOTAL",
+            allow_negative_numbers = true,
+            help = "example: dice-roller stats -d 1d20+7 --vs 17 (the chance of a total of 17 or more, and the margin to expect, after --ms and --df)"
+    )
+    ]
+    vs: Option<i64>,
+
+    #[
+    arg(
+            long = "need",
+            value_name = "SUCCESSES",
+            help = "example: dice-roller stats -d 8d10cs>=8 --need 3 (the chance of 3 or more successes, and how many to expect)"
+    )
+    ]
+    need: Option<u32>,
+
+    #[
+    arg(
+            long = "histogram",
+            help = "example: dice-roller stats -d 4d6dl1 --histogram (draw a bar for the chance of each total instead of listing them)"
+    )
+    ]
+    histogram: bool,
+
+    #[
+    arg(
+            long = "cumulative",
+            requires = "histogram",
+            help = "example: dice-roller stats -d 4d6dl1 --histogram --cumulative (shade the chance of rolling at least each total behind its bar)"
+    )
+    ]
+    cumulative: bool,
+}
+
+#[derive(Args)]
+struct TableArgs {
+    #[
+    arg(
+            help = "a text file with an entry on each line, optionally numbered, eg: 1-3: goblins"
+    )
+    ]
+    file: PathBuf,
+
+    #[
+    arg(
+            long = "seed",
+            help = "example: dice-roller table encounters.txt --seed 1234"
+    )
+    ]
+    seed: Option<u64>,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    #[
+    arg(
+            long = "force",
+            help = "example: dice-roller init --force (overwrite a config file that's already there)"
+    )
+    ]
+    force: bool,
+}
+
+// a dice roll from -d with the label and repeat count written in front of it, if any
+#[derive(Clone, Debug)]
+pub struct LabeledRoll {
+    pub label: Option<String>,
+    pub repeat: Option<u32>, // eg: 6 from "6x4d6dl1"
+    pub dice_roll: RollExpression,
+}
+
+// eg: "attack: 1d20+5", "1d20+5" or "stats: 6x4d6dl1", dice rolls never have a colon in them
+// and never start with a number followed by x
+pub fn parse_labeled_roll(s: &str) -> Result<LabeledRoll, ParseError> {
+    let (label, expression) = match s.split_once(':') {
+        Some((label, expression)) => (Some(label.trim()), expression),
+        None => (None, s),
+    };
+    let expression = expression.trim();
+    let repeat_regex = Regex::new(r"^(\d+)\s*[xX]\s*").unwrap();
+    let (repeat, skipped) = match repeat_regex.captures(expression) {
+        Some(captures) => {
+            let repeat = captures[1]
+                .parse::<u32>()
+                .ok()
+                .filter(|repeat| (1..=MAX_REPEAT).contains(repeat))
+                .ok_or_else(|| {
+                    ParseError::new(
+                        0,
+                        format!("a dice roll can be repeated 1 to {} times", MAX_REPEAT),
+                    )
+                })?;
+            (Some(repeat), captures[0].len())
+        }
+        None => (None, 0),
+    };
+    // errors point into the text after the label, repeat count included
+    let dice_roll = parse_expression(&expression[skipped..]).map_err(|mut error| {
+        error.position += skipped;
+        error
+    })?;
+    Ok(LabeledRoll {
+        label: label.filter(|label| !label.is_empty()).map(str::to_string),
+        repeat,
+        dice_roll,
+    })
+}
+
+fn validate_dice_roll(s: &str) -> Result<LabeledRoll, String> {
+    parse_labeled_roll(s).map_err(|error| {
+        let expression = s.split_once(':').map_or(s, |(_, expression)| expression);
+        format!(
+            "{}\n{}\nCorrect examples: 1d20+5, 2d6, 1d4-1, 4d6kh3, 10d10cs>=8, (1d8+4)*2, floor(2d6/2), attack: 1d20+5, 6x4d6dl1",
+            error,
+            error.pointer(expression.trim())
         )
-    }
+    })
 }
 
 fn validate_comparison(s: &str) -> Result<String, String> {
@@ -254,50 +576,597 @@ fn validate_yn_tf(s: &str) -> Result<String, String> {
 pub fn cli_app() {
     let cli = Cli::parse();
 
-    match cli.dice_roll {
-        None => {
-            println!("please enter a dice roll or enter -h or --help for details and examples")
-        }
-        Some(dice_roll) => {
-            let res = build_dice_roll_commands(
-                dice_roll,
-                cli.re_roll,
-                cli.re_roll_recursively,
-                cli.xplode,
-                cli.explode_once,
-            );
-            let result_keeping_rules = build_result_keeping_rules(
-                cli.keep_high,
-                cli.keep_low,
-                cli.drop_high,
-                cli.drop_low,
-                cli.max,
-                cli.min,
-            );
-            let success_keeping_rules = build_success_counting_rules(
-                cli.count_success,
-                cli.count_failure,
-                cli.even,
-                cli.odd,
-                cli.deduct_failure,
-                cli.subtract_failures,
-                cli.margin_of_success,
-            );
-            let commands = res.0;
-            let modifier = res.1;
-            let mut initial_results: Vec<InitialDiceRollResult> = vec![];
-            for command in commands {
-                initial_results.append(&mut command.roll_dice())
+    match &cli.command {
+        None => roll_command(&cli.roll, "-d"),
+        Some(Command::Roll(args)) => roll_command(args, "roll -d"),
+        Some(Command::Repl(args)) => repl::run_repl(args),
+        Some(Command::Table(args)) => exit_on_error(run_table(args), ""),
+        Some(Command::Init(args)) => exit_on_error(run_init(args), ""),
+        Some(Command::Stats(args)) => {
+            if args.compare.is_empty() {
+                required_dice_rolls(&args.roll, "stats -d");
             }
-            let mut secondary_results = result_keeping_rules.process_results(&mut initial_results);
-            let final_results =
-                success_keeping_rules.count_successes(&mut secondary_results, modifier);
-            let verbose = yn_tf_to_bool(cli.verbose);
-            if verbose {
-                final_results.verbose_display()
-            } else {
-                final_results.abridged_display()
+            if let Err((error, source)) = run_stats(args) {
+                fail(error, source)
             }
         }
+        Some(Command::Explain(args)) => {
+            for labeled in required_dice_rolls(args, "explain -d") {
+                exit_on_error(run_explain(args, labeled), &labeled.dice_roll.source)
+            }
+        }
+    }
+}
+
+fn roll_command(args: &RollArgs, usage: &str) {
+    required_dice_rolls(args, usage);
+    run(args)
+}
+
+// -d is optional for repl, every other subcommand needs it
+fn required_dice_rolls<'a>(args: &'a RollArgs, usage: &str) -> &'a [LabeledRoll] {
+    if args.dice_rolls.is_empty() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                format!("please enter a dice roll, eg: dice-roller {} 1d20+5", usage),
+            )
+            .exit()
+    }
+    &args.dice_rolls
+}
+
+fn exit_on_error(result: Result<(), DiceError>, source: &str) {
+    if let Err(error) = result {
+        fail(error, source)
+    }
+}
+
+fn fail(error: DiceError, source: &str) -> ! {
+    report_error(&error, source);
+    std::process::exit(error.exit_code())
+}
+
+// prints the error, pointing at where an expression went wrong
+pub(crate) fn report_error(error: &DiceError, source: &str) {
+    eprintln!("error: {}", error);
+    if let DiceError::InvalidExpression(error) = error {
+        eprintln!("{}", error.pointer(source))
+    }
+}
+
+// rolls every -d in order, printing them together once they've all been rolled
+fn run(cli: &RollArgs) {
+    let verbose = yn_tf_to_bool(cli.verbose.clone()).unwrap_or_else(|error| fail(error, ""));
+    let draws_tables = output_format(cli.format.as_deref(), verbose) == "table";
+    let table_style = table_style(cli, draws_tables).unwrap_or_else(|error| fail(error, ""));
+    check_set_rules(cli).unwrap_or_else(|error| fail(error, ""));
+    // a seed is always picked so that any roll can be replayed with --seed
+    let seed = cli.seed.unwrap_or_else(rand::random);
+    // several dice rolls each get a seed of their own, picked by the one for the whole call
+    let mut seeds = ChaCha8Rng::seed_from_u64(seed);
+    let mut remaining_rolls = cli.rolls.as_deref();
+    let mut rolled = vec![];
+    for labeled in &cli.dice_rolls {
+        let roll_seed = if cli.dice_rolls.len() == 1 {
+            seed
+        } else {
+            seeds.random()
+        };
+        let (outcome, recording) = roll_labeled(cli, labeled, roll_seed, remaining_rolls)
+            .unwrap_or_else(|error| fail(error, &labeled.dice_roll.source));
+        remaining_rolls = remaining_rolls.map(|rolls| &rolls[recording.len()..]);
+        rolled.push((labeled, roll_seed, outcome, recording));
+    }
+    if let Some(rolls) = remaining_rolls.filter(|rolls| !rolls.is_empty()) {
+        eprintln!("warning: {} of the given rolls were not used", rolls.len())
+    }
+
+    let rolls: Vec<RolledExpression> = rolled
+        .iter()
+        .map(|(labeled, roll_seed, outcome, recording)| {
+            let seed = cli.rolls.is_none().then_some(*roll_seed);
+            let mut context =
+                render_context(&labeled.dice_roll, seed, recording, table_style);
+            context.label = labeled.label.as_deref();
+            context.repeat = repeat_count(cli, labeled);
+            RolledExpression { outcome, context }
+        })
+        .collect();
+    let renderer = renderer_for(output_format(cli.format.as_deref(), verbose))
+        .unwrap_or_else(|error| fail(error, ""));
+    print!(
+        "{}",
+        renderer.render_all(&rolls, cli.rolls.is_none().then_some(seed))
+    );
+}
+
+// rolls the dice roll once with every flag applied, returning the result and its output
+pub(crate) fn roll_and_render(
+    cli: &RollArgs,
+    dice_roll: &RollExpression,
+    seed: u64,
+    format: Option<&str>,
+    verbose: bool,
+) -> Result<(SuccessCountingAfterResultKeeping, String), DiceError> {
+    let (result, recording) = roll_once(cli, dice_roll, seed, cli.rolls.as_deref())?;
+    if let Some(rolls) = &cli.rolls {
+        if rolls.len() > recording.len() {
+            eprintln!(
+                "warning: {} of the given rolls were not used",
+                rolls.len() - recording.len()
+            )
+        }
+    }
+    let seed = cli.rolls.is_none().then_some(seed);
+    let format = output_format(format, verbose);
+    let context = render_context(
+        dice_roll,
+        seed,
+        &recording,
+        table_style(cli, format == "table")?,
+    );
+    let output = renderer_for(format)?.render(&result, &context);
+    Ok((result, output))
+}
+
+// a count in front of the dice roll wins over --repeat
+fn repeat_count(cli: &RollArgs, labeled: &LabeledRoll) -> Option<u32> {
+    labeled.repeat.or(cli.repeat)
+}
+
+// --sort, --min-sum and --min-highest would be silently ignored without a repeated dice roll
+fn check_set_rules(cli: &RollArgs) -> Result<(), DiceError> {
+    let has_set_rules = cli.sort.is_some() || cli.min_sum.is_some() || cli.min_highest.is_some();
+    let repeats = cli
+        .dice_rolls
+        .iter()
+        .any(|labeled| repeat_count(cli, labeled).is_some());
+    if has_set_rules && !repeats {
+        return Err(DiceError::ConflictingOptions(
+            "--sort, --min-sum and --min-highest need a repeated dice roll, eg: -d 6x4d6dl1 or --repeat 6"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// rolls a dice roll from -d once, or as many times as it's repeated
+fn roll_labeled(
+    cli: &RollArgs,
+    labeled: &LabeledRoll,
+    seed: u64,
+    given_rolls: Option<&[u32]>,
+) -> Result<(RollOutcome, Vec<u32>), DiceError> {
+    let Some(repeat) = repeat_count(cli, labeled) else {
+        let (result, recording) = roll_once(cli, &labeled.dice_roll, seed, given_rolls)?;
+        return Ok((RollOutcome::Single(result), recording));
+    };
+    let dice_roll = &labeled.dice_roll;
+    let (commands, margin_of_success) = build_commands(cli, dice_roll)?;
+    let rules = build_set_rules(cli.sort.clone(), cli.min_sum, cli.min_highest)?;
+    // every repeat rolls from the same source, so one seed replays the whole set
+    match given_rolls {
+        Some(rolls) => roll_repeated_recorded(
+            dice_roll,
+            &commands,
+            margin_of_success,
+            (repeat, rules),
+            ScriptedRollSource::new(rolls.to_vec()),
+        ),
+        None => roll_repeated_recorded(
+            dice_roll,
+            &commands,
+            margin_of_success,
+            (repeat, rules),
+            ChaCha8Rng::seed_from_u64(seed),
+        ),
+    }
+}
+
+// rolls from the given rolls when there are any, or from the seed, keeping every face
+fn roll_once(
+    cli: &RollArgs,
+    dice_roll: &RollExpression,
+    seed: u64,
+    given_rolls: Option<&[u32]>,
+) -> Result<(SuccessCountingAfterResultKeeping, Vec<u32>), DiceError> {
+    let (commands, margin_of_success) = build_commands(cli, dice_roll)?;
+    match given_rolls {
+        Some(rolls) => roll_recorded(
+            dice_roll,
+            &commands,
+            margin_of_success,
+            ScriptedRollSource::new(rolls.to_vec()),
+        ),
+        None => roll_recorded(
+            dice_roll,
+            &commands,
+            margin_of_success,
+            ChaCha8Rng::seed_from_u64(seed),
+        ),
+    }
+}
+
+// -v is the same as --format table
+fn output_format(format: Option<&str>, verbose: bool) -> &str {
+    format.unwrap_or(if verbose { "table" } else { "compact" })
+}
+
+// the config file only has table settings, so it's only read when a table gets drawn
+fn table_style(cli: &RollArgs, draws_tables: bool) -> Result<TableStyle, DiceError> {
+    let ascii = cli
+        .ascii
+        .clone()
+        .map(|ascii| yn_tf_to_bool(Some(ascii)))
+        .transpose()?;
+    let config = if draws_tables {
+        Config::load()?
+    } else {
+        Config::default()
+    };
+    Ok(TableStyle::resolve(
+        cli.width,
+        ascii,
+        &config,
+        terminal_width(),
+    ))
+}
+
+fn render_context<'a>(
+    expression: &'a RollExpression,
+    seed: Option<u64>,
+    recording: &'a [u32],
+    table_style: TableStyle,
+) -> RenderContext<'a> {
+    let mut context = RenderContext::new(expression, seed, recording);
+    context.color = color_enabled();
+    context.table_style = table_style;
+    context
+}
+
+// the flags folded into a command per dice group, and the margin of success
+fn build_commands(
+    cli: &RollArgs,
+    dice_roll: &RollExpression,
+) -> Result<(Vec<DiceRollCommand>, u32), DiceError> {
+    let result_keeping_rules = build_result_keeping_rules(
+        cli.keep_high,
+        cli.keep_low,
+        cli.drop_high,
+        cli.drop_low,
+        cli.max,
+        cli.min,
+        cli.pool.clone(),
+    )?;
+    let success_counting_rules = build_success_counting_rules(
+        cli.count_success.clone(),
+        cli.count_failure.clone(),
+        cli.even.clone(),
+        cli.odd.clone(),
+        cli.deduct_failure,
+        cli.subtract_failures.clone(),
+    )?;
+    let commands = build_dice_roll_commands(
+        dice_roll,
+        cli.re_roll.clone(),
+        cli.re_roll_recursively.clone(),
+        cli.xplode.clone(),
+        cli.explode_once.clone(),
+        result_keeping_rules,
+        success_counting_rules,
+        build_roll_limits(cli.max_re_rolls, cli.max_explosions),
+    )?;
+
+    let margin_of_success = build_margin_of_success(dice_roll, cli.margin_of_success)?;
+    Ok((commands, margin_of_success))
+}
+
+fn run_explain(cli: &RollArgs, labeled: &LabeledRoll) -> Result<(), DiceError> {
+    let dice_roll = &labeled.dice_roll;
+    let (commands, margin_of_success) = build_commands(cli, dice_roll)?;
+    print!("{}", explain(dice_roll, &commands, margin_of_success));
+    if let Some(repeat) = repeat_count(cli, labeled) {
+        println!("  all of it is rolled {} times, showing each total", repeat);
+    }
+    Ok(())
+}
+
+// errors come with the dice roll they belong to, so the caller can point at it
+fn run_stats(args: &StatsArgs) -> Result<(), (DiceError, &str)> {
+    let cli = &args.roll;
+    // --compare takes the place of -d
+    let dice_rolls = if args.compare.is_empty() {
+        &cli.dice_rolls
+    } else {
+        &args.compare
+    };
+    let format = stats_format(cli.format.as_deref());
+    let table_style = check_stats_flags(args, dice_rolls, format)
+        .and_then(|_| table_style(cli, format != "json"))
+        .map_err(|error| (error, ""))?;
+    let seed = cli.seed.unwrap_or_else(rand::random);
+    // several dice rolls each get a seed of their own, the same way as when they're rolled
+    let mut seeds = ChaCha8Rng::seed_from_u64(seed);
+    let mut stats = vec![];
+    for labeled in dice_rolls {
+        let dice_roll = &labeled.dice_roll;
+        let roll_seed = if dice_rolls.len() == 1 {
+            seed
+        } else {
+            seeds.random()
+        };
+        let distribution = build_commands(cli, dice_roll)
+            .and_then(|(commands, margin_of_success)| match args.simulate {
+                Some(trials) => {
+                    let mut rng = ChaCha8Rng::seed_from_u64(roll_seed);
+                    simulate(
+                        trials,
+                        || roll_commands(dice_roll, &commands, margin_of_success, &mut rng),
+                        progress_indicator(&dice_roll.source, trials),
+                    )
+                }
+                None => exact_distribution(dice_roll, &commands, margin_of_success),
+            })
+            .map_err(|error| (error, dice_roll.source.as_str()))?;
+        let seed = args.simulate.and(Some(roll_seed));
+        // without a success rule no die ever succeeds, so --need would always be 0%
+        let never_succeeds = !distribution
+            .successes
+            .keys()
+            .any(|&successes| successes > 0);
+        if args.need.is_some() && never_succeeds {
+            return Err((
+                DiceError::ConflictingOptions(
+                    "--need counts successes, add a rule for them, eg: 8d10cs>=8 or --cs gt7"
+                        .to_string(),
+                ),
+                &dice_roll.source,
+            ));
+        }
+        let mut context = render_context(dice_roll, seed, &[], table_style);
+        context.label = labeled.label.as_deref();
+        stats.push((distribution, context));
+    }
+    let histogram = args.histogram.then_some(Histogram {
+        cumulative: args.cumulative,
+    });
+    let target = Target {
+        vs: args.vs,
+        need: args.need,
+    };
+    if args.compare.is_empty() {
+        print!("{}", render_all_stats(&stats, format, histogram, target));
+    } else {
+        print!("{}", render_comparison(&stats, format, histogram, target));
+    }
+    Ok(())
+}
+
+// flags stats can't work with, before anything is worked out
+fn check_stats_flags(
+    args: &StatsArgs,
+    dice_rolls: &[LabeledRoll],
+    format: &str,
+) -> Result<(), DiceError> {
+    let cli = &args.roll;
+    if cli.rolls.is_some() {
+        return Err(DiceError::ConflictingOptions(
+            "--rolls can't be used with stats, nothing is rolled".to_string(),
+        ));
+    }
+    if dice_rolls
+        .iter()
+        .any(|labeled| repeat_count(cli, labeled).is_some())
+    {
+        return Err(DiceError::ConflictingOptions(
+            "stats works out a single dice roll, leave out the repeat count".to_string(),
+        ));
+    }
+    if args.histogram && format == "json" {
+        return Err(DiceError::ConflictingOptions(
+            "--histogram draws a chart, it can't be used with --format json".to_string(),
+        ));
+    }
+    if cli.seed.is_some() && args.simulate.is_none() {
+        return Err(DiceError::ConflictingOptions(
+            "--seed only replays a simulation, add --simulate".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// simulations of at least this many rolls show their progress
+const PROGRESS_FROM: u32 = 100_000;
+
+// shows how far a long simulation got on stderr, eg: simulating 3d6x: 45%,
+// short ones or ones that aren't watched in a terminal don't show anything
+fn progress_indicator(expression: &str, trials: u32) -> impl FnMut(u32) {
+    let shown = trials >= PROGRESS_FROM && std::io::stderr().is_terminal();
+    let line = format!("simulating {}", expression.trim());
+    let step = trials / 100;
+    move |done| {
+        if !shown || done % step != 0 {
+            return;
+        }
+        if done == trials {
+            // clears the line, so only the stats are left
+            eprint!("\r{}\r", " ".repeat(line.len() + 6));
+        } else {
+            eprint!("\r{}: {}%", line, done / step);
+        }
+    }
+}
+
+// stats are tables unless another format is picked, compact and inline are lines of text
+fn stats_format(format: Option<&str>) -> &str {
+    match format {
+        None | Some("table") => "table",
+        Some("json") => "json",
+        Some(_) => "plain",
+    }
+}
+
+fn run_table(args: &TableArgs) -> Result<(), DiceError> {
+    let text = std::fs::read_to_string(&args.file).map_err(|error| {
+        DiceError::InvalidTable(format!("can't read {}: {}", args.file.display(), error))
+    })?;
+    let table = RandomTable::parse(&text)?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let (roll, entry) = table.roll(&mut ChaCha8Rng::seed_from_u64(seed))?;
+    println!("{} on a d{}: {}", roll, table.dice_size, entry.text);
+    println!("Seed: {}", seed);
+    Ok(())
+}
+
+fn run_init(args: &InitArgs) -> Result<(), DiceError> {
+    let path = config_path().ok_or_else(|| {
+        DiceError::InvalidConfig(format!(
+            "there's no home directory to put the config file in, set {}",
+            CONFIG_ENV
+        ))
+    })?;
+    Config::init(&path, args.force)?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+// rolls every dice group from the same source, keeping every face that came up
+fn roll_recorded(
+    dice_roll: &RollExpression,
+    commands: &[DiceRollCommand],
+    margin_of_success: u32,
+    source: impl RollSource,
+) -> Result<(SuccessCountingAfterResultKeeping, Vec<u32>), DiceError> {
+    let mut source = RecordingRollSource::new(source);
+    let final_results = roll_commands(dice_roll, commands, margin_of_success, &mut source)?;
+    Ok((final_results, source.recording))
+}
+
+// the same as roll_recorded, rolling the whole dice roll repeat times over
+fn roll_repeated_recorded(
+    dice_roll: &RollExpression,
+    commands: &[DiceRollCommand],
+    margin_of_success: u32,
+    (repeat, rules): (u32, SetRules),
+    source: impl RollSource,
+) -> Result<(RollOutcome, Vec<u32>), DiceError> {
+    let mut source = RecordingRollSource::new(source);
+    let repeated = roll_repeated(repeat, rules, || {
+        roll_commands(dice_roll, commands, margin_of_success, &mut source)
+    })?;
+    Ok((RollOutcome::Repeated(repeated), source.recording))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labeled_roll() {
+        let labeled = parse_labeled_roll("attack: 1d20+5").unwrap();
+        assert_eq!(labeled.label.as_deref(), Some("attack"));
+        assert_eq!(labeled.dice_roll.source, "1d20+5");
+        assert_eq!(parse_labeled_roll("2d6").unwrap().label, None);
+        assert_eq!(parse_labeled_roll(": 2d6").unwrap().label, None);
+        assert!(parse_labeled_roll("damage: 2d").is_err());
+    }
+
+    #[test]
+    fn test_parse_repeated_roll() {
+        let labeled = parse_labeled_roll("stats: 6x4d6dl1").unwrap();
+        assert_eq!(labeled.label.as_deref(), Some("stats"));
+        assert_eq!(labeled.repeat, Some(6));
+        assert_eq!(labeled.dice_roll.source, "4d6dl1");
+        assert_eq!(parse_labeled_roll("3 X 1d20").unwrap().repeat, Some(3));
+        assert_eq!(parse_labeled_roll("1d20").unwrap().repeat, None);
+        assert_eq!(
+            parse_labeled_roll("0x4d6").unwrap_err().message,
+            "a dice roll can be repeated 1 to 1000 times"
+        );
+        // errors in the dice roll still point at the right place
+        assert_eq!(parse_labeled_roll("6x4d").unwrap_err().position, 4);
+    }
+
+    #[test]
+    fn test_caret_lines_up_after_non_ascii_labels_and_whitespace() {
+        // the label and the no-break space take more bytes than characters
+        let error = parse_labeled_roll("schl\u{e4}ge: 6x\u{a0}4d6+q").unwrap_err();
+        assert_eq!(error.position, 8);
+        assert_eq!(error.pointer("6x\u{a0}4d6+q"), "6x\u{a0}4d6+q\n       ^");
+        let message = validate_dice_roll("schl\u{e4}ge: 6x\u{a0}4d6+q").unwrap_err();
+        assert!(message.contains("\n6x\u{a0}4d6+q\n       ^\n"));
+    }
+
+    #[test]
+    fn test_set_rules_need_a_repeated_roll() {
+        let args = |extra: &[&str]| {
+            let mut args = vec!["dice-roller", "-d", "4d6dl1"];
+            args.extend_from_slice(extra);
+            Cli::try_parse_from(args).unwrap().roll
+        };
+        assert!(matches!(
+            check_set_rules(&args(&["--sort", "desc"])),
+            Err(DiceError::ConflictingOptions(_))
+        ));
+        assert!(check_set_rules(&args(&["--sort", "desc", "--repeat", "6"])).is_ok());
+        assert!(check_set_rules(&args(&[])).is_ok());
+    }
+
+    #[test]
+    fn test_repeated_rolls_replay_from_the_given_rolls() {
+        let cli = Cli::try_parse_from(["dice-roller", "-d", "2x1d6+1", "--min-sum", "8"])
+            .unwrap()
+            .roll;
+        let (outcome, recording) =
+            roll_labeled(&cli, &cli.dice_rolls[0], 0, Some(&[1, 2, 3, 4, 5])).unwrap();
+        assert!(matches!(
+            outcome,
+            RollOutcome::Repeated(ref repeated) if repeated.totals == vec![4, 5] && repeated.sets_rolled == 2
+        ));
+        assert_eq!(recording, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_d_repeats_and_splits_on_semicolons() {
+        let cli = Cli::try_parse_from([
+            "dice-roller",
+            "-d",
+            "attack: 1d20+5; damage: 2d6+3",
+            "-d",
+            "1d4",
+        ])
+        .unwrap();
+        let labels: Vec<Option<&str>> = cli
+            .roll
+            .dice_rolls
+            .iter()
+            .map(|labeled| labeled.label.as_deref())
+            .collect();
+        assert_eq!(labels, vec![Some("attack"), Some("damage"), None]);
+        assert_eq!(cli.roll.dice_rolls[1].dice_roll.source, "2d6+3");
+    }
+
+    #[test]
+    fn test_stats_errors_are_returned_with_their_dice_roll() {
+        let stats = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Some(Command::Stats(args)) => args,
+            _ => unreachable!(),
+        };
+        let args = stats(&["dice-roller", "stats", "-d", "2d6", "--rolls", "1,2"]);
+        assert!(matches!(
+            run_stats(&args),
+            Err((DiceError::ConflictingOptions(_), ""))
+        ));
+        let args = stats(&["dice-roller", "stats", "-d", "1d6; 2d6", "--need", "1"]);
+        assert!(matches!(
+            run_stats(&args),
+            Err((DiceError::ConflictingOptions(_), "1d6"))
+        ));
+        let args = stats(&["dice-roller", "stats", "-d", "1d6/(1d4-1d4)"]);
+        assert!(matches!(
+            run_stats(&args),
+            Err((DiceError::DivisionByZero(_), "1d6/(1d4-1d4)"))
+        ));
     }
 }
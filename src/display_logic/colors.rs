@@ -0,0 +1,141 @@
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
+use comfy_table::{Attribute, Cell, Color};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+
+// what a die or value gets colored as in the terminal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Highlight {
+    Crit,     // the highest face, green
+    Fumble,   // a 1, red
+    Dropped,  // doesn't count towards the total, dimmed
+    Exploded, // an explosion chain, yellow
+    Success,  // green
+    Failure,  // red
+}
+
+impl Highlight {
+    fn ansi_code(&self) -> &'static str {
+        match self {
+            Highlight::Crit | Highlight::Success => "32",
+            Highlight::Fumble | Highlight::Failure => "31",
+            Highlight::Dropped => "2",
+            Highlight::Exploded => "33",
+        }
+    }
+
+    // wraps text in the escape codes, for output that isn't a comfy-table
+    pub fn paint(&self, text: &str) -> String {
+        format!("\x1b[{}m{}\x1b[0m", self.ansi_code(), text)
+    }
+
+    pub fn style_cell(&self, cell: Cell) -> Cell {
+        match self {
+            Highlight::Crit | Highlight::Success => cell.fg(Color::Green),
+            Highlight::Fumble | Highlight::Failure => cell.fg(Color::Red),
+            Highlight::Dropped => cell.add_attribute(Attribute::Dim),
+            Highlight::Exploded => cell.fg(Color::Yellow),
+        }
+    }
+}
+
+// dice that don't count are dimmed, otherwise a max face is a crit and a 1 a fumble
+pub fn die_highlight(roll: &SuccessCountingRulesApplied) -> Option<Highlight> {
+    if !roll.adds_to_total() {
+        Some(Highlight::Dropped)
+    } else if roll.final_roll == roll.dice_size && roll.dice_size > 1 {
+        Some(Highlight::Crit)
+    } else if roll.final_roll == 1 {
+        Some(Highlight::Fumble)
+    } else {
+        None
+    }
+}
+
+pub fn success_highlight(roll: &SuccessCountingRulesApplied) -> Option<Highlight> {
+    roll.succeeded().map(|succeeded| {
+        if succeeded {
+            Highlight::Success
+        } else {
+            Highlight::Failure
+        }
+    })
+}
+
+// colors only go to a terminal, and never when NO_COLOR is set (https://no-color.org)
+pub fn color_enabled() -> bool {
+    should_color(
+        std::io::stdout().is_terminal(),
+        std::env::var_os("NO_COLOR"),
+    )
+}
+
+fn should_color(is_terminal: bool, no_color: Option<OsString>) -> bool {
+    is_terminal && no_color.is_none_or(|value| value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn die(final_roll: u32, kept: bool) -> SuccessCountingRulesApplied {
+        counted_die(final_roll, kept, None, None)
+    }
+
+    fn counted_die(
+        final_roll: u32,
+        kept: bool,
+        success: Option<bool>,
+        failure: Option<bool>,
+    ) -> SuccessCountingRulesApplied {
+        SuccessCountingRulesApplied::new(
+            1,
+            1,
+            1,
+            6,
+            final_roll,
+            vec![],
+            vec![],
+            final_roll as i32,
+            false,
+            kept,
+            None,
+            success,
+            failure,
+            false,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_die_highlight() {
+        assert_eq!(die_highlight(&die(6, true)), Some(Highlight::Crit));
+        assert_eq!(die_highlight(&die(1, true)), Some(Highlight::Fumble));
+        assert_eq!(die_highlight(&die(6, false)), Some(Highlight::Dropped));
+        assert_eq!(die_highlight(&die(4, true)), None);
+    }
+
+    #[test]
+    fn test_success_highlight() {
+        let highlight =
+            |success, failure| success_highlight(&counted_die(4, true, success, failure));
+        assert_eq!(highlight(Some(true), None), Some(Highlight::Success));
+        assert_eq!(highlight(Some(false), None), Some(Highlight::Failure));
+        assert_eq!(highlight(None, Some(true)), Some(Highlight::Failure));
+        assert_eq!(highlight(None, Some(false)), Some(Highlight::Success));
+        assert_eq!(highlight(None, None), None);
+    }
+
+    #[test]
+    fn test_should_color() {
+        assert!(should_color(true, None));
+        assert!(should_color(true, Some(OsString::new())));
+        assert!(!should_color(true, Some(OsString::from("1"))));
+        assert!(!should_color(false, None));
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(Highlight::Fumble.paint("1"), "\x1b[31m1\x1b[0m");
+    }
+}
@@ -0,0 +1,91 @@
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
+use crate::display_logic::colors::die_highlight;
+use crate::display_logic::renderer::{format_modifier, RenderContext, Renderer};
+
+// the default output, the dice that count towards the total on a line per dice group
+pub struct CompactRenderer;
+
+impl Renderer for CompactRenderer {
+    fn render(
+        &self,
+        result: &SuccessCountingAfterResultKeeping,
+        context: &RenderContext,
+    ) -> String {
+        let mut rolls: Vec<&SuccessCountingRulesApplied> = result.rolls.iter().collect();
+        rolls.sort_by_key(|roll| roll.group);
+        let mut output = String::new();
+        let mut current_group = 1;
+        for roll in rolls {
+            if roll.group != current_group {
+                current_group = roll.group;
+                output.push('\n')
+            }
+            if roll.adds_to_total() {
+                let die = format!("{}/{}", roll.final_roll, roll.dice_size);
+                let die = match die_highlight(roll) {
+                    Some(highlight) if context.color => highlight.paint(&die),
+                    _ => die,
+                };
+                output.push_str(&format!("{}, ", die))
+            }
+        }
+        output.push('\n');
+        output.push_str(&format!(
+            "Modifier: {}, Total: {}\n",
+            format_modifier(result.final_modifier),
+            result.total
+        ));
+        // so the roll can be replayed with --seed
+        if let Some(seed) = context.seed {
+            output.push_str(&format!("Seed: {}\n", seed))
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_with;
+
+    fn render(expression: &str, rolls: &[u32]) -> String {
+        let result = roll_with(expression, &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression(expression).unwrap();
+        CompactRenderer.render(&result, &RenderContext::new(&expression, None, rolls))
+    }
+
+    #[test]
+    fn test_compact_lists_counted_dice_per_group() {
+        assert_eq!(
+            render("4d6dl1+1d4-2", &[3, 1, 6, 6, 2]),
+            "3/6, 6/6, 6/6, \n2/4, \nModifier: -2, Total: 15\n"
+        );
+    }
+
+    #[test]
+    fn test_compact_colors_crits_and_fumbles() {
+        let rolls = [6, 1, 3];
+        let result = roll_with("3d6", &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression("3d6").unwrap();
+        let mut context = RenderContext::new(&expression, None, &rolls);
+        context.color = true;
+        assert_eq!(
+            CompactRenderer.render(&result, &context),
+            "\x1b[32m6/6\x1b[0m, \x1b[31m1/6\x1b[0m, 3/6, \nModifier: +0, Total: 10\n"
+        );
+    }
+
+    #[test]
+    fn test_compact_shows_the_seed_to_replay_with() {
+        let result = roll_with("2d6", &mut ScriptedRollSource::new(vec![3, 4])).unwrap();
+        let expression = parse_expression("2d6").unwrap();
+        let context = RenderContext::new(&expression, Some(42), &[3, 4]);
+        assert_eq!(
+            CompactRenderer.render(&result, &context),
+            "3/6, 4/6, \nModifier: +0, Total: 7\nSeed: 42\n"
+        );
+    }
+}
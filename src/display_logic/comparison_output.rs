@@ -0,0 +1,314 @@
+use crate::dice_rolling_logic::distribution::{Distribution, Matchup};
+use crate::display_logic::histogram::{Histogram, Series};
+use crate::display_logic::json_output::{to_pretty_json, JSON_SCHEMA_VERSION};
+use crate::display_logic::renderer::RenderContext;
+use crate::display_logic::stats_output::{
+    format_percent, target_chances, StatsJson, Target, SMALLEST_LISTED,
+};
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+// several dice rolls side by side: their means and spreads, how often each one beats
+// the others, and one histogram with all of them, eg: stats --compare 2d6+3 1d12+4
+pub fn render_comparison(
+    stats: &[(Distribution, RenderContext)],
+    format: &str,
+    histogram: Option<Histogram>,
+    target: Target,
+) -> String {
+    let histogram = histogram.unwrap_or_default();
+    match format {
+        "json" => to_pretty_json(&ComparisonJson::new(stats, target)) + "\n",
+        "table" => comparison_tables(stats, histogram, target),
+        _ => comparison_lines(stats, histogram, target),
+    }
+}
+
+// the label when there is one, it's shorter than the dice roll
+fn name(context: &RenderContext) -> String {
+    context
+        .label
+        .unwrap_or(context.expression.source.trim())
+        .to_string()
+}
+
+// every dice roll against each one after it, eg: A against B, A against C, B against C
+fn matchups(stats: &[(Distribution, RenderContext)]) -> Vec<(String, String, Matchup)> {
+    let mut matchups = vec![];
+    for (index, (first, first_context)) in stats.iter().enumerate() {
+        for (second, second_context) in &stats[index + 1..] {
+            matchups.push((
+                name(first_context),
+                name(second_context),
+                first.against(second),
+            ));
+        }
+    }
+    matchups
+}
+
+fn heading(stats: &[(Distribution, RenderContext)]) -> String {
+    let names: Vec<String> = stats.iter().map(|(_, context)| name(context)).collect();
+    format!("Compare: {}", names.join(", "))
+}
+
+// every dice roll gets a row for each total any of them can come to, so the bars line up
+fn combined_histogram(stats: &[(Distribution, RenderContext)], histogram: Histogram) -> String {
+    let values: BTreeSet<i64> = stats
+        .iter()
+        .flat_map(|(distribution, _)| {
+            distribution
+                .totals
+                .iter()
+                .filter(|(_, &chance)| chance >= SMALLEST_LISTED)
+                .map(|(&value, _)| value)
+        })
+        .collect();
+    let series: Vec<Series> = stats
+        .iter()
+        .map(|(distribution, context)| {
+            let rows = values
+                .iter()
+                .map(|&value| {
+                    (
+                        value,
+                        distribution.totals.get(&value).copied().unwrap_or(0.0),
+                        distribution.chance_at_least(value).min(1.0),
+                    )
+                })
+                .collect();
+            Series {
+                name: name(context),
+                rows,
+                mean: distribution.mean(),
+            }
+        })
+        .collect();
+    let style = stats
+        .first()
+        .map(|(_, context)| context.table_style)
+        .unwrap_or_default();
+    histogram.render_compared(&series, style)
+}
+
+fn comparison_tables(
+    stats: &[(Distribution, RenderContext)],
+    histogram: Histogram,
+    target: Target,
+) -> String {
+    let new_table = || -> Table {
+        let (_, context) = &stats[0];
+        context.table_style.new_table(context.color)
+    };
+    let mut summary = new_table();
+    let mut header = vec![
+        Cell::new("Dice roll"),
+        Cell::new("Min"),
+        Cell::new("Max"),
+        Cell::new("Mean"),
+        Cell::new("Variance"),
+        Cell::new("Std dev"),
+    ];
+    // a column for the chance of each target, eg: 17 or more
+    let (first, _) = &stats[0];
+    header.extend(
+        target_chances(first, target)
+            .into_iter()
+            .map(|(name, _)| Cell::new(name)),
+    );
+    summary.set_header(header);
+    for (distribution, context) in stats {
+        let mut row = vec![
+            Cell::new(name(context)),
+            Cell::new(distribution.min()),
+            Cell::new(distribution.max()),
+            Cell::new(format!("{:.2}", distribution.mean())),
+            Cell::new(format!("{:.2}", distribution.variance())),
+            Cell::new(format!("{:.2}", distribution.std_dev())),
+        ];
+        row.extend(
+            target_chances(distribution, target)
+                .into_iter()
+                .map(|(_, chance)| Cell::new(format_percent(chance))),
+        );
+        summary.add_row(row);
+    }
+
+    let mut against = new_table();
+    against.set_header(vec![
+        Cell::new("Dice roll"),
+        Cell::new("Against"),
+        Cell::new("Wins"),
+        Cell::new("Ties"),
+        Cell::new("Loses"),
+    ]);
+    for (first, second, matchup) in matchups(stats) {
+        against.add_row(vec![
+            Cell::new(first),
+            Cell::new(second),
+            Cell::new(format_percent(matchup.wins)),
+            Cell::new(format_percent(matchup.ties)),
+            Cell::new(format_percent(matchup.losses)),
+        ]);
+    }
+    [
+        heading(stats),
+        summary.to_string(),
+        against.to_string(),
+        combined_histogram(stats, histogram),
+    ]
+    .join("\n")
+}
+
+fn comparison_lines(
+    stats: &[(Distribution, RenderContext)],
+    histogram: Histogram,
+    target: Target,
+) -> String {
+    let mut lines = vec![heading(stats)];
+    for (distribution, context) in stats {
+        let mut line = format!(
+            "{}: Mean: {:.2}, Variance: {:.2}, Std dev: {:.2}",
+            name(context),
+            distribution.mean(),
+            distribution.variance(),
+            distribution.std_dev()
+        );
+        for (name, chance) in target_chances(distribution, target) {
+            line.push_str(&format!(", {}: {}", name, format_percent(chance)));
+        }
+        lines.push(line);
+    }
+    for (first, second, matchup) in matchups(stats) {
+        lines.push(format!(
+            "{} against {}: wins {}, ties {}, loses {}",
+            first,
+            second,
+            format_percent(matchup.wins),
+            format_percent(matchup.ties),
+            format_percent(matchup.losses)
+        ));
+    }
+    lines.push(combined_histogram(stats, histogram));
+    lines.join("\n")
+}
+
+// the --format json output of stats --compare
+#[derive(Clone, Debug, Serialize)]
+pub struct ComparisonJson {
+    pub schema_version: u32,
+    pub stats: Vec<StatsJson>,
+    pub matchups: Vec<MatchupJson>,
+}
+
+// how often dice_roll comes out higher than, the same as or lower than against
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MatchupJson {
+    pub dice_roll: String,
+    pub against: String,
+    pub wins: f64,
+    pub ties: f64,
+    pub losses: f64,
+}
+
+impl ComparisonJson {
+    pub fn new(stats: &[(Distribution, RenderContext)], target: Target) -> Self {
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            stats: stats
+                .iter()
+                .map(|(distribution, context)| StatsJson::new(distribution, context, target))
+                .collect(),
+            matchups: matchups(stats)
+                .into_iter()
+                .map(|(dice_roll, against, matchup)| MatchupJson {
+                    dice_roll,
+                    against,
+                    wins: matchup.wins,
+                    ties: matchup.ties,
+                    losses: matchup.losses,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing_logic::ast::RollExpression;
+    use crate::parsing_logic::parser::parse_expression;
+
+    fn one_die(sides: i64) -> Distribution {
+        let mut distribution = Distribution::default();
+        for face in 1..=sides {
+            distribution.totals.insert(face, 1.0 / sides as f64);
+        }
+        distribution
+    }
+
+    fn expressions() -> [RollExpression; 2] {
+        [
+            parse_expression("1d6").unwrap(),
+            parse_expression("1d4").unwrap(),
+        ]
+    }
+
+    fn stats(expressions: &[RollExpression; 2]) -> Vec<(Distribution, RenderContext<'_>)> {
+        let mut context = RenderContext::new(&expressions[0], None, &[]);
+        context.label = Some("sword");
+        vec![
+            (one_die(6), context),
+            (one_die(4), RenderContext::new(&expressions[1], None, &[])),
+        ]
+    }
+
+    #[test]
+    fn test_comparison_lines() {
+        let output = render_comparison(&stats(&expressions()), "plain", None, Target::default());
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "Compare: sword, 1d4");
+        assert_eq!(lines[1], "sword: Mean: 3.50, Variance: 2.92, Std dev: 1.71");
+        assert_eq!(
+            lines[3],
+            "sword against 1d4: wins 58.33%, ties 16.67%, loses 25.00%"
+        );
+        assert_eq!(lines[4], "Total  Dice roll  Chance");
+        // 1d4 can't roll a 6, but still gets a row to line up with the sword's
+        assert!(output.contains("\n             1d4   0.00%\n"));
+    }
+
+    #[test]
+    fn test_comparison_tables_and_json() {
+        let output = render_comparison(&stats(&expressions()), "table", None, Target::default());
+        assert!(output.starts_with("Compare: sword, 1d4\n"));
+        assert!(output.contains("│ sword     ┆ 1d4     ┆ 58.33% ┆ 16.67% ┆ 25.00% │"));
+
+        let json: serde_json::Value = serde_json::from_str(&render_comparison(
+            &stats(&expressions()),
+            "json",
+            None,
+            Target::default(),
+        ))
+        .unwrap();
+        assert_eq!(json["stats"][0]["label"], "sword");
+        assert_eq!(json["matchups"][0]["dice_roll"], "sword");
+        assert_eq!(json["matchups"][0]["against"], "1d4");
+        assert!((json["matchups"][0]["losses"].as_f64().unwrap() - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_comparison_targets() {
+        let target = Target {
+            vs: Some(4),
+            need: None,
+        };
+        let output = render_comparison(&stats(&expressions()), "plain", None, target);
+        assert!(output
+            .contains("\nsword: Mean: 3.50, Variance: 2.92, Std dev: 1.71, 4 or more: 50.00%\n"));
+        assert!(output.contains(", 4 or more: 25.00%\n"));
+        let output = render_comparison(&stats(&expressions()), "table", None, target);
+        assert!(output.contains("┆ 4 or more │"));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::dice_rolling_logic::roll_command::DiceRollCommand;
+use crate::display_logic::renderer::format_modifier;
+use crate::enums::Operator;
+use crate::parsing_logic::ast::RollExpression;
+
+// what a dice roll does in words, with the flags already folded into its commands
+pub fn explain(
+    dice_roll: &RollExpression,
+    commands: &[DiceRollCommand],
+    margin_of_success: u32,
+) -> String {
+    let mut lines = vec![format!("{}:", dice_roll.source.trim())];
+    for command in commands {
+        let mut clauses = vec![format!(
+            "roll {}d{}{}",
+            command.dice_count,
+            command.dice_size,
+            if command.sign < 0 {
+                ", subtracted from the total"
+            } else {
+                ""
+            }
+        )];
+        clauses.extend(dice_clauses(command));
+        lines.push(format!("  group {}: {}", command.group, clauses.join(", ")));
+    }
+    if dice_roll.is_additive() {
+        let modifier: i32 = dice_roll
+            .flat_modifiers()
+            .iter()
+            .map(|modifier| modifier.value)
+            .sum();
+        if modifier != 0 {
+            lines.push(format!("  modifier: {}", format_modifier(modifier)));
+        }
+    } else {
+        lines.push(format!(
+            "  then each group's subtotal goes into {}",
+            dice_roll.source.trim()
+        ));
+    }
+    if margin_of_success > 0 {
+        lines.push(format!(
+            "  margin of success: {} is taken off the total",
+            margin_of_success
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+// re-rolls, explosions, keeping and counting, in the order they happen to a die
+fn dice_clauses(command: &DiceRollCommand) -> Vec<String> {
+    let mut clauses = vec![];
+    if let Some(target) = command.re_roll {
+        clauses.push(if command.re_roll_recursively {
+            format!(
+                "re-roll dice that roll {} until they don't (at most {} times)",
+                describe_operator(target),
+                command.limits.max_re_rolls
+            )
+        } else {
+            format!("re-roll dice that roll {} once", describe_operator(target))
+        });
+    }
+    if let Some(target) = command.explode {
+        clauses.push(if command.explode_once {
+            format!(
+                "add one more die for each die that rolls {}",
+                describe_operator(target)
+            )
+        } else {
+            format!(
+                "add another die each time a die rolls {} (at most {} times)",
+                describe_operator(target),
+                command.limits.max_explosions
+            )
+        });
+    }
+
+    let keeping = command.result_keeping_rules;
+    if let Some(value) = keeping.be_replaced_with {
+        clauses.push(if keeping.min {
+            format!("dice below {} count as {}", value, value)
+        } else {
+            format!("dice above {} count as {}", value, value)
+        });
+    }
+    if keeping.keep_or_drop_count > 0 {
+        clauses.push(format!(
+            "{} the {} {}{}",
+            if keeping.keep { "keep" } else { "drop" },
+            if keeping.high { "highest" } else { "lowest" },
+            keeping.keep_or_drop_count,
+            if keeping.pool {
+                " across every pooled group"
+            } else {
+                ""
+            }
+        ));
+    }
+
+    let counting = command.success_counting_rules;
+    if let Some(target) = counting.count_success {
+        clauses.push(format!(
+            "a die that rolls {} is a success, only successes add to the total",
+            describe_operator(target)
+        ));
+    }
+    if let Some(target) = counting.count_failure {
+        clauses.push(format!(
+            "a die that rolls {} is a failure and doesn't add to the total",
+            describe_operator(target)
+        ));
+    }
+    if counting.count_even {
+        clauses.push("count the even dice".to_string());
+    }
+    if counting.count_odd {
+        clauses.push("count the odd dice".to_string());
+    }
+    if let Some(deduction) = counting.deduct_failure {
+        clauses.push(format!("take {} off the total for each failure", deduction));
+    }
+    if counting.subtract_failure {
+        clauses.push("take each failed die off the total".to_string());
+    }
+    clauses
+}
+
+// eg: "6", "5 or more", "less than 3"
+pub fn describe_operator(operator: Operator) -> String {
+    match operator {
+        Operator::Eq(target) => format!("{}", target),
+        Operator::Gt(target) => format!("more than {}", target),
+        Operator::Gte(target) => format!("{} or more", target),
+        Operator::Lt(target) => format!("less than {}", target),
+        Operator::Lte(target) => format!("{} or less", target),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display_logic::builders::{build_dice_roll_commands, build_margin_of_success};
+    use crate::parsing_logic::parser::parse_expression;
+
+    fn explain_inline(expression: &str) -> String {
+        let dice_roll = parse_expression(expression).unwrap();
+        let commands = build_dice_roll_commands(
+            &dice_roll,
+            None,
+            None,
+            None,
+            None,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+        .unwrap();
+        let margin = build_margin_of_success(&dice_roll, None).unwrap();
+        explain(&dice_roll, &commands, margin)
+    }
+
+    #[test]
+    fn test_explain_keeping_and_modifiers() {
+        assert_eq!(
+            explain_inline("4d6dl1 - 1d4 + 3"),
+            "4d6dl1 - 1d4 + 3:\n  \
+             group 1: roll 4d6, drop the lowest 1\n  \
+             group 2: roll 1d4, subtracted from the total\n  \
+             modifier: +3\n"
+        );
+    }
+
+    #[test]
+    fn test_explain_re_rolls_explosions_and_successes() {
+        let output = explain_inline("5d10rr1x>=9cs>=8ms2");
+        assert!(output.contains("re-roll dice that roll 1 until they don't (at most 100 times)"));
+        assert!(output.contains("add another die each time a die rolls 9 or more"));
+        assert!(output.contains("a die that rolls 8 or more is a success"));
+        assert!(output.contains("margin of success: 2 is taken off the total"));
+    }
+
+    #[test]
+    fn test_explain_steps() {
+        assert!(
+            explain_inline("(1d8+4)*2").contains("then each group's subtotal goes into (1d8+4)*2")
+        );
+        assert_eq!(describe_operator(Operator::Lt(3)), "less than 3");
+    }
+}
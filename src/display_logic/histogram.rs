@@ -0,0 +1,264 @@
+use crate::display_logic::stats_output::format_percent;
+use crate::display_logic::table_style::TableStyle;
+
+// bars never get shorter than this, even when the width leaves less room
+const MIN_BAR_WIDTH: usize = 10;
+
+// a horizontal bar for each value, as long as its chance, eg:
+// Total  Chance
+//     7  16.67%  ████████████████████ ◀ mean 7.00
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Histogram {
+    pub cumulative: bool, // shades the chance of each value or more behind its bar
+}
+
+// one dice roll in a combined chart, rows are each value with its chance and the chance
+// of it or more, the same values in the same order for every series
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub rows: Vec<(i64, f64, f64)>,
+    pub mean: f64,
+}
+
+// a line of the chart, the cells in front of its bar and what the bar shows
+struct Bar {
+    cells: Vec<String>,
+    chance: f64,
+    at_least: f64,
+    fill: char,
+    mean_mark: Option<String>,
+}
+
+impl Histogram {
+    // rows are each value with its chance and the chance of it or more, lowest value first
+    pub fn render(
+        &self,
+        name: &str,
+        rows: &[(i64, f64, f64)],
+        mean: f64,
+        style: TableStyle,
+    ) -> String {
+        let mean_row = closest_to(rows, mean);
+        let bars = rows
+            .iter()
+            .map(|&(value, chance, at_least)| Bar {
+                cells: self.cells(vec![value.to_string()], chance, at_least),
+                chance,
+                at_least,
+                fill: fills(style)[0],
+                mean_mark: (Some(value) == mean_row).then(|| mean_mark(mean, style)),
+            })
+            .collect();
+        self.draw(self.header(vec![name]), bars, style)
+    }
+
+    // several dice rolls in one chart, a bar for each of them under every total, eg:
+    // Total  Dice roll  Chance
+    //     5      2d6+3   2.78%  ███
+    //           1d12+4   8.33%  ▓▓▓▓▓▓▓▓▓▓
+    pub fn render_compared(&self, series: &[Series], style: TableStyle) -> String {
+        let mean_rows: Vec<Option<i64>> = series
+            .iter()
+            .map(|series| closest_to(&series.rows, series.mean))
+            .collect();
+        let values: Vec<i64> = series.first().map_or(vec![], |series| {
+            series.rows.iter().map(|&(value, _, _)| value).collect()
+        });
+        let mut bars = vec![];
+        for (row, &value) in values.iter().enumerate() {
+            for (index, series) in series.iter().enumerate() {
+                let (_, chance, at_least) = series.rows[row];
+                // the value is only written above its first bar
+                let shown_value = if index == 0 {
+                    value.to_string()
+                } else {
+                    String::new()
+                };
+                bars.push(Bar {
+                    cells: self.cells(vec![shown_value, series.name.clone()], chance, at_least),
+                    chance,
+                    at_least,
+                    fill: fills(style)[index % 4],
+                    mean_mark: (Some(value) == mean_rows[index])
+                        .then(|| mean_mark(series.mean, style)),
+                });
+            }
+        }
+        self.draw(self.header(vec!["Total", "Dice roll"]), bars, style)
+    }
+
+    fn header(&self, names: Vec<&str>) -> Vec<String> {
+        let mut header: Vec<String> = names.into_iter().map(str::to_string).collect();
+        header.push("Chance".to_string());
+        if self.cumulative {
+            header.push("At least".to_string());
+        }
+        header
+    }
+
+    fn cells(&self, mut cells: Vec<String>, chance: f64, at_least: f64) -> Vec<String> {
+        cells.push(format_percent(chance));
+        if self.cumulative {
+            cells.push(format_percent(at_least));
+        }
+        cells
+    }
+
+    fn draw(&self, header: Vec<String>, bars: Vec<Bar>, style: TableStyle) -> String {
+        let shade = if style.ascii { ':' } else { '░' };
+        let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+        for bar in &bars {
+            for (width, cell) in widths.iter_mut().zip(&bar.cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mark_width = bars
+            .iter()
+            .filter_map(|bar| bar.mean_mark.as_ref())
+            .map(|mark| mark.chars().count() + 1)
+            .max()
+            .unwrap_or(0);
+        let prefix_width: usize = widths.iter().map(|width| width + 2).sum();
+        let bar_width = (style.width as usize)
+            .saturating_sub(prefix_width + mark_width)
+            .max(MIN_BAR_WIDTH);
+        // with the shading every bar is out of 100%, otherwise the most likely value fills the bar
+        let scale = if self.cumulative {
+            1.0
+        } else {
+            bars.iter().map(|bar| bar.chance).fold(0.0, f64::max)
+        };
+        let length =
+            |chance: f64| (((chance / scale) * bar_width as f64).round() as usize).min(bar_width);
+
+        let mut lines = vec![header
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ")];
+        for bar in bars {
+            let mut line = String::new();
+            for (cell, width) in bar.cells.iter().zip(&widths) {
+                line.push_str(&format!("{:>width$}  ", cell, width = width));
+            }
+            let filled = length(bar.chance);
+            line.extend(std::iter::repeat_n(bar.fill, filled));
+            if self.cumulative {
+                let shaded = length(bar.at_least).saturating_sub(filled);
+                line.extend(std::iter::repeat_n(shade, shaded));
+            }
+            if let Some(mark) = bar.mean_mark {
+                line.push(' ');
+                line.push_str(&mark);
+            }
+            lines.push(line.trim_end().to_string());
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+// a different character for each dice roll in a combined chart
+fn fills(style: TableStyle) -> [char; 4] {
+    if style.ascii {
+        ['#', '=', '+', '*']
+    } else {
+        ['█', '▓', '▒', '▞']
+    }
+}
+
+// eg: ◀ mean 7.00
+fn mean_mark(mean: f64, style: TableStyle) -> String {
+    let arrow = if style.ascii { "<-" } else { "◀" };
+    format!("{} mean {:.2}", arrow, mean)
+}
+
+// the mean is marked on the value closest to it
+fn closest_to(rows: &[(i64, f64, f64)], mean: f64) -> Option<i64> {
+    rows.iter().map(|&(value, _, _)| value).min_by(|a, b| {
+        (*a as f64 - mean)
+            .abs()
+            .total_cmp(&(*b as f64 - mean).abs())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<(i64, f64, f64)> {
+        vec![(1, 0.25, 1.0), (2, 0.5, 0.75), (3, 0.25, 0.25)]
+    }
+
+    #[test]
+    fn test_bars_fit_the_width_and_mark_the_mean() {
+        let style = TableStyle {
+            width: 41,
+            ascii: false,
+        };
+        let output = Histogram::default().render("Total", &rows(), 2.0, style);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "Total  Chance");
+        assert!(lines[2].starts_with("    2  50.00%  ████"));
+        assert!(lines[2].ends_with("█ ◀ mean 2.00"));
+        assert!(!lines[1].contains("mean"));
+        assert!(lines.iter().all(|line| line.chars().count() <= 41));
+        // the most likely value fills the bar, the others are half as long
+        let bars = |line: &str| line.chars().filter(|&c| c == '█').count();
+        assert_eq!(bars(lines[1]) * 2, bars(lines[2]));
+    }
+
+    #[test]
+    fn test_ascii_and_cumulative_shading() {
+        let style = TableStyle {
+            width: 62,
+            ascii: true,
+        };
+        let histogram = Histogram { cumulative: true };
+        let output = histogram.render("Successes", &rows(), 1.4, style);
+        assert!(output.is_ascii());
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "Successes  Chance  At least");
+        assert!(lines[1].ends_with("<- mean 1.40"));
+        let count = |line: &str, c: char| line.chars().filter(|&found| found == c).count();
+        // every bar is out of 100%, filled up to its chance and shaded up to the chance of it or more
+        let full = count(lines[1], '#') + count(lines[1], ':');
+        assert_eq!(count(lines[1], '#') * 4, full);
+        assert_eq!(count(lines[3], ':'), 0);
+        assert_eq!((count(lines[2], '#') + count(lines[2], ':')) * 4, full * 3);
+    }
+
+    #[test]
+    fn test_compared_bars_share_a_scale() {
+        let style = TableStyle {
+            width: 60,
+            ascii: true,
+        };
+        let series = vec![
+            Series {
+                name: "2d2".to_string(),
+                rows: rows(),
+                mean: 2.0,
+            },
+            Series {
+                name: "1d3+1".to_string(),
+                rows: vec![
+                    (1, 0.0, 1.0),
+                    (2, 1.0 / 3.0, 1.0),
+                    (3, 1.0 / 3.0, 2.0 / 3.0),
+                ],
+                mean: 3.0,
+            },
+        ];
+        let output = Histogram::default().render_compared(&series, style);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "Total  Dice roll  Chance");
+        assert_eq!(lines[2], "           1d3+1   0.00%");
+        assert!(lines[3].starts_with("    2        2d2  50.00%  ###"));
+        assert!(lines[3].ends_with("# <- mean 2.00"));
+        assert!(lines[6].ends_with("= <- mean 3.00"));
+        assert!(lines.iter().all(|line| line.len() <= 60));
+    }
+}
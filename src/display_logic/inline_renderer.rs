@@ -0,0 +1,227 @@
+use crate::dice_rolling_logic::repeated_roll::RepeatedRoll;
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
+use crate::display_logic::renderer::{
+    format_totals, repeated_expression, summary_total, RenderContext, Renderer, RolledExpression,
+};
+use crate::parsing_logic::ast::RollExpression;
+
+// a single line for chat and session logs, the Roll20 way,
+// eg: 4d6dl1 + 3 = (6 + 5 + 4 + ~~1~~) + 3 = 18
+pub struct InlineRenderer;
+
+impl Renderer for InlineRenderer {
+    fn render(
+        &self,
+        result: &SuccessCountingAfterResultKeeping,
+        context: &RenderContext,
+    ) -> String {
+        let mut line = format!(
+            "{} = {}",
+            context.expression.source.trim(),
+            breakdown(result, context.expression)
+        );
+        let taken_off = [
+            (result.deductions_from_failure, "deductions"),
+            (result.subtractions_from_failure, "failures"),
+            (result.margin_of_success, "margin of success"),
+        ];
+        for (value, name) in taken_off {
+            if value > 0 {
+                line.push_str(&format!(" - {} ({})", value, name))
+            }
+        }
+        format!("{} = {}\n", line, result.total)
+    }
+
+    // eg: 3x4d6dl1 = 12, 15, 9 = 36
+    fn render_repeated(&self, repeated: &RepeatedRoll, context: &RenderContext) -> String {
+        format!(
+            "{} = {} = {}\n",
+            repeated_expression(context),
+            format_totals(&repeated.totals),
+            repeated.sum()
+        )
+    }
+
+    // a line per dice roll, each already shows its dice roll so only the label goes in front
+    fn render_all(&self, rolls: &[RolledExpression], _seed: Option<u64>) -> String {
+        let mut output = String::new();
+        for roll in rolls {
+            if let Some(label) = roll.context.label {
+                output.push_str(&format!("{}: ", label));
+            }
+            output.push_str(&self.render_outcome(roll.outcome, &roll.context));
+        }
+        if rolls.len() > 1 {
+            output.push_str(&format!("Total: {}\n", summary_total(rolls)));
+        }
+        output
+    }
+}
+
+// the expression as it was written, with every dice group swapped for its dice
+fn breakdown(result: &SuccessCountingAfterResultKeeping, parsed: &RollExpression) -> String {
+    let expression = parsed.source.as_str();
+    let mut output = String::new();
+    let mut written_up_to = 0;
+    for (group, start, end) in parsed.dice_group_spans() {
+        let written = &expression[start..end];
+        // a unary plus is part of the dice group's span, keep it in front
+        let kept_prefix = written.len() - written.trim_start_matches(['+', ' ']).len();
+        output.push_str(&expression[written_up_to..start + kept_prefix]);
+        output.push_str(&dice_for_group(result, group));
+        written_up_to = end;
+    }
+    output.push_str(&expression[written_up_to..]);
+    output.trim().to_string()
+}
+
+fn dice_for_group(result: &SuccessCountingAfterResultKeeping, group: i32) -> String {
+    let mut rolls: Vec<&SuccessCountingRulesApplied> = result
+        .rolls
+        .iter()
+        .filter(|roll| roll.group == group)
+        .collect();
+    rolls.sort_by_key(|roll| roll.roll_number);
+    let dice = rolls
+        .into_iter()
+        .map(describe_die)
+        .collect::<Vec<String>>()
+        .join(" + ");
+    format!("({})", dice)
+}
+
+// [1] 4 was re-rolled from a 1, 1→3 was changed by min/max, 6!6!2 exploded twice,
+// ~~1~~ doesn't count
+fn describe_die(roll: &SuccessCountingRulesApplied) -> String {
+    let mut die = String::new();
+    for discarded in &roll.discarded_rolls {
+        die.push_str(&format!("[{}] ", discarded));
+    }
+    if let Some(replaced) = roll.replaced_roll {
+        die.push_str(&format!("{}→", replaced));
+    }
+    die.push_str(&roll.final_roll.to_string());
+    for exploded in &roll.exploded_rolls {
+        die.push_str(&format!("!{}", exploded));
+    }
+    if roll.adds_to_total() {
+        die
+    } else {
+        format!("~~{}~~", die)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::repeated_roll::RollOutcome;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_with;
+
+    fn render(expression: &str, rolls: &[u32]) -> String {
+        let result = roll_with(expression, &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression(expression).unwrap();
+        InlineRenderer.render(&result, &RenderContext::new(&expression, None, rolls))
+    }
+
+    #[test]
+    fn test_inline_strikes_dropped_dice() {
+        assert_eq!(
+            render("4d6dl1 + 3", &[6, 5, 4, 1]),
+            "4d6dl1 + 3 = (6 + 5 + 4 + ~~1~~) + 3 = 18\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_re_rolls_and_explosions() {
+        assert_eq!(
+            render("1d20r1 - 1d6x", &[1, 12, 6, 6, 2]),
+            "1d20r1 - 1d6x = ([1] 12) - (6!6!2) = -2\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_min_and_max_replacements() {
+        assert_eq!(
+            render("3d6min3 + 1d20max15", &[1, 4, 2, 18]),
+            "3d6min3 + 1d20max15 = (1→3 + 4 + 2→3) + (18→15) = 25\n"
+        );
+        assert_eq!(
+            render("1d6r1min3", &[1, 2]),
+            "1d6r1min3 = ([1] 2→3) = 3\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_keeps_the_expression_around_the_dice() {
+        assert_eq!(
+            render("floor((1d8+4)/2)", &[5]),
+            "floor((1d8+4)/2) = floor(((5)+4)/2) = 4\n"
+        );
+        assert_eq!(render("+1d4", &[3]), "+1d4 = +(3) = 3\n");
+        assert_eq!(
+            render("1d6\u{a0}+ 1d4", &[3, 2]),
+            "1d6\u{a0}+ 1d4 = (3)\u{a0}+ (2) = 5\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_labels_and_total() {
+        let attack = RollOutcome::Single(
+            roll_with("1d20+5", &mut ScriptedRollSource::new(vec![12])).unwrap(),
+        );
+        let damage = RollOutcome::Single(
+            roll_with("2d6", &mut ScriptedRollSource::new(vec![3, 4])).unwrap(),
+        );
+        let (attack_roll, damage_roll) = (
+            parse_expression("1d20+5").unwrap(),
+            parse_expression("2d6").unwrap(),
+        );
+        let mut context = RenderContext::new(&attack_roll, None, &[12]);
+        context.label = Some("attack");
+        let rolls = [
+            RolledExpression {
+                outcome: &attack,
+                context,
+            },
+            RolledExpression {
+                outcome: &damage,
+                context: RenderContext::new(&damage_roll, None, &[3, 4]),
+            },
+        ];
+        assert_eq!(
+            InlineRenderer.render_all(&rolls, None),
+            "attack: 1d20+5 = (12)+5 = 17\n2d6 = (3 + 4) = 7\nTotal: 24\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_repeated_roll() {
+        let repeated = RepeatedRoll {
+            totals: vec![12, 15, 9],
+            sets_rolled: 1,
+        };
+        let expression = parse_expression("4d6dl1").unwrap();
+        let mut context = RenderContext::new(&expression, None, &[]);
+        context.repeat = Some(3);
+        assert_eq!(
+            InlineRenderer.render_repeated(&repeated, &context),
+            "3x4d6dl1 = 12, 15, 9 = 36\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_failed_successes_and_margin() {
+        assert_eq!(
+            render("3d10cs>=8", &[9, 2, 8]),
+            "3d10cs>=8 = (9 + ~~2~~ + 8) = 17\n"
+        );
+        assert_eq!(
+            render("1d20ms10+15", &[12]),
+            "1d20ms10+15 = (12)+15 - 10 (margin of success) = 17\n"
+        );
+    }
+}
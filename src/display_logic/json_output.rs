@@ -0,0 +1,394 @@
+use crate::dice_rolling_logic::repeated_roll::{RepeatedRoll, RollOutcome};
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
+use crate::display_logic::renderer::{summary_total, RenderContext, Renderer, RolledExpression};
+use crate::parsing_logic::ast::FlatModifier;
+use crate::parsing_logic::evaluation::EvaluationStep;
+use serde::Serialize;
+
+// bumped whenever a field is renamed, removed or changes meaning,
+// new fields can be added without a bump
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+// every json output is made of plain numbers, strings, lists and structs of them,
+// none of which serde_json can fail to write
+pub fn to_pretty_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).expect("json output only holds plain values")
+}
+
+// the --format json output, every field is listed in the README
+#[derive(Clone, Debug, Serialize)]
+pub struct RollJson {
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>, // only there when -d gave one, eg: "attack: 1d20+5"
+    pub expression: String,
+    pub total: i32,
+    pub total_before_modifier: i32,
+    pub initial_modifier: i32,
+    pub final_modifier: i32,
+    pub deductions_from_failure: u32,
+    pub subtractions_from_failure: u32,
+    pub margin_of_success: u32,
+    pub successes: u32,
+    pub failures: u32,
+    pub evens: u32,
+    pub odds: u32,
+    pub groups: Vec<GroupJson>,
+    pub flat_modifiers: Vec<FlatModifierJson>,
+    pub steps: Vec<StepJson>,
+    pub dice: Vec<DieJson>,
+    pub seed: Option<u64>, // None when the dice were given with --rolls
+    pub rolls: Vec<u32>,   // every face in the order it came up, what --rolls reads back
+}
+
+// a dice roll rolled several times, eg: 6x4d6dl1, only the totals are kept
+#[derive(Clone, Debug, Serialize)]
+pub struct RepeatedRollJson {
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub expression: String,
+    pub repeat: u32,
+    pub totals: Vec<i32>, // in the order rolled, unless --sort was used
+    pub sum: i64,
+    pub sets_rolled: u32, // more than 1 when --min-sum or --min-highest rolled the set again
+    pub seed: Option<u64>,
+    pub rolls: Vec<u32>, // every face of every set, in the order it came up
+}
+
+// either kind of dice roll inside a set, told apart by repeat being there
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum RollSetEntryJson {
+    Roll(Box<RollJson>),
+    Repeated(RepeatedRollJson),
+}
+
+// several dice rolls from one call
+#[derive(Clone, Debug, Serialize)]
+pub struct RollSetJson {
+    pub schema_version: u32,
+    pub rolls: Vec<RollSetEntryJson>,
+    pub total: i64,        // every total added up
+    pub seed: Option<u64>, // replays the whole call, each roll also has its own
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GroupJson {
+    pub group: i32,
+    pub subtotal: i32, // signed, so a subtracted group is negative
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FlatModifierJson {
+    pub value: i32,
+    pub position: usize,
+    pub group: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StepJson {
+    pub expression: String,
+    pub value: f64,
+    pub numerator: i64, // the exact value is numerator / denominator
+    pub denominator: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DieJson {
+    pub group: i32,
+    pub sign: i32,
+    pub roll_number: u32,
+    pub dice_size: u32,
+    pub final_roll: u32,
+    pub discarded_rolls: Vec<u32>,
+    pub exploded_rolls: Vec<u32>,
+    pub replaced_roll: Option<u32>,
+    pub subtotal: i32,
+    pub capped: bool,
+    pub kept: bool,
+    pub success: Option<bool>,
+    pub failure: Option<bool>,
+    pub subtracted: bool,
+    pub deductions: u32,
+    pub adds_to_total: bool,
+}
+
+impl RollJson {
+    pub fn new(
+        result: &SuccessCountingAfterResultKeeping,
+        expression: &str,
+        seed: Option<u64>,
+        rolls: &[u32],
+    ) -> Self {
+        let mut groups: Vec<GroupJson> = result
+            .grouped_subtotals
+            .iter()
+            .map(|(&group, &subtotal)| GroupJson { group, subtotal })
+            .collect();
+        groups.sort_by_key(|group| group.group);
+
+        let mut dice: Vec<&SuccessCountingRulesApplied> = result.rolls.iter().collect();
+        dice.sort_by_key(|roll| (roll.group, roll.roll_number));
+
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            label: None,
+            expression: expression.to_string(),
+            total: result.total,
+            total_before_modifier: result.total_before_modifier,
+            initial_modifier: result.initial_modifier,
+            final_modifier: result.final_modifier,
+            deductions_from_failure: result.deductions_from_failure,
+            subtractions_from_failure: result.subtractions_from_failure,
+            margin_of_success: result.margin_of_success,
+            successes: result.successes,
+            failures: result.failures,
+            evens: result.evens,
+            odds: result.odds,
+            groups,
+            flat_modifiers: result
+                .flat_modifiers
+                .iter()
+                .map(FlatModifierJson::from)
+                .collect(),
+            steps: result.steps.iter().map(StepJson::from).collect(),
+            dice: dice.into_iter().map(DieJson::from).collect(),
+            seed,
+            rolls: rolls.to_vec(),
+        }
+    }
+
+    pub fn to_json_string(&self) -> String {
+        to_pretty_json(self)
+    }
+}
+
+pub struct JsonRenderer;
+
+impl RollJson {
+    fn from_context(result: &SuccessCountingAfterResultKeeping, context: &RenderContext) -> Self {
+        let mut json = Self::new(
+            result,
+            &context.expression.source,
+            context.seed,
+            context.rolls,
+        );
+        json.label = context.label.map(str::to_string);
+        json
+    }
+}
+
+impl RepeatedRollJson {
+    fn from_context(repeated: &RepeatedRoll, context: &RenderContext) -> Self {
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            label: context.label.map(str::to_string),
+            expression: context.expression.source.clone(),
+            repeat: repeated.totals.len() as u32,
+            totals: repeated.totals.clone(),
+            sum: repeated.sum(),
+            sets_rolled: repeated.sets_rolled,
+            seed: context.seed,
+            rolls: context.rolls.to_vec(),
+        }
+    }
+}
+
+impl RollSetEntryJson {
+    fn from_context(outcome: &RollOutcome, context: &RenderContext) -> Self {
+        match outcome {
+            RollOutcome::Single(result) => {
+                Self::Roll(Box::new(RollJson::from_context(result, context)))
+            }
+            RollOutcome::Repeated(repeated) => {
+                Self::Repeated(RepeatedRollJson::from_context(repeated, context))
+            }
+        }
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn render(
+        &self,
+        result: &SuccessCountingAfterResultKeeping,
+        context: &RenderContext,
+    ) -> String {
+        RollJson::from_context(result, context).to_json_string() + "\n"
+    }
+
+    fn render_repeated(&self, repeated: &RepeatedRoll, context: &RenderContext) -> String {
+        to_pretty_json(&RepeatedRollJson::from_context(repeated, context)) + "\n"
+    }
+
+    // one dice roll stays a single roll object, several are wrapped in a set
+    fn render_all(&self, rolls: &[RolledExpression], seed: Option<u64>) -> String {
+        if let [roll] = rolls {
+            return self.render_outcome(roll.outcome, &roll.context);
+        }
+        let set = RollSetJson {
+            schema_version: JSON_SCHEMA_VERSION,
+            rolls: rolls
+                .iter()
+                .map(|roll| RollSetEntryJson::from_context(roll.outcome, &roll.context))
+                .collect(),
+            total: summary_total(rolls),
+            seed,
+        };
+        to_pretty_json(&set) + "\n"
+    }
+}
+
+impl From<&FlatModifier> for FlatModifierJson {
+    fn from(modifier: &FlatModifier) -> Self {
+        Self {
+            value: modifier.value,
+            position: modifier.position,
+            group: modifier.group,
+        }
+    }
+}
+
+impl From<&EvaluationStep> for StepJson {
+    fn from(step: &EvaluationStep) -> Self {
+        Self {
+            expression: step.expression.clone(),
+            value: step.value.to_f64(),
+            numerator: step.value.numerator,
+            denominator: step.value.denominator,
+        }
+    }
+}
+
+impl From<&SuccessCountingRulesApplied> for DieJson {
+    fn from(roll: &SuccessCountingRulesApplied) -> Self {
+        Self {
+            group: roll.group,
+            sign: roll.sign,
+            roll_number: roll.roll_number,
+            dice_size: roll.dice_size,
+            final_roll: roll.final_roll,
+            discarded_rolls: roll.discarded_rolls.clone(),
+            exploded_rolls: roll.exploded_rolls.clone(),
+            replaced_roll: roll.replaced_roll,
+            subtotal: roll.subtotal,
+            capped: roll.capped,
+            kept: roll.kept,
+            success: roll.success,
+            failure: roll.failure,
+            subtracted: roll.subtracted,
+            deductions: roll.deductions,
+            adds_to_total: roll.adds_to_total(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_with;
+
+    fn json_for(expression: &str, rolls: &[u32]) -> RollJson {
+        let result = roll_with(expression, &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        RollJson::new(&result, expression, None, rolls)
+    }
+
+    #[test]
+    fn test_json_keeps_every_die() {
+        let json = json_for("4d6dl1+2", &[3, 1, 6, 6]);
+        assert_eq!(json.schema_version, JSON_SCHEMA_VERSION);
+        assert_eq!(json.total, 17);
+        assert_eq!(
+            json.groups,
+            vec![GroupJson {
+                group: 1,
+                subtotal: 15
+            }]
+        );
+        assert_eq!(json.flat_modifiers[0].value, 2);
+        assert_eq!(json.dice.len(), 4);
+        assert!(!json.dice[1].kept);
+        assert!(!json.dice[1].adds_to_total);
+        assert_eq!(json.rolls, vec![3, 1, 6, 6]);
+    }
+
+    #[test]
+    fn test_json_explosions_and_steps() {
+        let json = json_for("floor(1d6x/2)", &[6, 3]);
+        assert_eq!(json.dice[0].exploded_rolls, vec![3]);
+        assert_eq!(json.dice[0].subtotal, 9);
+        let step = &json.steps[1];
+        assert_eq!(step.expression, "1d6x/2");
+        assert_eq!((step.numerator, step.denominator), (9, 2));
+        assert_eq!(json.total, 4);
+    }
+
+    #[test]
+    fn test_json_sets_and_labels() {
+        let attack = RollOutcome::Single(
+            roll_with("1d20+5", &mut ScriptedRollSource::new(vec![12])).unwrap(),
+        );
+        let damage = RollOutcome::Single(
+            roll_with("2d6", &mut ScriptedRollSource::new(vec![3, 4])).unwrap(),
+        );
+        let (attack_roll, damage_roll) = (
+            parse_expression("1d20+5").unwrap(),
+            parse_expression("2d6").unwrap(),
+        );
+        let mut context = RenderContext::new(&attack_roll, Some(5), &[12]);
+        context.label = Some("attack");
+        let mut rolls = vec![RolledExpression {
+            outcome: &attack,
+            context,
+        }];
+        let single: serde_json::Value =
+            serde_json::from_str(&JsonRenderer.render_all(&rolls, Some(5))).unwrap();
+        assert_eq!(single["label"], "attack");
+        assert_eq!(single["total"], 17);
+
+        rolls.push(RolledExpression {
+            outcome: &damage,
+            context: RenderContext::new(&damage_roll, Some(6), &[3, 4]),
+        });
+        let set: serde_json::Value =
+            serde_json::from_str(&JsonRenderer.render_all(&rolls, Some(1))).unwrap();
+        assert_eq!(set["total"], 24);
+        assert_eq!(set["seed"], 1);
+        assert_eq!(set["rolls"][1]["expression"], "2d6");
+        assert!(set["rolls"][1].get("label").is_none());
+    }
+
+    #[test]
+    fn test_json_repeated_rolls() {
+        let stats = RollOutcome::Repeated(RepeatedRoll {
+            totals: vec![14, 9],
+            sets_rolled: 1,
+        });
+        let expression = parse_expression("4d6dl1").unwrap();
+        let mut context = RenderContext::new(&expression, Some(7), &[]);
+        context.repeat = Some(2);
+        let rolls = [RolledExpression {
+            outcome: &stats,
+            context,
+        }];
+        let json: serde_json::Value =
+            serde_json::from_str(&JsonRenderer.render_all(&rolls, Some(7))).unwrap();
+        assert_eq!(json["expression"], "4d6dl1");
+        assert_eq!(json["repeat"], 2);
+        assert_eq!(json["totals"], serde_json::json!([14, 9]));
+        assert_eq!(json["sum"], 23);
+    }
+
+    #[test]
+    fn test_json_field_names() {
+        let text = json_for("1d20", &[20]).to_json_string();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["schema_version"], 1);
+        assert_eq!(value["dice"][0]["final_roll"], 20);
+        assert_eq!(value["seed"], serde_json::Value::Null);
+        assert_eq!(value["dice"][0]["replaced_roll"], serde_json::Value::Null);
+    }
+}
@@ -1,2 +1,15 @@
 pub mod builders;
 pub mod cli;
+pub mod colors;
+pub mod compact_renderer;
+pub mod explain;
+pub mod inline_renderer;
+pub mod json_output;
+pub mod plain_renderer;
+pub mod renderer;
+pub mod repl;
+pub mod table_renderer;
+pub mod table_style;
+pub mod stats_output;
+pub mod histogram;
+pub mod comparison_output;
@@ -0,0 +1,163 @@
+use crate::dice_rolling_logic::repeated_roll::RepeatedRoll;
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
+use crate::display_logic::renderer::{
+    format_modifier, format_rolls, format_totals, repeated_expression, RenderContext, Renderer,
+};
+
+// the verbose output as lines of text, for logs, chat and screen readers
+pub struct PlainRenderer;
+
+impl Renderer for PlainRenderer {
+    fn render(
+        &self,
+        result: &SuccessCountingAfterResultKeeping,
+        context: &RenderContext,
+    ) -> String {
+        let mut rolls: Vec<&SuccessCountingRulesApplied> = result.rolls.iter().collect();
+        rolls.sort_by_key(|roll| (roll.group, roll.roll_number));
+
+        let mut lines = vec![format!("Roll: {}", context.expression.source)];
+        for group in rolls.chunk_by(|a, b| a.group == b.group) {
+            let dice = group
+                .iter()
+                .map(|roll| describe_die(roll))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let subtotal = result.grouped_subtotals.get(&group[0].group).unwrap_or(&0);
+            lines.push(format!(
+                "Group {} (d{}): {} = {}",
+                group[0].group, group[0].dice_size, dice, subtotal
+            ));
+        }
+        for step in &result.steps {
+            lines.push(format!("Step: {} = {}", step.expression, step.value));
+        }
+        if result.initial_modifier != result.final_modifier {
+            lines.push(format!(
+                "Initial modifier: {}",
+                format_modifier(result.initial_modifier)
+            ));
+        }
+        lines.push(format!(
+            "Modifier: {}",
+            format_modifier(result.final_modifier)
+        ));
+        let counts = [
+            ("Deductions from failure", result.deductions_from_failure),
+            (
+                "Subtractions from failure",
+                result.subtractions_from_failure,
+            ),
+            ("Margin of success", result.margin_of_success),
+            ("Successes", result.successes),
+            ("Failures", result.failures),
+            ("Evens", result.evens),
+            ("Odds", result.odds),
+        ];
+        for (name, count) in counts {
+            if count > 0 {
+                lines.push(format!("{}: {}", name, count))
+            }
+        }
+        lines.push(format!("Total: {}", result.total));
+        if let Some(seed) = context.seed {
+            lines.push(format!("Seed: {}", seed))
+        }
+        if !context.rolls.is_empty() {
+            lines.push(format!("Rolls: {}", format_rolls(context.rolls)))
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn render_repeated(&self, repeated: &RepeatedRoll, context: &RenderContext) -> String {
+        let mut lines = vec![
+            format!("Roll: {}", repeated_expression(context)),
+            format!("Totals: {}", format_totals(&repeated.totals)),
+            format!("Sum: {}", repeated.sum()),
+        ];
+        if repeated.sets_rolled > 1 {
+            lines.push(format!("Sets rolled: {}", repeated.sets_rolled))
+        }
+        if let Some(seed) = context.seed {
+            lines.push(format!("Seed: {}", seed))
+        }
+        if !context.rolls.is_empty() {
+            lines.push(format!("Rolls: {}", format_rolls(context.rolls)))
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+// the final roll followed by anything that happened to it, eg: 6 (exploded into 3)
+fn describe_die(roll: &SuccessCountingRulesApplied) -> String {
+    let mut notes = vec![];
+    if !roll.discarded_rolls.is_empty() {
+        notes.push(format!("re-rolled {}", format_rolls(&roll.discarded_rolls)));
+    }
+    if !roll.exploded_rolls.is_empty() {
+        notes.push(format!(
+            "exploded into {}",
+            format_rolls(&roll.exploded_rolls)
+        ));
+    }
+    if roll.capped {
+        notes.push("capped".to_string());
+    }
+    if let Some(replaced) = roll.replaced_roll {
+        notes.push(format!("was {}", replaced));
+    }
+    if !roll.kept {
+        notes.push("dropped".to_string());
+    }
+    match roll.succeeded() {
+        Some(true) => notes.push("success".to_string()),
+        Some(false) => notes.push("failure".to_string()),
+        None => {}
+    }
+    if notes.is_empty() {
+        roll.final_roll.to_string()
+    } else {
+        format!("{} ({})", roll.final_roll, notes.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_with;
+
+    fn render(expression: &str, rolls: &[u32]) -> String {
+        let result = roll_with(expression, &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression(expression).unwrap();
+        PlainRenderer.render(&result, &RenderContext::new(&expression, None, rolls))
+    }
+
+    #[test]
+    fn test_plain_lists_every_die() {
+        assert_eq!(
+            render("4d6dl1+2", &[3, 1, 6, 6]),
+            "Roll: 4d6dl1+2\n\
+             Group 1 (d6): 3, 1 (dropped), 6, 6 = 15\n\
+             Modifier: +2\n\
+             Total: 17\n\
+             Rolls: 3,1,6,6\n"
+        );
+    }
+
+    #[test]
+    fn test_plain_notes_explosions_and_re_rolls() {
+        let output = render("1d6x + 1d20r1", &[6, 2, 1, 15]);
+        assert!(output.contains("Group 1 (d6): 6 (exploded into 2) = 8"));
+        assert!(output.contains("Group 2 (d20): 15 (re-rolled 1) = 15"));
+        assert!(output.contains("Total: 23"));
+    }
+
+    #[test]
+    fn test_plain_labels_successes_and_failures() {
+        let output = render("3d10cs>=8", &[9, 2, 8]);
+        assert!(output.contains("Group 1 (d10): 9 (success), 2 (failure), 8 (success)"));
+    }
+}
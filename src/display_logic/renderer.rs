@@ -0,0 +1,277 @@
+use crate::dice_rolling_logic::repeated_roll::{RepeatedRoll, RollOutcome};
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::display_logic::compact_renderer::CompactRenderer;
+use crate::display_logic::inline_renderer::InlineRenderer;
+use crate::display_logic::json_output::JsonRenderer;
+use crate::display_logic::plain_renderer::PlainRenderer;
+use crate::display_logic::table_renderer::TableRenderer;
+use crate::display_logic::table_style::TableStyle;
+use crate::errors::DiceError;
+use crate::parsing_logic::ast::RollExpression;
+
+pub const OUTPUT_FORMATS: [&str; 5] = ["table", "compact", "json", "plain", "inline"];
+
+// turns a finished roll into a document, it's up to the caller where it goes
+pub trait Renderer {
+    fn render(&self, result: &SuccessCountingAfterResultKeeping, context: &RenderContext)
+        -> String;
+
+    // a dice roll rolled several times, eg: 6x4d6dl1, only the totals are shown
+    fn render_repeated(&self, repeated: &RepeatedRoll, context: &RenderContext) -> String {
+        let mut lines = vec![
+            format!("Totals: {}", format_totals(&repeated.totals)),
+            format!("Sum: {}", repeated.sum()),
+        ];
+        if repeated.sets_rolled > 1 {
+            lines.push(format!("Sets rolled: {}", repeated.sets_rolled));
+        }
+        if let Some(seed) = context.seed {
+            lines.push(format!("Seed: {}", seed));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn render_outcome(&self, outcome: &RollOutcome, context: &RenderContext) -> String {
+        match outcome {
+            RollOutcome::Single(result) => self.render(result, context),
+            RollOutcome::Repeated(repeated) => self.render_repeated(repeated, context),
+        }
+    }
+
+    // several dice rolls from one call, each under its own heading, then a summary,
+    // a single dice roll without a label is the same as render
+    fn render_all(&self, rolls: &[RolledExpression], seed: Option<u64>) -> String {
+        if let [roll] = rolls {
+            if roll.context.label.is_none() {
+                return self.render_outcome(roll.outcome, &roll.context);
+            }
+        }
+        let mut blocks: Vec<String> = rolls
+            .iter()
+            .map(|roll| {
+                format!(
+                    "{}\n{}",
+                    heading(&roll.context),
+                    self.render_outcome(roll.outcome, &roll.context)
+                )
+            })
+            .collect();
+        if rolls.len() > 1 {
+            blocks.push(self.render_summary(rolls, seed));
+        }
+        blocks.join("\n")
+    }
+
+    // every total and what they add up to, seed is the one that replays the whole call
+    fn render_summary(&self, rolls: &[RolledExpression], seed: Option<u64>) -> String {
+        let mut lines = vec!["Summary".to_string()];
+        for roll in rolls {
+            lines.push(format!(
+                "{}: {}",
+                summary_name(&roll.context),
+                roll.outcome.total()
+            ));
+        }
+        lines.push(format!("Total: {}", summary_total(rolls)));
+        if let Some(seed) = seed {
+            lines.push(format!("Seed: {}", seed));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+// one dice roll out of the ones rolled in the same call
+#[derive(Clone, Copy, Debug)]
+pub struct RolledExpression<'a> {
+    pub outcome: &'a RollOutcome,
+    pub context: RenderContext<'a>,
+}
+
+// eg: "attack: 1d20+5", or only the dice roll when it has no label
+pub fn heading(context: &RenderContext) -> String {
+    match context.label {
+        Some(label) => format!("{}: {}", label, repeated_expression(context)),
+        None => repeated_expression(context),
+    }
+}
+
+// eg: "attack (1d20+5)" in the summary
+pub fn summary_name(context: &RenderContext) -> String {
+    match context.label {
+        Some(label) => format!("{} ({})", label, repeated_expression(context)),
+        None => repeated_expression(context),
+    }
+}
+
+// eg: "6x4d6dl1", the dice roll with how many times it was rolled in front
+pub fn repeated_expression(context: &RenderContext) -> String {
+    match context.repeat {
+        Some(count) => format!("{}x{}", count, context.expression.source.trim()),
+        None => context.expression.source.trim().to_string(),
+    }
+}
+
+pub fn summary_total(rolls: &[RolledExpression]) -> i64 {
+    rolls.iter().map(|roll| roll.outcome.total()).sum()
+}
+
+// what the renderers need to know besides the result itself
+#[derive(Clone, Copy, Debug)]
+pub struct RenderContext<'a> {
+    pub expression: &'a RollExpression, // its source is the dice roll as it was written
+    pub seed: Option<u64>, // None when there's no seed to replay with, eg: --rolls was used
+    pub rolls: &'a [u32],  // every face in the order it came up
+    pub color: bool,       // whether ANSI colors can be used, off unless set
+    pub table_style: TableStyle,
+    pub label: Option<&'a str>, // eg: "attack" from -d "attack: 1d20+5"
+    pub repeat: Option<u32>,    // eg: 6 from -d 6x4d6dl1 or --repeat 6
+}
+
+impl<'a> RenderContext<'a> {
+    pub fn new(expression: &'a RollExpression, seed: Option<u64>, rolls: &'a [u32]) -> Self {
+        Self {
+            expression,
+            seed,
+            rolls,
+            color: false,
+            table_style: TableStyle::default(),
+            label: None,
+            repeat: None,
+        }
+    }
+}
+
+pub fn renderer_for(format: &str) -> Result<Box<dyn Renderer>, DiceError> {
+    match format.to_lowercase().as_str() {
+        "table" => Ok(Box::new(TableRenderer::default())),
+        "compact" => Ok(Box::new(CompactRenderer)),
+        "json" => Ok(Box::new(JsonRenderer)),
+        "plain" => Ok(Box::new(PlainRenderer)),
+        "inline" => Ok(Box::new(InlineRenderer)),
+        _ => Err(DiceError::InvalidOption(format!(
+            "'{}' isn't an output format, use one of {}",
+            format,
+            OUTPUT_FORMATS.join(", ")
+        ))),
+    }
+}
+
+pub fn format_modifier(modifier: i32) -> String {
+    if modifier >= 0 {
+        format!("+{}", modifier)
+    } else {
+        format!("{}", modifier)
+    }
+}
+
+// in the same format --rolls reads back
+pub fn format_rolls(rolls: &[u32]) -> String {
+    rolls
+        .iter()
+        .map(|roll| roll.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+pub fn format_totals(totals: &[i32]) -> String {
+    totals
+        .iter()
+        .map(|total| total.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_with;
+
+    #[test]
+    fn test_every_format_renders_the_total() {
+        let result = roll_with("2d6+1", &mut ScriptedRollSource::new(vec![2, 5])).unwrap();
+        let expression = parse_expression("2d6+1").unwrap();
+        let context = RenderContext::new(&expression, Some(1), &[2, 5]);
+        for format in OUTPUT_FORMATS {
+            let output = renderer_for(format).unwrap().render(&result, &context);
+            assert!(output.contains('8'), "{} output: {}", format, output);
+            assert!(output.ends_with('\n'));
+        }
+    }
+
+    #[test]
+    fn test_render_all_adds_headings_and_a_summary() {
+        let first = RollOutcome::Single(
+            roll_with("1d20+5", &mut ScriptedRollSource::new(vec![12])).unwrap(),
+        );
+        let second = RollOutcome::Single(
+            roll_with("2d6", &mut ScriptedRollSource::new(vec![3, 4])).unwrap(),
+        );
+        let (attack_roll, damage_roll) = (
+            parse_expression("1d20+5").unwrap(),
+            parse_expression("2d6").unwrap(),
+        );
+        let mut attack = RenderContext::new(&attack_roll, Some(1), &[12]);
+        attack.label = Some("attack");
+        let rolls = [
+            RolledExpression {
+                outcome: &first,
+                context: attack,
+            },
+            RolledExpression {
+                outcome: &second,
+                context: RenderContext::new(&damage_roll, Some(2), &[3, 4]),
+            },
+        ];
+        let output = renderer_for("compact").unwrap().render_all(&rolls, Some(9));
+        assert!(output.starts_with("attack: 1d20+5\n12/20, \n"));
+        assert!(output.contains("\n2d6\n3/6, 4/6, \n"));
+        assert!(output.ends_with("Summary\nattack (1d20+5): 17\n2d6: 7\nTotal: 24\nSeed: 9\n"));
+
+        // one dice roll without a label looks the same as it always has
+        let single = renderer_for("compact")
+            .unwrap()
+            .render_all(&rolls[1..], Some(9));
+        assert_eq!(single, "3/6, 4/6, \nModifier: +0, Total: 7\nSeed: 2\n");
+    }
+
+    #[test]
+    fn test_repeated_rolls_show_their_totals() {
+        let repeated = RollOutcome::Repeated(RepeatedRoll {
+            totals: vec![12, 15, 9],
+            sets_rolled: 2,
+        });
+        let expression = parse_expression("4d6dl1").unwrap();
+        let mut context = RenderContext::new(&expression, Some(3), &[]);
+        context.repeat = Some(3);
+        let rolls = [RolledExpression {
+            outcome: &repeated,
+            context,
+        }];
+        let renderer = renderer_for("compact").unwrap();
+        assert_eq!(
+            renderer.render_all(&rolls, Some(3)),
+            "Totals: 12, 15, 9\nSum: 36\nSets rolled: 2\nSeed: 3\n"
+        );
+        assert_eq!(summary_name(&context), "3x4d6dl1");
+        assert_eq!(summary_total(&rolls), 36);
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(matches!(
+            renderer_for("yaml"),
+            Err(DiceError::InvalidOption(_))
+        ));
+        assert!(renderer_for("JSON").is_ok());
+    }
+
+    #[test]
+    fn test_format_modifier_and_rolls() {
+        assert_eq!(format_modifier(3), "+3");
+        assert_eq!(format_modifier(0), "+0");
+        assert_eq!(format_modifier(-2), "-2");
+        assert_eq!(format_rolls(&[6, 6, 2]), "6,6,2");
+    }
+}
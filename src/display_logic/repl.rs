@@ -0,0 +1,274 @@
+use crate::config::history_path;
+use crate::display_logic::cli::{report_error, roll_and_render, RollArgs};
+use crate::errors::DiceError;
+use crate::parsing_logic::parse_error::ParseError;
+use crate::parsing_logic::parser::parse_expression;
+use crate::utils::yn_tf_to_bool;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use regex::Regex;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+const REPL_HELP: &str = "\
+enter a dice roll, eg: 4d6dl1 or 1d20+$last
+  $last      the total of the last roll
+  $1, $2 ..  the total of roll 1, 2 ..
+  :r         roll the last dice roll again
+  :v         switch between the compact and table output
+  :history   list the rolls so far
+  :q         quit, as does Ctrl-D";
+
+// a line typed at the prompt
+#[derive(Debug, PartialEq)]
+pub enum ReplInput {
+    Roll(String),
+    Reroll,
+    ToggleVerbose,
+    History,
+    Help,
+    Quit,
+    Empty,
+}
+
+pub fn parse_repl_input(line: &str) -> Result<ReplInput, DiceError> {
+    let line = line.trim();
+    match line.to_lowercase().as_str() {
+        "" => Ok(ReplInput::Empty),
+        ":r" | ":reroll" => Ok(ReplInput::Reroll),
+        ":v" | ":verbose" => Ok(ReplInput::ToggleVerbose),
+        ":history" => Ok(ReplInput::History),
+        ":h" | ":help" | "help" => Ok(ReplInput::Help),
+        ":q" | ":quit" | "quit" | "exit" => Ok(ReplInput::Quit),
+        command if command.starts_with(':') => Err(DiceError::InvalidOption(format!(
+            "'{}' isn't a command, enter :help for the list",
+            line
+        ))),
+        _ => Ok(ReplInput::Roll(line.to_string())),
+    }
+}
+
+// swaps $last and $1, $2 .. for the totals of earlier rolls
+pub fn expand_references(line: &str, totals: &[i32]) -> Result<String, DiceError> {
+    let reference_regex = Regex::new(r"(?i)\$(last|\d+)").unwrap();
+    let mut expanded = String::new();
+    let mut written_up_to = 0;
+    for reference in reference_regex.captures_iter(line) {
+        let whole = reference.get(0).unwrap();
+        let name = &reference[1];
+        let total = if name.eq_ignore_ascii_case("last") {
+            totals.last()
+        } else {
+            name.parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1))
+                .and_then(|index| totals.get(index))
+        };
+        let total = total.ok_or_else(|| {
+            DiceError::from(ParseError::new(
+                whole.start(),
+                format!("there's no roll {} yet", whole.as_str()),
+            ))
+        })?;
+        expanded.push_str(&line[written_up_to..whole.start()]);
+        // negative totals keep their sign, eg: 1d4-$1 with $1 = -2
+        if *total < 0 {
+            expanded.push_str(&format!("({})", total));
+        } else {
+            expanded.push_str(&total.to_string());
+        }
+        written_up_to = whole.end();
+    }
+    expanded.push_str(&line[written_up_to..]);
+    Ok(expanded)
+}
+
+// what's kept between rolls
+struct ReplSession<'a> {
+    cli: &'a RollArgs,
+    rolls: Vec<(String, i32)>, // each dice roll, with its references swapped out, and its total
+    verbose: bool,
+    format: Option<String>,
+    seeds: ChaCha8Rng, // every roll gets its own seed, printed so it can be replayed
+}
+
+impl ReplSession<'_> {
+    // errors are reported here, against the text they point into
+    fn roll(&mut self, line: &str) {
+        let totals: Vec<i32> = self.rolls.iter().map(|(_, total)| *total).collect();
+        let expanded = match expand_references(line, &totals) {
+            Ok(expanded) => expanded,
+            Err(error) => return report_error(&error, line),
+        };
+        if expanded != line {
+            println!("{}", expanded);
+        }
+        if let Err(error) = self.roll_expanded(&expanded) {
+            report_error(&error, &expanded)
+        }
+    }
+
+    fn roll_expanded(&mut self, expanded: &str) -> Result<(), DiceError> {
+        let dice_roll = parse_expression(expanded)?;
+        let seed = self.seeds.random();
+        let (result, output) = roll_and_render(
+            self.cli,
+            &dice_roll,
+            seed,
+            self.format.as_deref(),
+            self.verbose,
+        )?;
+        print!("{}", output);
+        self.rolls.push((expanded.to_string(), result.total));
+        println!("${} = {}", self.rolls.len(), result.total);
+        Ok(())
+    }
+
+    fn handle(&mut self, line: &str) -> Result<bool, DiceError> {
+        match parse_repl_input(line)? {
+            ReplInput::Roll(line) => self.roll(&line),
+            ReplInput::Reroll => match self.rolls.last() {
+                Some((expression, _)) => self.roll(&expression.clone()),
+                None => println!("nothing to roll again yet"),
+            },
+            ReplInput::ToggleVerbose => {
+                self.verbose = !self.verbose;
+                // :v picks between table and compact, over --format
+                self.format = None;
+                println!("verbose {}", if self.verbose { "on" } else { "off" });
+            }
+            ReplInput::History => {
+                for (number, (expression, total)) in self.rolls.iter().enumerate() {
+                    println!("${} = {}  {}", number + 1, total, expression);
+                }
+            }
+            ReplInput::Help => println!("{}", REPL_HELP),
+            ReplInput::Quit => return Ok(false),
+            ReplInput::Empty => {}
+        }
+        Ok(true)
+    }
+}
+
+pub fn run_repl(cli: &RollArgs) {
+    if let Err(error) = check_repl_options(cli) {
+        report_error(&error, "");
+        std::process::exit(error.exit_code())
+    }
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(error) => {
+            eprintln!("error: can't start the prompt: {}", error);
+            std::process::exit(1)
+        }
+    };
+    let history = history_path();
+    if let Some(path) = &history {
+        // there's no history yet the first time
+        let _ = editor.load_history(path);
+    }
+    let mut session = ReplSession {
+        cli,
+        rolls: vec![],
+        verbose: yn_tf_to_bool(cli.verbose.clone()).unwrap_or(false),
+        format: cli.format.clone(),
+        seeds: ChaCha8Rng::seed_from_u64(cli.seed.unwrap_or_else(rand::random)),
+    };
+    println!("enter a dice roll, :help for commands or :q to quit");
+    for labeled in &cli.dice_rolls {
+        session.roll(&labeled.dice_roll.source);
+    }
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                match session.handle(&line) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(error) => report_error(&error, line.trim()),
+                }
+            }
+            // Ctrl-C clears the line, Ctrl-D quits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                break;
+            }
+        }
+    }
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(error) = editor.save_history(path) {
+            eprintln!(
+                "warning: can't save the history to {}: {}",
+                path.display(),
+                error
+            )
+        }
+    }
+}
+
+// the flags are checked once up front instead of failing every roll
+fn check_repl_options(cli: &RollArgs) -> Result<(), DiceError> {
+    if cli.rolls.is_some() {
+        return Err(DiceError::ConflictingOptions(
+            "--rolls can't be used with repl, every roll would get the same dice".to_string(),
+        ));
+    }
+    if cli.repeat.is_some() {
+        return Err(DiceError::ConflictingOptions(
+            "--repeat can't be used with repl, the $N references need one total per roll"
+                .to_string(),
+        ));
+    }
+    yn_tf_to_bool(cli.verbose.clone())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repl_input() {
+        assert_eq!(
+            parse_repl_input(" 4d6dl1 ").unwrap(),
+            ReplInput::Roll("4d6dl1".to_string())
+        );
+        assert_eq!(parse_repl_input(":V").unwrap(), ReplInput::ToggleVerbose);
+        assert_eq!(parse_repl_input(":reroll").unwrap(), ReplInput::Reroll);
+        assert_eq!(parse_repl_input("exit").unwrap(), ReplInput::Quit);
+        assert_eq!(parse_repl_input("").unwrap(), ReplInput::Empty);
+        assert!(matches!(
+            parse_repl_input(":x"),
+            Err(DiceError::InvalidOption(_))
+        ));
+    }
+
+    #[test]
+    fn test_expand_references() {
+        let totals = [12, -2, 7];
+        assert_eq!(expand_references("1d20+$last", &totals).unwrap(), "1d20+7");
+        assert_eq!(
+            expand_references("$1 + 1d4 - $2", &totals).unwrap(),
+            "12 + 1d4 - (-2)"
+        );
+        assert_eq!(expand_references("$LAST*2", &totals).unwrap(), "7*2");
+        assert_eq!(expand_references("2d6", &[]).unwrap(), "2d6");
+    }
+
+    #[test]
+    fn test_missing_references_point_at_the_reference() {
+        assert_eq!(
+            expand_references("1d20+$4", &[3]),
+            Err(ParseError::new(5, "there's no roll $4 yet").into())
+        );
+        assert!(expand_references("$last", &[]).is_err());
+        assert!(expand_references("$0", &[3]).is_err());
+    }
+}
@@ -0,0 +1,554 @@
+use crate::dice_rolling_logic::distribution::Distribution;
+use crate::display_logic::histogram::Histogram;
+use crate::display_logic::json_output::{to_pretty_json, JSON_SCHEMA_VERSION};
+use crate::display_logic::renderer::{heading, RenderContext};
+use comfy_table::{Cell, Table};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// the percentiles shown for every distribution
+pub const PERCENTILES: [f64; 7] = [5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0];
+
+// chances that would show as 0.00% are left out of the lists
+pub const SMALLEST_LISTED: f64 = 0.00005;
+
+// a total to meet and a number of successes to get, eg: --vs 17 for AC 17, --need 3
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Target {
+    pub vs: Option<i64>,
+    pub need: Option<u32>,
+}
+
+// what a dice roll comes to on average and how often each total comes up,
+// as tables, lines of text or json, with a histogram in place of the chances when given one
+pub fn render_stats(
+    distribution: &Distribution,
+    context: &RenderContext,
+    format: &str,
+    histogram: Option<Histogram>,
+    target: Target,
+) -> String {
+    match format {
+        "json" => to_pretty_json(&StatsJson::new(distribution, context, target)) + "\n",
+        "table" => stats_tables(distribution, context, histogram, target),
+        _ => stats_lines(distribution, context, histogram, target),
+    }
+}
+
+// several dice rolls are listed one after another, or wrapped in a set for json
+pub fn render_all_stats(
+    stats: &[(Distribution, RenderContext)],
+    format: &str,
+    histogram: Option<Histogram>,
+    target: Target,
+) -> String {
+    if format == "json" && stats.len() > 1 {
+        let set = StatsSetJson {
+            schema_version: JSON_SCHEMA_VERSION,
+            stats: stats
+                .iter()
+                .map(|(distribution, context)| StatsJson::new(distribution, context, target))
+                .collect(),
+        };
+        return to_pretty_json(&set) + "\n";
+    }
+    stats
+        .iter()
+        .map(|(distribution, context)| {
+            render_stats(distribution, context, format, histogram, target)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// eg: 12.35%
+pub fn format_percent(chance: f64) -> String {
+    format!("{:.2}%", chance * 100.0)
+}
+
+// the name of each target with its chance, eg: 17 or more, 3 or more successes
+pub fn target_chances(distribution: &Distribution, target: Target) -> Vec<(String, f64)> {
+    let mut chances = vec![];
+    if let Some(vs) = target.vs {
+        chances.push((format!("{} or more", vs), distribution.chance_at_least(vs)));
+    }
+    if let Some(need) = target.need {
+        chances.push((
+            format!("{} or more successes", need),
+            distribution.chance_of_successes(need),
+        ));
+    }
+    chances
+}
+
+// each target with its chance and what to expect,
+// eg: 17 or more, 55.00%, margin +0.50, +4.00 on a hit
+fn target_rows(distribution: &Distribution, target: Target) -> Vec<(String, String, String)> {
+    let mut expected = vec![];
+    if let Some(vs) = target.vs {
+        let margin = format!("margin {:+.2}", distribution.mean() - vs as f64);
+        expected.push(match distribution.margin_on_hit(vs) {
+            Some(on_hit) => format!("{}, {:+.2} on a hit", margin, on_hit),
+            None => format!("{}, never hits", margin),
+        });
+    }
+    if target.need.is_some() {
+        let mut successes = format!("{:.2} successes", distribution.mean_successes());
+        if distribution.failures.keys().any(|&failures| failures > 0) {
+            successes.push_str(&format!(", {:.2} failures", distribution.mean_failures()));
+        }
+        expected.push(successes);
+    }
+    target_chances(distribution, target)
+        .into_iter()
+        .zip(expected)
+        .map(|((name, chance), expected)| (name, format_percent(chance), expected))
+        .collect()
+}
+
+// eg: Simulated from 100000 rolls, seed: 1234
+fn simulated_line(distribution: &Distribution, context: &RenderContext) -> Option<String> {
+    let trials = distribution.trials?;
+    Some(match context.seed {
+        Some(seed) => format!("Simulated from {} rolls, seed: {}", trials, seed),
+        None => format!("Simulated from {} rolls", trials),
+    })
+}
+
+// a chart of the totals, and one of the successes when they're counted
+fn histograms(
+    distribution: &Distribution,
+    context: &RenderContext,
+    histogram: Histogram,
+) -> String {
+    let mut charts = vec![histogram.render(
+        "Total",
+        &listed(&distribution.totals),
+        distribution.mean(),
+        context.table_style,
+    )];
+    if distribution.counts_successes() {
+        charts.push(histogram.render(
+            "Successes",
+            &listed(&success_chances(distribution)),
+            distribution.mean_successes(),
+            context.table_style,
+        ));
+    }
+    charts.join("\n").trim_end().to_string()
+}
+
+fn stats_tables(
+    distribution: &Distribution,
+    context: &RenderContext,
+    histogram: Option<Histogram>,
+    target: Target,
+) -> String {
+    let new_table = || -> Table { context.table_style.new_table(context.color) };
+    let mut sections = vec![format!("Stats: {}", heading(context))];
+    sections.extend(simulated_line(distribution, context));
+
+    let mut summary = new_table();
+    let mut header = vec![
+        Cell::new("Min"),
+        Cell::new("Max"),
+        Cell::new("Mean"),
+        Cell::new("Std dev"),
+    ];
+    let mut row = vec![
+        Cell::new(distribution.min()),
+        Cell::new(distribution.max()),
+        Cell::new(format!("{:.2}", distribution.mean())),
+        Cell::new(format!("{:.2}", distribution.std_dev())),
+    ];
+    if distribution.counts_successes() {
+        header.push(Cell::new("Mean successes"));
+        row.push(Cell::new(format!("{:.2}", distribution.mean_successes())));
+    }
+    summary.set_header(header).add_row(row);
+    sections.push(summary.to_string());
+
+    let target_rows = target_rows(distribution, target);
+    if !target_rows.is_empty() {
+        let mut targets = new_table();
+        targets.set_header(vec![
+            Cell::new("Target"),
+            Cell::new("Chance"),
+            Cell::new("Expected"),
+        ]);
+        for (name, chance, expected) in target_rows {
+            targets.add_row(vec![
+                Cell::new(name),
+                Cell::new(chance),
+                Cell::new(expected),
+            ]);
+        }
+        sections.push(targets.to_string());
+    }
+
+    let mut percentiles = new_table();
+    percentiles
+        .set_header(
+            PERCENTILES
+                .iter()
+                .map(|percent| Cell::new(format!("{}%", percent))),
+        )
+        .add_row(
+            PERCENTILES
+                .iter()
+                .map(|&percent| Cell::new(distribution.percentile(percent))),
+        );
+    sections.push(percentiles.to_string());
+
+    let chance_table = |name: &str, chances: &BTreeMap<i64, f64>| {
+        let mut table = new_table();
+        table.set_header(vec![
+            Cell::new(name),
+            Cell::new("Chance"),
+            Cell::new("At least"),
+        ]);
+        for (value, chance, at_least) in listed(chances) {
+            table.add_row(vec![
+                Cell::new(value),
+                Cell::new(format_percent(chance)),
+                Cell::new(format_percent(at_least)),
+            ]);
+        }
+        table.to_string()
+    };
+    if let Some(histogram) = histogram {
+        sections.push(histograms(distribution, context, histogram));
+    } else {
+        sections.push(chance_table("Total", &distribution.totals));
+    }
+    if histogram.is_none() && distribution.counts_successes() {
+        sections.push(chance_table("Successes", &success_chances(distribution)));
+    }
+    sections.join("\n") + "\n"
+}
+
+fn stats_lines(
+    distribution: &Distribution,
+    context: &RenderContext,
+    histogram: Option<Histogram>,
+    target: Target,
+) -> String {
+    let mut lines = vec![
+        format!("Stats: {}", heading(context)),
+        format!(
+            "Min: {}, Max: {}, Mean: {:.2}, Std dev: {:.2}",
+            distribution.min(),
+            distribution.max(),
+            distribution.mean(),
+            distribution.std_dev()
+        ),
+    ];
+    if let Some(simulated) = simulated_line(distribution, context) {
+        lines.insert(1, simulated);
+    }
+    if distribution.counts_successes() {
+        lines.push(format!(
+            "Mean successes: {:.2}",
+            distribution.mean_successes()
+        ));
+    }
+    for (name, chance, expected) in target_rows(distribution, target) {
+        lines.push(format!("{}: {} (expected: {})", name, chance, expected));
+    }
+    lines.push(format!(
+        "Percentiles: {}",
+        PERCENTILES
+            .iter()
+            .map(|&percent| format!("{}%: {}", percent, distribution.percentile(percent)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    ));
+    if let Some(histogram) = histogram {
+        lines.push(histograms(distribution, context, histogram));
+        return lines.join("\n") + "\n";
+    }
+    for (total, chance, at_least) in listed(&distribution.totals) {
+        lines.push(format!(
+            "Total {}: {} (at least: {})",
+            total,
+            format_percent(chance),
+            format_percent(at_least)
+        ));
+    }
+    if distribution.counts_successes() {
+        for (successes, chance, at_least) in listed(&success_chances(distribution)) {
+            lines.push(format!(
+                "Successes {}: {} (at least: {})",
+                successes,
+                format_percent(chance),
+                format_percent(at_least)
+            ));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+// each value with its chance and the chance of it or more, leaving out the ones too rare to show
+fn listed(chances: &BTreeMap<i64, f64>) -> Vec<(i64, f64, f64)> {
+    let mut at_least: f64 = chances.values().sum();
+    let mut listed = vec![];
+    for (&value, &chance) in chances {
+        if chance >= SMALLEST_LISTED {
+            listed.push((value, chance, at_least.min(1.0)));
+        }
+        at_least -= chance;
+    }
+    listed
+}
+
+fn success_chances(distribution: &Distribution) -> BTreeMap<i64, f64> {
+    distribution
+        .successes
+        .iter()
+        .map(|(&successes, &chance)| (successes as i64, chance))
+        .collect()
+}
+
+// the --format json output of stats
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsJson {
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub expression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulated: Option<u32>, // how many rolls it was simulated from, left out when exact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub min: i64,
+    pub max: i64,
+    pub mean: f64,
+    pub variance: f64,
+    pub std_dev: f64,
+    pub percentiles: Vec<PercentileJson>,
+    pub totals: Vec<ChanceJson>, // every total that can come up, lowest first
+    pub mean_successes: f64,
+    pub successes: Vec<ChanceJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vs: Option<VsJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub need: Option<NeedJson>,
+}
+
+// the chance of a total of at least target, from --vs
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct VsJson {
+    pub target: i64,
+    pub chance: f64,
+    pub expected_margin: f64,       // the mean total minus the target
+    pub margin_on_hit: Option<f64>, // null when the target can't be met
+}
+
+// the chance of at least successes successes, from --need
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NeedJson {
+    pub successes: u32,
+    pub chance: f64,
+    pub expected_successes: f64,
+    pub expected_failures: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PercentileJson {
+    pub percent: f64,
+    pub total: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChanceJson {
+    pub value: i64,
+    pub chance: f64, // between 0 and 1
+}
+
+// several dice rolls from one call
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsSetJson {
+    pub schema_version: u32,
+    pub stats: Vec<StatsJson>,
+}
+
+impl StatsJson {
+    pub fn new(distribution: &Distribution, context: &RenderContext, target: Target) -> Self {
+        let chances = |chances: &BTreeMap<i64, f64>| {
+            chances
+                .iter()
+                .map(|(&value, &chance)| ChanceJson { value, chance })
+                .collect()
+        };
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            label: context.label.map(str::to_string),
+            expression: context.expression.source.clone(),
+            simulated: distribution.trials,
+            seed: distribution.trials.and(context.seed),
+            min: distribution.min(),
+            max: distribution.max(),
+            mean: distribution.mean(),
+            variance: distribution.variance(),
+            std_dev: distribution.std_dev(),
+            percentiles: PERCENTILES
+                .iter()
+                .map(|&percent| PercentileJson {
+                    percent,
+                    total: distribution.percentile(percent),
+                })
+                .collect(),
+            totals: chances(&distribution.totals),
+            mean_successes: distribution.mean_successes(),
+            successes: chances(&success_chances(distribution)),
+            vs: target.vs.map(|vs| VsJson {
+                target: vs,
+                chance: distribution.chance_at_least(vs),
+                expected_margin: distribution.mean() - vs as f64,
+                margin_on_hit: distribution.margin_on_hit(vs),
+            }),
+            need: target.need.map(|need| NeedJson {
+                successes: need,
+                chance: distribution.chance_of_successes(need),
+                expected_successes: distribution.mean_successes(),
+                expected_failures: distribution.mean_failures(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing_logic::parser::parse_expression;
+
+    fn two_d6() -> Distribution {
+        let mut distribution = Distribution::default();
+        for first in 1..=6 {
+            for second in 1..=6 {
+                *distribution.totals.entry(first + second).or_insert(0.0) += 1.0 / 36.0;
+            }
+        }
+        distribution.successes.insert(0, 1.0);
+        distribution.failures.insert(0, 1.0);
+        distribution
+    }
+
+    #[test]
+    fn test_stats_lines() {
+        let expression = parse_expression("2d6").unwrap();
+        let output = render_stats(
+            &two_d6(),
+            &RenderContext::new(&expression, None, &[]),
+            "plain",
+            None,
+            Target::default(),
+        );
+        assert!(output.starts_with(
+            "Stats: 2d6\nMin: 2, Max: 12, Mean: 7.00, Std dev: 2.42\n\
+             Percentiles: 5%: 3, 10%: 4, 25%: 5, 50%: 7, 75%: 9, 90%: 10, 95%: 11\n\
+             Total 2: 2.78% (at least: 100.00%)\n"
+        ));
+        assert!(output.ends_with("Total 12: 2.78% (at least: 2.78%)\n"));
+        assert!(!output.contains("Successes"));
+    }
+
+    #[test]
+    fn test_stats_tables_and_json() {
+        let expression = parse_expression("2d6").unwrap();
+        let mut context = RenderContext::new(&expression, None, &[]);
+        context.label = Some("damage");
+        let output = render_stats(&two_d6(), &context, "table", None, Target::default());
+        assert!(output.starts_with("Stats: damage: 2d6\n"));
+        assert!(output.contains("│ 7     ┆ 16.67% ┆ 58.33%   │"));
+
+        let json: serde_json::Value = serde_json::from_str(&render_stats(
+            &two_d6(),
+            &context,
+            "json",
+            None,
+            Target::default(),
+        ))
+        .unwrap();
+        assert_eq!(json["label"], "damage");
+        assert_eq!(json["percentiles"][3]["total"], 7);
+        assert_eq!(json["totals"][0]["value"], 2);
+        assert!(json.get("simulated").is_none());
+    }
+
+    #[test]
+    fn test_histogram_takes_the_place_of_the_chances() {
+        let expression = parse_expression("2d6").unwrap();
+        let context = RenderContext::new(&expression, None, &[]);
+        for format in ["plain", "table"] {
+            let histogram = Some(Histogram::default());
+            let output = render_stats(&two_d6(), &context, format, histogram, Target::default());
+            assert!(output.contains("\nTotal  Chance\n    2   2.78%  "));
+            assert!(output.contains("◀ mean 7.00\n"));
+            assert!(!output.contains("At least"));
+        }
+    }
+
+    #[test]
+    fn test_simulated_stats_show_their_rolls_and_seed() {
+        let mut distribution = two_d6();
+        distribution.trials = Some(36);
+        let expression = parse_expression("2d6").unwrap();
+        let context = RenderContext::new(&expression, Some(1234), &[]);
+        assert!(
+            render_stats(&distribution, &context, "plain", None, Target::default())
+                .starts_with("Stats: 2d6\nSimulated from 36 rolls, seed: 1234\nMin: 2")
+        );
+        assert!(
+            render_stats(&distribution, &context, "table", None, Target::default())
+                .starts_with("Stats: 2d6\nSimulated from 36 rolls, seed: 1234\n")
+        );
+        let json: serde_json::Value = serde_json::from_str(&render_stats(
+            &distribution,
+            &context,
+            "json",
+            None,
+            Target::default(),
+        ))
+        .unwrap();
+        assert_eq!(json["simulated"], 36);
+        assert_eq!(json["seed"], 1234);
+    }
+
+    #[test]
+    fn test_targets() {
+        let expression = parse_expression("2d6").unwrap();
+        let context = RenderContext::new(&expression, None, &[]);
+        let target = Target {
+            vs: Some(10),
+            need: Some(1),
+        };
+        let output = render_stats(&two_d6(), &context, "plain", None, target);
+        assert!(output.contains(
+            "\n10 or more: 16.67% (expected: margin -3.00, +0.67 on a hit)\n\
+             1 or more successes: 0.00% (expected: 0.00 successes)\n\
+             Percentiles:"
+        ));
+        let output = render_stats(&two_d6(), &context, "table", None, target);
+        assert!(output.contains("│ 10 or more          ┆ 16.67% ┆ margin -3.00, +0.67 on a hit │"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_stats(&two_d6(), &context, "json", None, target)).unwrap();
+        assert_eq!(json["vs"]["target"], 10);
+        assert!((json["vs"]["margin_on_hit"].as_f64().unwrap() - 4.0 / 6.0).abs() < 1e-9);
+        assert_eq!(json["need"]["chance"], 0.0);
+        let json: serde_json::Value = serde_json::from_str(&render_stats(
+            &two_d6(),
+            &context,
+            "json",
+            None,
+            Target {
+                vs: Some(13),
+                need: None,
+            },
+        ))
+        .unwrap();
+        assert!(json["vs"]["margin_on_hit"].is_null());
+        assert!(json.get("need").is_none());
+    }
+}
@@ -0,0 +1,523 @@
+use crate::dice_rolling_logic::repeated_roll::RepeatedRoll;
+use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+use crate::dice_rolling_logic::success_counting_rules::SuccessCountingRulesApplied;
+use crate::display_logic::colors::{die_highlight, success_highlight, Highlight};
+use crate::display_logic::renderer::{
+    format_modifier, format_rolls, summary_name, summary_total, RenderContext, Renderer,
+    RolledExpression,
+};
+use comfy_table::{Cell, Table};
+
+// past this many dice the dice table is swapped for a count of each face
+pub const DEFAULT_MAX_DICE_ROWS: usize = 30;
+
+// the verbose output, a table of every die followed by the totals
+pub struct TableRenderer {
+    pub max_dice_rows: usize,
+}
+
+impl Default for TableRenderer {
+    fn default() -> Self {
+        Self {
+            max_dice_rows: DEFAULT_MAX_DICE_ROWS,
+        }
+    }
+}
+
+impl Renderer for TableRenderer {
+    fn render(
+        &self,
+        result: &SuccessCountingAfterResultKeeping,
+        context: &RenderContext,
+    ) -> String {
+        let mut rolls: Vec<&SuccessCountingRulesApplied> = result.rolls.iter().collect();
+        rolls.sort_by_key(|roll| (roll.group, roll.roll_number));
+        let mut sections = vec![if rolls.len() > self.max_dice_rows {
+            face_summary_table(&rolls, context)
+        } else {
+            dice_table(&rolls, context)
+        }];
+        sections.push(totals_table(result, context));
+
+        if !result.steps.is_empty() {
+            let mut steps = new_table(context);
+            steps.set_header(vec![Cell::new("Step"), Cell::new("Value")]);
+            for step in &result.steps {
+                steps.add_row(vec![Cell::new(&step.expression), Cell::new(step.value)]);
+            }
+            sections.push(steps.to_string());
+        }
+
+        let dropped = result.dropped_by_group();
+        if !dropped.is_empty() {
+            let mut dropped_table = new_table(context);
+            dropped_table.set_header(vec![
+                Cell::new("Dice group"),
+                Cell::new("Dice"),
+                Cell::new("Dropped rolls"),
+            ]);
+            for (group, dice_size, values) in dropped {
+                dropped_table.add_row(vec![
+                    Cell::new(group),
+                    Cell::new(format!("d{}", dice_size)),
+                    Cell::new(format!("{:?}", values)),
+                ]);
+            }
+            sections.push(dropped_table.to_string());
+        }
+
+        let terms = result.terms();
+        if terms.len() > 1 {
+            let mut term_table = new_table(context);
+            term_table
+                .set_header(terms.iter().map(|(name, _)| Cell::new(name)))
+                .add_row(
+                    terms
+                        .iter()
+                        .map(|(_, value)| Cell::new(format_modifier(*value))),
+                );
+            sections.push(term_table.to_string());
+        }
+
+        if let Some(seed) = context.seed {
+            sections.push(format!("Seed: {}", seed))
+        }
+        if !context.rolls.is_empty() {
+            sections.push(format!("Rolls: {}", format_rolls(context.rolls)))
+        }
+        sections.join("\n") + "\n"
+    }
+
+    // a row of the totals numbered in the order they're shown, then their sum
+    fn render_repeated(&self, repeated: &RepeatedRoll, context: &RenderContext) -> String {
+        let mut table = new_table(context);
+        let mut header: Vec<Cell> = (1..=repeated.totals.len()).map(Cell::new).collect();
+        header.push(Cell::new("Sum"));
+        let mut row: Vec<Cell> = repeated.totals.iter().map(Cell::new).collect();
+        row.push(Cell::new(repeated.sum()));
+        table.set_header(header).add_row(row);
+        let mut sections = vec![table.to_string()];
+        if repeated.sets_rolled > 1 {
+            sections.push(format!("Sets rolled: {}", repeated.sets_rolled))
+        }
+        if let Some(seed) = context.seed {
+            sections.push(format!("Seed: {}", seed))
+        }
+        if !context.rolls.is_empty() {
+            sections.push(format!("Rolls: {}", format_rolls(context.rolls)))
+        }
+        sections.join("\n") + "\n"
+    }
+
+    fn render_summary(&self, rolls: &[RolledExpression], seed: Option<u64>) -> String {
+        // every context shares the same style and color setting
+        let mut table = new_table(&rolls[0].context);
+        table.set_header(vec![Cell::new("Dice roll"), Cell::new("Total")]);
+        for roll in rolls {
+            table.add_row(vec![
+                Cell::new(summary_name(&roll.context)),
+                Cell::new(roll.outcome.total()),
+            ]);
+        }
+        table.add_row(vec![Cell::new("Total"), Cell::new(summary_total(rolls))]);
+        let mut sections = vec![table.to_string()];
+        if let Some(seed) = seed {
+            sections.push(format!("Seed: {}", seed))
+        }
+        sections.join("\n") + "\n"
+    }
+}
+
+fn new_table(context: &RenderContext) -> Table {
+    context.table_style.new_table(context.color)
+}
+
+fn totals_table(result: &SuccessCountingAfterResultKeeping, context: &RenderContext) -> String {
+    let mut header = vec![
+        Cell::new("Total Before Modifier"),
+        Cell::new("Total"),
+        Cell::new("Doubled"),
+        Cell::new("Halved"),
+    ];
+    let mut row = vec![
+        Cell::new(result.total_before_modifier),
+        Cell::new(result.total),
+        Cell::new(result.doubled),
+        Cell::new(result.halved),
+    ];
+
+    if result.initial_modifier != result.final_modifier {
+        header.push(Cell::new("Initial Modifier"));
+        row.push(Cell::new(format_modifier(result.initial_modifier)));
+        header.push(Cell::new("Final Modifier"));
+        row.push(Cell::new(format_modifier(result.final_modifier)));
+    } else {
+        header.push(Cell::new("Modifier"));
+        row.push(Cell::new(format_modifier(result.final_modifier)));
+    }
+    if result.deductions_from_failure > 0 {
+        header.push(Cell::new("Deductions From Failure"));
+        row.push(Cell::new(result.deductions_from_failure));
+    }
+    if result.subtractions_from_failure > 0 {
+        header.push(Cell::new("Subtractions From Failure"));
+        row.push(Cell::new(result.subtractions_from_failure));
+    }
+    if result.margin_of_success > 0 {
+        header.push(Cell::new("Margin of Success"));
+        row.push(Cell::new(result.margin_of_success));
+    }
+    if result.successes > 0 {
+        header.push(Cell::new("Successes"));
+        row.push(Cell::new(result.successes));
+    }
+    if result.failures > 0 {
+        header.push(Cell::new("Failures"));
+        row.push(Cell::new(result.failures));
+    }
+    if result.evens > 0 {
+        header.push(Cell::new("Evens"));
+        row.push(Cell::new(result.evens));
+    }
+    if result.odds > 0 {
+        header.push(Cell::new("Odds"));
+        row.push(Cell::new(result.odds));
+    }
+
+    let mut main_result = new_table(context);
+    main_result.set_header(header).add_row(row);
+    main_result.to_string()
+}
+
+// one row per die, columns only show up when at least one die needs them
+fn dice_table(rolls: &[&SuccessCountingRulesApplied], context: &RenderContext) -> String {
+    let success = |roll: &SuccessCountingRulesApplied| {
+        roll.succeeded()
+            .map(|succeeded| if succeeded { "true" } else { "false" })
+    };
+    let any =
+        |check: &dyn Fn(&SuccessCountingRulesApplied) -> bool| rolls.iter().any(|roll| check(roll));
+    let show_discarded = any(&|roll| !roll.discarded_rolls.is_empty());
+    let show_exploded = any(&|roll| !roll.exploded_rolls.is_empty());
+    let show_capped = any(&|roll| roll.capped);
+    let show_replaced = any(&|roll| roll.replaced_roll.is_some());
+    let show_kept = any(&|roll| !roll.kept);
+    let show_success = any(&|roll| success(roll).is_some());
+    let show_deductions = any(&|roll| roll.deductions > 0);
+    let show_subtracted = any(&|roll| roll.subtracted);
+
+    let mut header = vec![
+        Cell::new("Dice group"),
+        Cell::new("Dice"),
+        Cell::new("Added"),
+        Cell::new("Roll Number"),
+        Cell::new("Final roll"),
+    ];
+    let optional_headers = [
+        (show_discarded, "Discarded rolls from re-rolling"),
+        (show_exploded, "Exploded rolls"),
+        (show_capped, "Capped"),
+        (show_replaced, "Replaced"),
+        (show_kept, "Kept"),
+        (show_success, "Success"),
+        (show_deductions, "Deductions from final roll"),
+        (show_subtracted, "Subtracted from final roll"),
+    ];
+    for (show, name) in optional_headers {
+        if show {
+            header.push(Cell::new(name));
+        }
+    }
+    header.push(Cell::new("Subtotal"));
+
+    let highlight = |cell: Cell, highlight: Option<Highlight>| match highlight {
+        Some(highlight) if context.color => highlight.style_cell(cell),
+        _ => cell,
+    };
+    let mut table = new_table(context);
+    table.set_header(header);
+    for roll in rolls {
+        // a die that doesn't count is dimmed across the whole row instead
+        let die = die_highlight(roll);
+        let dropped = die == Some(Highlight::Dropped);
+        let mut row = vec![
+            Cell::new(roll.group),
+            Cell::new(format!("d{}", roll.dice_size)),
+            Cell::new(roll.sign > 0),
+            Cell::new(roll.roll_number),
+            highlight(Cell::new(roll.final_roll), die.filter(|_| !dropped)),
+        ];
+        if show_discarded {
+            row.push(Cell::new(format_list(&roll.discarded_rolls)));
+        }
+        if show_exploded {
+            let exploded =
+                (!roll.exploded_rolls.is_empty() && !dropped).then_some(Highlight::Exploded);
+            row.push(highlight(
+                Cell::new(format_list(&roll.exploded_rolls)),
+                exploded,
+            ));
+        }
+        if show_capped {
+            row.push(Cell::new(if roll.capped {
+                "stopped at the re-roll/explosion limit"
+            } else {
+                ""
+            }));
+        }
+        if show_replaced {
+            row.push(Cell::new(match roll.replaced_roll {
+                Some(target) => format!("{} has been replaced with {}", target, roll.final_roll),
+                None => String::new(),
+            }));
+        }
+        if show_kept {
+            row.push(Cell::new(roll.kept));
+        }
+        if show_success {
+            // a failed die doesn't count either, so its row is dimmed but stays red
+            let success_cell = Cell::new(success(roll).unwrap_or(""));
+            row.push(highlight(success_cell, success_highlight(roll)));
+        }
+        if show_deductions {
+            row.push(Cell::new(roll.deductions));
+        }
+        if show_subtracted {
+            row.push(Cell::new(roll.subtracted));
+        }
+        row.push(Cell::new(roll.subtotal));
+        if dropped {
+            row = row.into_iter().map(|cell| highlight(cell, die)).collect();
+        }
+        table.add_row(row);
+    }
+    table.to_string()
+}
+
+// a row per dice group counting how often each face came up, for pools too big to list
+fn face_summary_table(rolls: &[&SuccessCountingRulesApplied], context: &RenderContext) -> String {
+    let mut table = new_table(context);
+    table.set_header(vec![
+        Cell::new("Dice group"),
+        Cell::new("Dice"),
+        Cell::new("Added"),
+        Cell::new("Rolled"),
+        Cell::new("Faces"),
+        Cell::new("Kept"),
+        Cell::new("Extra rolls"),
+        Cell::new("Subtotal"),
+    ]);
+    for group in rolls.chunk_by(|a, b| a.group == b.group) {
+        let extra_rolls: usize = group
+            .iter()
+            .map(|roll| roll.discarded_rolls.len() + roll.exploded_rolls.len())
+            .sum();
+        table.add_row(vec![
+            Cell::new(group[0].group),
+            Cell::new(format!("d{}", group[0].dice_size)),
+            Cell::new(group[0].sign > 0),
+            Cell::new(group.len()),
+            Cell::new(format_face_counts(
+                &face_counts(group),
+                context.table_style.ascii,
+            )),
+            Cell::new(group.iter().filter(|roll| roll.kept).count()),
+            Cell::new(extra_rolls),
+            Cell::new(
+                group
+                    .iter()
+                    .filter(|roll| roll.adds_to_total())
+                    .map(|roll| roll.subtotal)
+                    .sum::<i32>(),
+            ),
+        ]);
+    }
+    table.to_string()
+}
+
+// how many dice ended on each face, highest face first
+pub fn face_counts(rolls: &[&SuccessCountingRulesApplied]) -> Vec<(u32, usize)> {
+    let mut counts: Vec<(u32, usize)> = vec![];
+    let mut faces: Vec<u32> = rolls.iter().map(|roll| roll.final_roll).collect();
+    faces.sort_by(|a, b| b.cmp(a));
+    for face in faces {
+        match counts.last_mut() {
+            Some((last, count)) if *last == face => *count += 1,
+            _ => counts.push((face, 1)),
+        }
+    }
+    counts
+}
+
+// eg: 6×3, 5×4, 1×1 or 6x3, 5x4, 1x1 in ascii
+pub fn format_face_counts(counts: &[(u32, usize)], ascii: bool) -> String {
+    let times = if ascii { 'x' } else { '×' };
+    counts
+        .iter()
+        .map(|(face, count)| format!("{}{}{}", face, times, count))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn format_list(values: &[u32]) -> String {
+    if values.is_empty() {
+        String::new()
+    } else {
+        format!("{:?}", values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::repeated_roll::RollOutcome;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+    use crate::display_logic::table_style::TableStyle;
+    use crate::parsing_logic::parser::parse_expression;
+    use crate::roll_with;
+
+    fn render(expression: &str, rolls: &[u32], seed: Option<u64>) -> String {
+        let result = roll_with(expression, &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression(expression).unwrap();
+        TableRenderer::default().render(&result, &RenderContext::new(&expression, seed, rolls))
+    }
+
+    #[test]
+    fn test_table_has_one_dice_table_and_the_totals() {
+        let output = render("2d6+1", &[2, 5], Some(9));
+        assert_eq!(output.matches("Roll Number").count(), 1);
+        assert_eq!(output.matches("d6").count(), 2);
+        assert!(output.contains("Total Before Modifier"));
+        assert!(output.contains("Seed: 9\nRolls: 2,5\n"));
+    }
+
+    #[test]
+    fn test_table_lists_dropped_rolls_and_steps() {
+        let output = render("(4d6dl1)*2", &[3, 1, 6, 6], None);
+        assert!(output.contains("Dropped rolls"));
+        assert!(output.contains("(4d6dl1)*2"));
+        assert!(!output.contains("Seed:"));
+    }
+
+    #[test]
+    fn test_columns_only_show_when_a_die_needs_them() {
+        let output = render("3d6x", &[2, 6, 1, 4], None);
+        assert!(output.contains("Exploded rolls"));
+        assert!(!output.contains("Discarded rolls"));
+        assert!(!output.contains("Kept"));
+        assert!(output.contains("[1]"));
+    }
+
+    #[test]
+    fn test_success_column_shows_each_die() {
+        // every die is added, so only the Success column can say false
+        let failed = |output: &str| output.lines().filter(|line| line.contains("false")).count();
+        let output = render("5d10cs>=8", &[9, 2, 8, 1, 10], None);
+        assert!(output.contains("Success"));
+        assert_eq!(failed(&output), 2);
+        let output = render("3d6cf<=2", &[1, 4, 2], None);
+        assert!(output.contains("Success"));
+        assert_eq!(failed(&output), 2);
+        assert!(!render("2d6", &[1, 4], None).contains("Success"));
+    }
+
+    #[test]
+    fn test_large_pools_switch_to_face_counts() {
+        let rolls = [6, 6, 6, 5, 5, 5, 5, 1];
+        let result = roll_with("8d6", &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let renderer = TableRenderer { max_dice_rows: 5 };
+        let expression = parse_expression("8d6").unwrap();
+        let output = renderer.render(&result, &RenderContext::new(&expression, None, &rolls));
+        assert!(output.contains("6×3, 5×4, 1×1"));
+        assert!(!output.contains("Roll Number"));
+    }
+
+    #[test]
+    fn test_colors_only_when_asked_for() {
+        let rolls = [6, 1, 3];
+        let result = roll_with("3d6dl1", &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression("3d6dl1").unwrap();
+        let mut context = RenderContext::new(&expression, None, &rolls);
+        assert!(!TableRenderer::default()
+            .render(&result, &context)
+            .contains('\x1b'));
+
+        context.color = true;
+        let output = TableRenderer::default().render(&result, &context);
+        assert!(output.contains("\x1b[38;5;10m 6 "));
+        assert!(output.contains("\x1b[2m"));
+    }
+
+    #[test]
+    fn test_colors_successes_and_failures() {
+        let rolls = [9, 2];
+        let result = roll_with("2d10cs>=8", &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression("2d10cs>=8").unwrap();
+        let mut context = RenderContext::new(&expression, None, &rolls);
+        context.color = true;
+        let output = TableRenderer::default().render(&result, &context);
+        assert!(output.contains("\x1b[38;5;10m true "));
+        assert!(output.contains("\x1b[38;5;9m\x1b[2m false "));
+    }
+
+    #[test]
+    fn test_summary_table_adds_up_the_totals() {
+        let first = RollOutcome::Single(
+            roll_with("1d20+5", &mut ScriptedRollSource::new(vec![12])).unwrap(),
+        );
+        let second = RollOutcome::Single(
+            roll_with("2d6", &mut ScriptedRollSource::new(vec![3, 4])).unwrap(),
+        );
+        let (attack_roll, damage_roll) = (
+            parse_expression("1d20+5").unwrap(),
+            parse_expression("2d6").unwrap(),
+        );
+        let mut attack = RenderContext::new(&attack_roll, None, &[12]);
+        attack.label = Some("attack");
+        let rolls = [
+            RolledExpression {
+                outcome: &first,
+                context: attack,
+            },
+            RolledExpression {
+                outcome: &second,
+                context: RenderContext::new(&damage_roll, None, &[3, 4]),
+            },
+        ];
+        let output = TableRenderer::default().render_all(&rolls, Some(3));
+        assert_eq!(output.matches("Roll Number").count(), 2);
+        assert!(output.contains("attack (1d20+5)"));
+        assert!(output.contains("│ Total           ┆ 24    │"));
+        assert!(output.ends_with("Seed: 3\n"));
+    }
+
+    #[test]
+    fn test_repeated_table_numbers_the_totals() {
+        let repeated = RepeatedRoll {
+            totals: vec![12, 15, 9],
+            sets_rolled: 4,
+        };
+        let expression = parse_expression("4d6dl1").unwrap();
+        let output = TableRenderer::default()
+            .render_repeated(&repeated, &RenderContext::new(&expression, Some(2), &[]));
+        assert!(output.contains("│ 1  ┆ 2  ┆ 3 ┆ Sum │"));
+        assert!(output.contains("│ 12 ┆ 15 ┆ 9 ┆ 36  │"));
+        assert!(output.ends_with("Sets rolled: 4\nSeed: 2\n"));
+    }
+
+    #[test]
+    fn test_ascii_style_has_no_box_drawing() {
+        let rolls = [6, 6, 6, 5, 5, 5, 5, 1];
+        let result = roll_with("8d6", &mut ScriptedRollSource::new(rolls.to_vec())).unwrap();
+        let expression = parse_expression("8d6").unwrap();
+        let mut context = RenderContext::new(&expression, None, &rolls);
+        context.table_style = TableStyle {
+            width: 100,
+            ascii: true,
+        };
+        let output = TableRenderer { max_dice_rows: 5 }.render(&result, &context);
+        assert!(output.is_ascii());
+        assert!(output.contains("6x3, 5x4, 1x1"));
+        assert!(output.lines().all(|line| line.len() <= 100));
+    }
+}
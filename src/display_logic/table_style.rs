@@ -0,0 +1,117 @@
+use crate::config::Config;
+use comfy_table::presets::{ASCII_FULL, UTF8_FULL};
+use comfy_table::{ContentArrangement, Table};
+
+// used when the output isn't a terminal and nothing else sets a width
+pub const DEFAULT_TABLE_WIDTH: u16 = 160;
+
+// narrower tables can't fit their columns, the lowest --width or config width
+pub const MIN_TABLE_WIDTH: u16 = 20;
+
+// how wide the tables get and which characters draw them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableStyle {
+    pub width: u16,
+    pub ascii: bool, // +, - and | borders, for log viewers that mangle box drawing characters
+}
+
+impl Default for TableStyle {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_TABLE_WIDTH,
+            ascii: false,
+        }
+    }
+}
+
+impl TableStyle {
+    // a flag wins over the config file, which wins over the terminal's width
+    pub fn resolve(
+        width: Option<u16>,
+        ascii: Option<bool>,
+        config: &Config,
+        terminal_width: Option<u16>,
+    ) -> Self {
+        Self {
+            width: width
+                .or(config.width)
+                .or(terminal_width)
+                .unwrap_or(DEFAULT_TABLE_WIDTH),
+            ascii: ascii.or(config.ascii).unwrap_or(false),
+        }
+    }
+
+    pub fn new_table(&self, color: bool) -> Table {
+        let mut table = Table::new();
+        table
+            .load_preset(if self.ascii { ASCII_FULL } else { UTF8_FULL })
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_width(self.width);
+        // the renderer decides on colors, not comfy-table's own terminal check
+        if color {
+            table.enforce_styling();
+        } else {
+            table.force_no_tty();
+        }
+        table
+    }
+}
+
+// None when stdout isn't a terminal, eg: piped into a file
+pub fn terminal_width() -> Option<u16> {
+    Table::new().width()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_order() {
+        let config = Config {
+            width: Some(100),
+            ascii: Some(true),
+        };
+        let style = TableStyle::resolve(Some(80), Some(false), &config, Some(200));
+        assert_eq!(
+            style,
+            TableStyle {
+                width: 80,
+                ascii: false
+            }
+        );
+        let style = TableStyle::resolve(None, None, &config, Some(200));
+        assert_eq!(
+            style,
+            TableStyle {
+                width: 100,
+                ascii: true
+            }
+        );
+        let style = TableStyle::resolve(None, None, &Config::default(), Some(200));
+        assert_eq!(
+            style,
+            TableStyle {
+                width: 200,
+                ascii: false
+            }
+        );
+        let style = TableStyle::resolve(None, None, &Config::default(), None);
+        assert_eq!(style, TableStyle::default());
+    }
+
+    #[test]
+    fn test_ascii_and_width() {
+        let mut table = TableStyle {
+            width: 30,
+            ascii: true,
+        }
+        .new_table(false);
+        table
+            .set_header(vec!["Dice group", "Total"])
+            .add_row(vec!["1", "a long cell that has to wrap to fit"]);
+        let output = table.to_string();
+        assert!(output.is_ascii());
+        assert!(output.lines().all(|line| line.len() <= 30));
+    }
+}
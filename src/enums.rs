@@ -7,3 +7,15 @@ pub enum Operator {
     Lt(u32),
     Lte(u32),
 }
+
+impl Operator {
+    pub fn target(&self) -> u32 {
+        match self {
+            Operator::Eq(target)
+            | Operator::Gt(target)
+            | Operator::Gte(target)
+            | Operator::Lt(target)
+            | Operator::Lte(target) => *target,
+        }
+    }
+}
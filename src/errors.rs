@@ -0,0 +1,112 @@
+use crate::parsing_logic::evaluation::EvaluationError;
+use crate::parsing_logic::parse_error::ParseError;
+use std::fmt;
+
+// exit codes, so scripts can tell what went wrong (a crash exits with 101)
+pub const EXIT_INVALID_EXPRESSION: i32 = 2;
+pub const EXIT_INVALID_OPTION: i32 = 3;
+pub const EXIT_ROLL_FAILED: i32 = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiceError {
+    InvalidExpression(ParseError),   // the dice roll itself can't be read or used
+    InvalidOption(String),           // a flag value that doesn't make sense, eg: --xo x
+    ConflictingOptions(String),      // flags that can't be used together, eg: --kh and --kl
+    TargetOutOfRange(String),        // a re-roll or explode target no dice group can roll
+    NeverStops(String),              // a condition every face meets, re-rolling forever
+    DivisionByZero(String),          // the part of the expression that divided by zero
+    Overflow(String),                // the part of the expression whose value is too large
+    ScriptedRollsExhausted(u32),     // --rolls ran out, holds the size of the dice being rolled
+    ScriptedRollOutOfRange(u32, u32), // --rolls value that the dice can't roll, (value, dice size)
+    InvalidConfig(String),           // a config file that can't be read or has unknown settings
+    InvalidTable(String),            // a random table file that can't be rolled on
+    TooComplex(String),              // a dice roll with too many outcomes to work out exactly
+}
+
+impl DiceError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DiceError::InvalidExpression(_) => EXIT_INVALID_EXPRESSION,
+            DiceError::InvalidOption(_)
+            | DiceError::ConflictingOptions(_)
+            | DiceError::TargetOutOfRange(_)
+            | DiceError::NeverStops(_)
+            | DiceError::InvalidConfig(_)
+            | DiceError::InvalidTable(_)
+            | DiceError::TooComplex(_) => EXIT_INVALID_OPTION,
+            DiceError::DivisionByZero(_)
+            | DiceError::Overflow(_)
+            | DiceError::ScriptedRollsExhausted(_)
+            | DiceError::ScriptedRollOutOfRange(_, _) => EXIT_ROLL_FAILED,
+        }
+    }
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceError::InvalidExpression(error) => write!(f, "{}", error),
+            DiceError::InvalidOption(message)
+            | DiceError::ConflictingOptions(message)
+            | DiceError::TargetOutOfRange(message)
+            | DiceError::NeverStops(message)
+            | DiceError::TooComplex(message) => write!(f, "{}", message),
+            DiceError::InvalidConfig(message) => write!(f, "config: {}", message),
+            DiceError::InvalidTable(message) => write!(f, "table: {}", message),
+            DiceError::DivisionByZero(expression) => {
+                write!(f, "division by zero in {}", expression)
+            }
+            DiceError::Overflow(expression) => write!(f, "{} is too large to work out", expression),
+            DiceError::ScriptedRollsExhausted(dice_size) => {
+                write!(f, "ran out of given rolls for a d{}", dice_size)
+            }
+            DiceError::ScriptedRollOutOfRange(value, dice_size) => {
+                write!(f, "given roll {} can't be rolled on a d{}", value, dice_size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiceError {}
+
+impl From<ParseError> for DiceError {
+    fn from(error: ParseError) -> Self {
+        DiceError::InvalidExpression(error)
+    }
+}
+
+impl From<EvaluationError> for DiceError {
+    fn from(error: EvaluationError) -> Self {
+        match error {
+            EvaluationError::DivisionByZero(expression) => DiceError::DivisionByZero(expression),
+            EvaluationError::Overflow(expression) => DiceError::Overflow(expression),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_tell_errors_apart() {
+        let expression = DiceError::from(ParseError::new(2, "expected a dice size"));
+        let option = DiceError::ConflictingOptions("Only one of max or min can be used".into());
+        let roll = DiceError::ScriptedRollsExhausted(6);
+        assert_eq!(expression.exit_code(), EXIT_INVALID_EXPRESSION);
+        assert_eq!(option.exit_code(), EXIT_INVALID_OPTION);
+        assert_eq!(roll.exit_code(), EXIT_ROLL_FAILED);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            DiceError::from(ParseError::new(2, "expected a dice size")).to_string(),
+            "expected a dice size at position 2"
+        );
+        assert_eq!(
+            DiceError::ScriptedRollOutOfRange(7, 6).to_string(),
+            "given roll 7 can't be rolled on a d6"
+        );
+    }
+}
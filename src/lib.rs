@@ -0,0 +1,167 @@
+pub(crate) mod config;
+pub(crate) mod dice_rolling_logic;
+pub(crate) mod display_logic;
+pub(crate) mod enums;
+pub(crate) mod errors;
+pub(crate) mod parsing_logic;
+pub(crate) mod utils;
+
+use crate::display_logic::builders::{build_dice_roll_commands, build_margin_of_success};
+
+// the library, everything else is only for the command line
+pub use crate::dice_rolling_logic::result_keeping_rules::{
+    process_groups, ResultKeepingRules, ResultKeepingRulesApplied,
+};
+pub use crate::dice_rolling_logic::roll_command::{
+    DiceRollCommand, InitialDiceRollResult, RollLimits,
+};
+pub use crate::dice_rolling_logic::roll_result::SuccessCountingAfterResultKeeping;
+pub use crate::dice_rolling_logic::roll_source::{
+    RecordingRollSource, RollSource, ScriptedRollSource,
+};
+pub use crate::dice_rolling_logic::success_counting_rules::{
+    count_successes, SuccessCountingRules, SuccessCountingRulesApplied,
+};
+pub use crate::enums::Operator;
+pub use crate::errors::DiceError;
+pub use crate::parsing_logic::ast::{FlatModifier, RollExpression};
+pub use crate::parsing_logic::evaluation::EvaluationStep;
+pub use crate::parsing_logic::fraction::Fraction;
+pub use crate::parsing_logic::parser::parse_expression;
+
+// not part of the library, only there so main.rs can reach the command line
+#[doc(hidden)]
+pub mod cli {
+    pub use crate::display_logic::cli::cli_app;
+}
+
+// rolls a dice roll with only its inline modifiers, eg: roll("4d6dl1")
+pub fn roll(expression: &str) -> Result<SuccessCountingAfterResultKeeping, DiceError> {
+    roll_with(expression, &mut rand::rng())
+}
+
+// the same as roll, with the dice coming from the given source, eg: a seeded rng
+pub fn roll_with(
+    expression: &str,
+    source: &mut impl RollSource,
+) -> Result<SuccessCountingAfterResultKeeping, DiceError> {
+    let expression = parse_expression(expression)?;
+    let commands = build_commands(&expression, RollLimits::default())?;
+    let margin = margin_of_success(&expression)?;
+    roll_commands(&expression, &commands, margin, source)
+}
+
+// a command per dice group of an already parsed dice roll, with only its inline modifiers,
+// they can be changed before rolling, eg: to keep the highest 3 like --kh 3
+pub fn build_commands(
+    expression: &RollExpression,
+    limits: RollLimits,
+) -> Result<Vec<DiceRollCommand>, DiceError> {
+    build_dice_roll_commands(
+        expression,
+        None,
+        None,
+        None,
+        None,
+        ResultKeepingRules::default(),
+        SuccessCountingRules::default(),
+        limits,
+    )
+}
+
+// the margin of success written inline, eg: 15 for 1d20ms15, 0 without one
+pub fn margin_of_success(expression: &RollExpression) -> Result<u32, DiceError> {
+    build_margin_of_success(expression, None)
+}
+
+// runs already built commands through the rest of the pipeline:
+// rolling, keeping/dropping, counting successes and working out the total
+pub fn roll_commands(
+    expression: &RollExpression,
+    commands: &[DiceRollCommand],
+    margin_of_success: u32,
+    source: &mut impl RollSource,
+) -> Result<SuccessCountingAfterResultKeeping, DiceError> {
+    let mut initial_results = vec![];
+    for command in commands {
+        initial_results.append(&mut command.roll_dice(source)?)
+    }
+    let mut secondary_results = process_groups(&mut initial_results)?;
+    let mut final_results = count_successes(
+        &mut secondary_results,
+        expression.flat_modifiers(),
+        margin_of_success,
+    )?;
+    final_results.apply_expression(expression)?;
+    Ok(final_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dice_rolling_logic::roll_source::ScriptedRollSource;
+
+    #[test]
+    fn test_roll_with_scripted_dice() {
+        let mut source = ScriptedRollSource::new(vec![1, 3, 4, 5]);
+        let result = roll_with("4d6dl1", &mut source).unwrap();
+        assert_eq!(result.total, 12);
+        assert_eq!(result.rolls.iter().filter(|roll| roll.kept).count(), 3);
+    }
+
+    #[test]
+    fn test_roll_stays_in_range() {
+        for _ in 0..20 {
+            let total = roll("2d6+3").unwrap().total;
+            assert!((5..=15).contains(&total));
+        }
+    }
+
+    #[test]
+    fn test_roll_errors() {
+        assert!(matches!(roll("4d"), Err(DiceError::InvalidExpression(_))));
+        let mut source = ScriptedRollSource::new(vec![3, 3, 3]);
+        assert_eq!(
+            roll_with("1d6/(1d4-1d4)", &mut source).unwrap_err(),
+            DiceError::DivisionByZero("1d6/(1d4-1d4)".to_string())
+        );
+        let mut source = ScriptedRollSource::new(vec![1_000_000; 4]);
+        assert!(matches!(
+            roll_with("1d1000000*1d1000000*1d1000000*1d1000000", &mut source),
+            Err(DiceError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_commands_can_be_changed_before_rolling() {
+        let expression = parse_expression("4d6+1d20ms15").unwrap();
+        let mut commands = build_commands(&expression, RollLimits::default()).unwrap();
+        commands[0].result_keeping_rules =
+            ResultKeepingRules::new(true, true, 3, None, false, false);
+        commands[0].explode = Some(Operator::Eq(6));
+        let margin = margin_of_success(&expression).unwrap();
+        assert_eq!(margin, 15);
+
+        let mut source = ScriptedRollSource::new(vec![1, 3, 4, 5, 18]);
+        let result = roll_commands(&expression, &commands, margin, &mut source).unwrap();
+        assert_eq!(result.total, 3 + 4 + 5 + 18 - 15);
+    }
+
+    #[test]
+    fn test_roll_errors_instead_of_overflowing_an_i32() {
+        let mut source = ScriptedRollSource::new(vec![2_000_000_000; 2]);
+        assert_eq!(
+            roll_with("1d2000000000+1d2000000000", &mut source).unwrap_err(),
+            DiceError::Overflow("the total".to_string())
+        );
+        let mut source = ScriptedRollSource::new(vec![2_000_000_000, 2_000_000_000, 1]);
+        assert_eq!(
+            roll_with("1d2000000000x>1999999999", &mut source).unwrap_err(),
+            DiceError::Overflow("a d2000000000 roll".to_string())
+        );
+        assert!(matches!(
+            roll("1d4000000000"),
+            Err(DiceError::InvalidExpression(_))
+        ));
+    }
+}
@@ -1,9 +1,3 @@
-use display_logic::cli;
-mod dice_rolling_logic;
-mod display_logic;
-mod enums;
-mod utils;
-
 fn main() {
-    cli::cli_app();
+    dice_roller::cli::cli_app();
 }
@@ -0,0 +1,225 @@
+use crate::enums::Operator;
+use crate::parsing_logic::fraction::Fraction;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollExpression {
+    pub source: String,
+    pub root: Expr,
+}
+
+impl RollExpression {
+    // every dice group in group order, with the sign it is added to the total with
+    pub fn dice_groups(&self) -> Vec<(i32, &DiceGroup)> {
+        let mut groups = vec![];
+        self.root.collect_dice_groups(1, &mut groups);
+        groups
+    }
+
+    // where each dice group was written, as (group, start, end) byte spans
+    pub fn dice_group_spans(&self) -> Vec<(i32, usize, usize)> {
+        let mut spans = vec![];
+        self.root.collect_dice_group_spans(&mut spans);
+        spans
+    }
+
+    pub fn modifiers(&self) -> impl Iterator<Item = &Modifier> {
+        self.dice_groups()
+            .into_iter()
+            .flat_map(|(_, dice)| dice.modifiers.iter())
+    }
+
+    // every flat number that is simply added to or subtracted from the dice, in order
+    pub fn flat_modifiers(&self) -> Vec<FlatModifier> {
+        let mut modifiers = vec![];
+        self.root
+            .collect_flat_modifiers(Some(1), &mut 0, &mut modifiers);
+        modifiers
+    }
+
+    // true when the expression only adds and subtracts, so the total is
+    // just the dice subtotals plus the modifier
+    pub fn is_additive(&self) -> bool {
+        self.root.is_additive()
+    }
+
+    pub fn text(&self, expr: &Expr) -> &str {
+        self.source
+            .get(expr.start..expr.end)
+            .unwrap_or(&self.source)
+            .trim()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub start: usize, // byte span of the sub expression in the source
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    Number(u32),
+    Dice(DiceGroup),
+    Negate(Box<Expr>),
+    Binary(BinaryOperator, Box<Expr>, Box<Expr>),
+    Rounding(Rounding, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    Round,
+}
+
+impl Rounding {
+    pub fn apply(&self, value: Fraction) -> Fraction {
+        Fraction::integer(match self {
+            Rounding::Floor => value.floor(),
+            Rounding::Ceil => value.ceil(),
+            Rounding::Round => value.round(),
+        })
+    }
+}
+
+impl Expr {
+    fn collect_dice_groups<'a>(&'a self, sign: i32, groups: &mut Vec<(i32, &'a DiceGroup)>) {
+        match &self.kind {
+            ExprKind::Number(_) => {}
+            ExprKind::Dice(dice) => groups.push((sign, dice)),
+            ExprKind::Negate(inner) => inner.collect_dice_groups(-sign, groups),
+            ExprKind::Binary(operator, lhs, rhs) => {
+                lhs.collect_dice_groups(sign, groups);
+                let rhs_sign = match operator {
+                    BinaryOperator::Subtract => -sign,
+                    _ => sign,
+                };
+                rhs.collect_dice_groups(rhs_sign, groups);
+            }
+            ExprKind::Rounding(_, inner) => inner.collect_dice_groups(sign, groups),
+        }
+    }
+
+    fn collect_dice_group_spans(&self, spans: &mut Vec<(i32, usize, usize)>) {
+        match &self.kind {
+            ExprKind::Number(_) => {}
+            ExprKind::Dice(dice) => spans.push((dice.group, self.start, self.end)),
+            ExprKind::Negate(inner) | ExprKind::Rounding(_, inner) => {
+                inner.collect_dice_group_spans(spans)
+            }
+            ExprKind::Binary(_, lhs, rhs) => {
+                lhs.collect_dice_group_spans(spans);
+                rhs.collect_dice_group_spans(spans);
+            }
+        }
+    }
+
+    // sign is None once the walk is inside a multiplication, division or rounding,
+    // where numbers stop being plain modifiers but dice groups still need counting
+    fn collect_flat_modifiers(
+        &self,
+        sign: Option<i32>,
+        last_group: &mut i32,
+        modifiers: &mut Vec<FlatModifier>,
+    ) {
+        match &self.kind {
+            ExprKind::Number(value) => {
+                if let Some(sign) = sign {
+                    modifiers.push(FlatModifier::new(
+                        sign * *value as i32,
+                        self.start,
+                        *last_group,
+                    ));
+                }
+            }
+            ExprKind::Dice(dice) => *last_group = dice.group,
+            ExprKind::Negate(inner) => {
+                inner.collect_flat_modifiers(sign.map(|sign| -sign), last_group, modifiers)
+            }
+            ExprKind::Binary(operator, lhs, rhs) => {
+                let (lhs_sign, rhs_sign) = match operator {
+                    BinaryOperator::Add => (sign, sign),
+                    BinaryOperator::Subtract => (sign, sign.map(|sign| -sign)),
+                    _ => (None, None),
+                };
+                lhs.collect_flat_modifiers(lhs_sign, last_group, modifiers);
+                rhs.collect_flat_modifiers(rhs_sign, last_group, modifiers);
+            }
+            ExprKind::Rounding(_, inner) => {
+                inner.collect_flat_modifiers(None, last_group, modifiers)
+            }
+        }
+    }
+
+    fn is_additive(&self) -> bool {
+        match &self.kind {
+            ExprKind::Number(_) | ExprKind::Dice(_) => true,
+            ExprKind::Negate(inner) => inner.is_additive(),
+            ExprKind::Binary(BinaryOperator::Add | BinaryOperator::Subtract, lhs, rhs) => {
+                lhs.is_additive() && rhs.is_additive()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlatModifier {
+    pub value: i32,
+    pub position: usize, // byte offset of the number in the expression
+    pub group: i32,      // dice group it follows, 0 when no dice come before it
+}
+
+impl FlatModifier {
+    pub fn new(value: i32, position: usize, group: i32) -> Self {
+        Self {
+            value,
+            position,
+            group,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiceGroup {
+    pub group: i32,
+    pub dice_count: u32,
+    pub dice_size: u32,
+    pub modifiers: Vec<Modifier>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modifier {
+    pub kind: ModifierKind,
+    pub position: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModifierKind {
+    KeepHigh(u32),
+    KeepLow(u32),
+    DropHigh(u32),
+    DropLow(u32),
+    ReRoll(Option<Operator>),
+    ReRollRecursively(Option<Operator>),
+    Explode(Option<Operator>),
+    ExplodeOnce(Option<Operator>),
+    Min(u32),
+    Max(u32),
+    CountSuccess(Operator),
+    CountFailure(Operator),
+    SubtractFailure(Operator),
+    DeductFailure(u32),
+    Even,
+    Odd,
+    MarginOfSuccess(u32),
+}
@@ -0,0 +1,144 @@
+use crate::parsing_logic::ast::{BinaryOperator, DiceGroup, Expr, ExprKind, RollExpression};
+use crate::parsing_logic::fraction::Fraction;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationStep {
+    pub expression: String,
+    pub value: Fraction,
+}
+
+// both hold the part of the expression that couldn't be worked out
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvaluationError {
+    DivisionByZero(String),
+    Overflow(String),
+}
+
+// evaluates the expression bottom up, recording the value of every dice group
+// and every operation in the order they were worked out
+pub fn evaluate(
+    expression: &RollExpression,
+    dice_value: &impl Fn(&DiceGroup) -> Fraction,
+) -> Result<(Fraction, Vec<EvaluationStep>), EvaluationError> {
+    let mut steps = vec![];
+    let value = evaluate_expr(expression, &expression.root, dice_value, &mut steps)?;
+    Ok((value, steps))
+}
+
+fn evaluate_expr(
+    expression: &RollExpression,
+    expr: &Expr,
+    dice_value: &impl Fn(&DiceGroup) -> Fraction,
+    steps: &mut Vec<EvaluationStep>,
+) -> Result<Fraction, EvaluationError> {
+    let overflow = || EvaluationError::Overflow(expression.text(expr).to_string());
+    let value = match &expr.kind {
+        ExprKind::Number(value) => return Ok(Fraction::integer(*value as i64)),
+        ExprKind::Dice(dice) => dice_value(dice),
+        ExprKind::Negate(inner) => {
+            let inner_value = evaluate_expr(expression, inner, dice_value, steps)?;
+            let value = inner_value.checked_neg().ok_or_else(overflow)?;
+            if matches!(inner.kind, ExprKind::Number(_)) {
+                return Ok(value);
+            }
+            value
+        }
+        ExprKind::Binary(operator, lhs, rhs) => {
+            let lhs_value = evaluate_expr(expression, lhs, dice_value, steps)?;
+            let rhs_value = evaluate_expr(expression, rhs, dice_value, steps)?;
+            let value = match operator {
+                BinaryOperator::Add => lhs_value.checked_add(rhs_value),
+                BinaryOperator::Subtract => lhs_value.checked_sub(rhs_value),
+                BinaryOperator::Multiply => lhs_value.checked_mul(rhs_value),
+                BinaryOperator::Divide => {
+                    if rhs_value.numerator == 0 {
+                        return Err(EvaluationError::DivisionByZero(
+                            expression.text(expr).to_string(),
+                        ));
+                    }
+                    lhs_value.checked_div(rhs_value)
+                }
+            };
+            value.ok_or_else(overflow)?
+        }
+        ExprKind::Rounding(rounding, inner) => {
+            rounding.apply(evaluate_expr(expression, inner, dice_value, steps)?)
+        }
+    };
+    steps.push(EvaluationStep {
+        expression: expression.text(expr).to_string(),
+        value,
+    });
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing_logic::parser::parse_expression;
+
+    fn evaluate_with(input: &str, values: &[i64]) -> (Fraction, Vec<EvaluationStep>) {
+        let expression = parse_expression(input).unwrap();
+        evaluate(&expression, &|dice: &DiceGroup| {
+            Fraction::integer(values[dice.group as usize - 1])
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_precedence_and_parentheses() {
+        assert_eq!(evaluate_with("1d8+4*2", &[5]).0, Fraction::integer(13));
+        assert_eq!(evaluate_with("(1d8+4)*2", &[5]).0, Fraction::integer(18));
+        assert_eq!(
+            evaluate_with("((1d8+4)*2-1)*2", &[5]).0,
+            Fraction::integer(34)
+        );
+        assert_eq!(evaluate_with("-(1d6-1d4)", &[2, 3]).0, Fraction::integer(1));
+    }
+
+    #[test]
+    fn test_evaluate_division_is_exact_until_rounded() {
+        assert_eq!(evaluate_with("2d6/2", &[7]).0, Fraction::new(7, 2).unwrap());
+        assert_eq!(evaluate_with("floor(2d6/2)", &[7]).0, Fraction::integer(3));
+        assert_eq!(evaluate_with("ceil(2d6/2)", &[7]).0, Fraction::integer(4));
+        assert_eq!(evaluate_with("round(2d6/4)", &[6]).0, Fraction::integer(2));
+        assert_eq!(evaluate_with("2d6/3*3", &[7]).0, Fraction::integer(7));
+    }
+
+    #[test]
+    fn test_evaluate_records_each_step() {
+        let (_, steps) = evaluate_with("(1d8+4)*2", &[5]);
+        let steps: Vec<(String, Fraction)> = steps
+            .into_iter()
+            .map(|step| (step.expression, step.value))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("1d8".to_string(), Fraction::integer(5)),
+                ("(1d8+4)".to_string(), Fraction::integer(9)),
+                ("(1d8+4)*2".to_string(), Fraction::integer(18)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_division_by_zero() {
+        let expression = parse_expression("1d6/(1d4-1d4)").unwrap();
+        let error = evaluate(&expression, &|_: &DiceGroup| Fraction::integer(2)).unwrap_err();
+        assert_eq!(
+            error,
+            EvaluationError::DivisionByZero("1d6/(1d4-1d4)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_evaluate_overflow() {
+        let expression = parse_expression("1d2*100000*100000*100000*100000").unwrap();
+        let error = evaluate(&expression, &|_: &DiceGroup| Fraction::integer(2)).unwrap_err();
+        assert_eq!(
+            error,
+            EvaluationError::Overflow("1d2*100000*100000*100000*100000".to_string())
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::errors::DiceError;
+use std::cmp::Ordering;
+use std::fmt;
+
+// exact value used while evaluating an expression so that division
+// never loses precision before an explicit floor/ceil/round
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Fraction {
+    pub numerator: i64,
+    pub denominator: i64, // always positive
+}
+
+impl Fraction {
+    // reduced, eg: 4/8 is 1/2, with the sign always on the numerator
+    pub fn new(numerator: i64, denominator: i64) -> Result<Self, DiceError> {
+        let written = || format!("{}/{}", numerator, denominator);
+        if denominator == 0 {
+            return Err(DiceError::DivisionByZero(written()));
+        }
+        // only i64::MIN over a negative denominator can't be flipped
+        Self::reduce(numerator as i128, denominator as i128)
+            .ok_or_else(|| DiceError::Overflow(written()))
+    }
+
+    pub fn integer(value: i64) -> Self {
+        Self {
+            numerator: value,
+            denominator: 1,
+        }
+    }
+
+    // the arithmetic is worked out wide and gives None when the result doesn't fit,
+    // or when dividing by zero
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let (a, b) = (self.wide(), other.wide());
+        Self::reduce((a.0 * b.1).checked_add(b.0 * a.1)?, a.1 * b.1)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        let (a, b) = (self.wide(), other.wide());
+        Self::reduce(a.0 * b.0, a.1 * b.1)
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.numerator == 0 {
+            return None;
+        }
+        let (a, b) = (self.wide(), other.wide());
+        Self::reduce(a.0 * b.1, a.1 * b.0)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Self {
+            numerator: self.numerator.checked_neg()?,
+            denominator: self.denominator,
+        })
+    }
+
+    pub fn floor(self) -> i64 {
+        self.numerator.div_euclid(self.denominator)
+    }
+
+    pub fn ceil(self) -> i64 {
+        let (numerator, denominator) = self.wide();
+        (-(-numerator).div_euclid(denominator)) as i64
+    }
+
+    // halves round away from zero
+    pub fn round(self) -> i64 {
+        let (numerator, denominator) = self.wide();
+        let rounded = (2 * numerator.abs() + denominator).div_euclid(2 * denominator);
+        (numerator.signum() * rounded) as i64
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == 1
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    fn wide(self) -> (i128, i128) {
+        (self.numerator as i128, self.denominator as i128)
+    }
+
+    fn reduce(numerator: i128, denominator: i128) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator.abs(), denominator.abs()).max(1);
+        let sign = denominator.signum();
+        Some(Self {
+            numerator: i64::try_from(sign * numerator / divisor).ok()?,
+            denominator: i64::try_from(sign * denominator / divisor).ok()?,
+        })
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = (self.wide(), other.wide());
+        (a.0 * b.1).cmp(&(b.0 * a.1))
+    }
+}
+
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            let decimal = format!("{:.2}", self.to_f64());
+            write!(f, "{}", decimal.trim_end_matches('0').trim_end_matches('.'))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction_is_reduced() {
+        assert_eq!(Fraction::new(4, 8).unwrap(), Fraction::new(1, 2).unwrap());
+        assert_eq!(Fraction::new(3, -6).unwrap(), Fraction::new(-1, 2).unwrap());
+    }
+
+    #[test]
+    fn test_fraction_new_errors_instead_of_panicking() {
+        assert_eq!(
+            Fraction::new(1, 0),
+            Err(DiceError::DivisionByZero("1/0".to_string()))
+        );
+        assert_eq!(
+            Fraction::new(i64::MIN, -1),
+            Err(DiceError::Overflow(format!("{}/-1", i64::MIN)))
+        );
+        assert_eq!(Fraction::new(i64::MIN, 1).unwrap().floor(), i64::MIN);
+    }
+
+    #[test]
+    fn test_fraction_arithmetic() {
+        let seven_halves = Fraction::integer(7)
+            .checked_div(Fraction::integer(2))
+            .unwrap();
+        assert_eq!(
+            seven_halves.checked_mul(Fraction::integer(2)),
+            Some(Fraction::integer(7))
+        );
+        assert_eq!(
+            seven_halves.checked_sub(Fraction::new(1, 2).unwrap()),
+            Some(Fraction::integer(3))
+        );
+        assert_eq!(Fraction::integer(1).checked_div(Fraction::integer(0)), None);
+    }
+
+    #[test]
+    fn test_fraction_overflow_is_none() {
+        let huge = Fraction::integer(i64::MAX);
+        assert_eq!(huge.checked_add(Fraction::integer(1)), None);
+        assert_eq!(huge.checked_mul(Fraction::integer(2)), None);
+        assert_eq!(Fraction::integer(i64::MIN).checked_neg(), None);
+        assert_eq!(
+            huge.checked_div(Fraction::integer(i64::MAX)),
+            Some(Fraction::integer(1))
+        );
+        assert_eq!(Fraction::integer(i64::MIN).ceil(), i64::MIN);
+        assert_eq!(huge.round(), i64::MAX);
+    }
+
+    #[test]
+    fn test_fraction_rounding() {
+        assert_eq!(Fraction::new(7, 2).unwrap().floor(), 3);
+        assert_eq!(Fraction::new(7, 2).unwrap().ceil(), 4);
+        assert_eq!(Fraction::new(7, 2).unwrap().round(), 4);
+        assert_eq!(Fraction::new(-7, 2).unwrap().floor(), -4);
+        assert_eq!(Fraction::new(-7, 2).unwrap().ceil(), -3);
+        assert_eq!(Fraction::new(-7, 2).unwrap().round(), -4);
+        assert_eq!(Fraction::new(10, 3).unwrap().round(), 3);
+    }
+
+    #[test]
+    fn test_fraction_display() {
+        assert_eq!(Fraction::integer(-3).to_string(), "-3");
+        assert_eq!(Fraction::new(7, 2).unwrap().to_string(), "3.5");
+        assert_eq!(Fraction::new(7, 3).unwrap().to_string(), "2.33");
+    }
+}
@@ -0,0 +1,312 @@
+use crate::parsing_logic::ast::Rounding;
+use crate::parsing_logic::parse_error::ParseError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Keyword {
+    Dice,              // d (also drop lowest when it follows a dice group)
+    Keep,              // k
+    KeepHigh,          // kh
+    KeepLow,           // kl
+    DropHigh,          // dh
+    DropLow,           // dl
+    ReRoll,            // r
+    ReRollRecursively, // rr
+    Explode,           // x
+    ExplodeOnce,       // xo
+    Min,               // min
+    Max,               // max
+    CountSuccess,      // cs
+    CountFailure,      // cf
+    SubtractFailure,   // sf
+    DeductFailure,     // df
+    Even,              // even
+    Odd,               // odd
+    MarginOfSuccess,   // ms
+}
+
+// longer spellings first so that the longest keyword always wins
+const KEYWORDS: [(&str, TokenKind); 22] = [
+    ("floor", TokenKind::Function(Rounding::Floor)),
+    ("round", TokenKind::Function(Rounding::Round)),
+    ("ceil", TokenKind::Function(Rounding::Ceil)),
+    ("even", TokenKind::Keyword(Keyword::Even)),
+    ("min", TokenKind::Keyword(Keyword::Min)),
+    ("max", TokenKind::Keyword(Keyword::Max)),
+    ("odd", TokenKind::Keyword(Keyword::Odd)),
+    ("kh", TokenKind::Keyword(Keyword::KeepHigh)),
+    ("kl", TokenKind::Keyword(Keyword::KeepLow)),
+    ("dh", TokenKind::Keyword(Keyword::DropHigh)),
+    ("dl", TokenKind::Keyword(Keyword::DropLow)),
+    ("rr", TokenKind::Keyword(Keyword::ReRollRecursively)),
+    ("xo", TokenKind::Keyword(Keyword::ExplodeOnce)),
+    ("cs", TokenKind::Keyword(Keyword::CountSuccess)),
+    ("cf", TokenKind::Keyword(Keyword::CountFailure)),
+    ("sf", TokenKind::Keyword(Keyword::SubtractFailure)),
+    ("df", TokenKind::Keyword(Keyword::DeductFailure)),
+    ("ms", TokenKind::Keyword(Keyword::MarginOfSuccess)),
+    ("d", TokenKind::Keyword(Keyword::Dice)),
+    ("k", TokenKind::Keyword(Keyword::Keep)),
+    ("r", TokenKind::Keyword(Keyword::ReRoll)),
+    ("x", TokenKind::Keyword(Keyword::Explode)),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    Number(u32),
+    Keyword(Keyword),
+    Function(Rounding),
+    Comparison(Comparison),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LeftParen,
+    RightParen,
+    End,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub position: usize,
+    pub end: usize,
+}
+
+impl Token {
+    fn new(kind: TokenKind, position: usize, end: usize) -> Self {
+        Self {
+            kind,
+            position,
+            end,
+        }
+    }
+}
+
+// positions are byte offsets into the input as it was written, so they can slice it
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let bytes = input.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while let Some(c) = input[i..].chars().next() {
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits = &input[start..i];
+            // capped so every number still fits once it is added up as an i32
+            let number = digits
+                .parse::<u32>()
+                .ok()
+                .filter(|number| *number <= i32::MAX as u32)
+                .ok_or_else(|| ParseError::new(start, format!("number {} is too large", digits)))?;
+            tokens.push(Token::new(TokenKind::Number(number), start, i));
+        } else if c.is_ascii_alphabetic() {
+            let start = i;
+            while i < bytes.len() && bytes[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            let word = input[start..i].to_ascii_lowercase();
+            tokens.append(&mut split_keywords(&word, start)?);
+        } else {
+            let start = i;
+            let kind = match c {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '(' => TokenKind::LeftParen,
+                ')' => TokenKind::RightParen,
+                '=' => TokenKind::Comparison(Comparison::Eq),
+                '>' | '<' => {
+                    let or_equal = bytes.get(i + 1) == Some(&b'=');
+                    let comparison = match (c, or_equal) {
+                        ('>', true) => Comparison::Gte,
+                        ('>', false) => Comparison::Gt,
+                        ('<', true) => Comparison::Lte,
+                        _ => Comparison::Lt,
+                    };
+                    if or_equal {
+                        i += 1;
+                    }
+                    TokenKind::Comparison(comparison)
+                }
+                _ => return Err(ParseError::new(i, format!("unexpected character '{}'", c))),
+            };
+            i += 1;
+            tokens.push(Token::new(kind, start, i));
+        }
+    }
+
+    tokens.push(Token::new(TokenKind::End, input.len(), input.len()));
+    Ok(tokens)
+}
+
+// modifiers are written back to back (eg: "khxo"), so a run of letters
+// is split greedily into the longest keywords it starts with
+fn split_keywords(word: &str, start: usize) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut offset = 0;
+    while offset < word.len() {
+        let rest = &word[offset..];
+        let (spelling, kind) = KEYWORDS
+            .iter()
+            .find(|(spelling, _)| rest.starts_with(spelling))
+            .ok_or_else(|| {
+                ParseError::new(start + offset, format!("unknown modifier '{}'", rest))
+            })?;
+        let end = start + offset + spelling.len();
+        tokens.push(Token::new(*kind, start + offset, end));
+        offset += spelling.len();
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<TokenKind> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_dice_and_modifier() {
+        assert_eq!(
+            kinds("1d20 + 5"),
+            vec![
+                TokenKind::Number(1),
+                TokenKind::Keyword(Keyword::Dice),
+                TokenKind::Number(20),
+                TokenKind::Plus,
+                TokenKind::Number(5),
+                TokenKind::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_splits_adjacent_keywords() {
+        assert_eq!(
+            kinds("3d8x8xo"),
+            vec![
+                TokenKind::Number(3),
+                TokenKind::Keyword(Keyword::Dice),
+                TokenKind::Number(8),
+                TokenKind::Keyword(Keyword::Explode),
+                TokenKind::Number(8),
+                TokenKind::Keyword(Keyword::ExplodeOnce),
+                TokenKind::End,
+            ]
+        );
+        assert_eq!(
+            kinds("4d6dl"),
+            vec![
+                TokenKind::Number(4),
+                TokenKind::Keyword(Keyword::Dice),
+                TokenKind::Number(6),
+                TokenKind::Keyword(Keyword::DropLow),
+                TokenKind::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_comparisons() {
+        assert_eq!(
+            kinds("cs>=8"),
+            vec![
+                TokenKind::Keyword(Keyword::CountSuccess),
+                TokenKind::Comparison(Comparison::Gte),
+                TokenKind::Number(8),
+                TokenKind::End,
+            ]
+        );
+        let tokens = tokenize("cf<=2").unwrap();
+        assert_eq!(tokens[1].kind, TokenKind::Comparison(Comparison::Lte));
+        assert_eq!(tokens[1].position, 2);
+        assert_eq!(tokens[2].position, 4);
+    }
+
+    #[test]
+    fn test_tokenize_arithmetic() {
+        assert_eq!(
+            kinds("floor((1d8+4)*2/3)"),
+            vec![
+                TokenKind::Function(Rounding::Floor),
+                TokenKind::LeftParen,
+                TokenKind::LeftParen,
+                TokenKind::Number(1),
+                TokenKind::Keyword(Keyword::Dice),
+                TokenKind::Number(8),
+                TokenKind::Plus,
+                TokenKind::Number(4),
+                TokenKind::RightParen,
+                TokenKind::Star,
+                TokenKind::Number(2),
+                TokenKind::Slash,
+                TokenKind::Number(3),
+                TokenKind::RightParen,
+                TokenKind::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_is_case_insensitive() {
+        assert_eq!(kinds("1D6KH1"), kinds("1d6kh1"));
+    }
+
+    #[test]
+    fn test_tokenize_unknown_modifier_position() {
+        let error = tokenize("1d20q").unwrap_err();
+        assert_eq!(error.position, 4);
+        let error = tokenize("1d20 ? 3").unwrap_err();
+        assert_eq!(error.position, 5);
+    }
+
+    #[test]
+    fn test_tokenize_positions_are_bytes_into_the_input() {
+        // a non-breaking space is 2 bytes and a kelvin sign lowercases to an ascii k
+        let tokens = tokenize("1d6\u{a0}+ 2D4").unwrap();
+        assert_eq!(tokens[3].position, 5);
+        assert_eq!((tokens[5].position, tokens[5].end), (8, 9));
+        assert_eq!(tokens[7].position, 10);
+        let error = tokenize("1d6\u{212a}h1").unwrap_err();
+        assert_eq!(error.position, 3);
+        // the caret still goes under the right character
+        let error = tokenize("1d6\u{a0}+ 2q").unwrap_err();
+        assert_eq!(error.position, 8);
+        assert_eq!(error.pointer("1d6\u{a0}+ 2q"), "1d6\u{a0}+ 2q\n       ^");
+    }
+
+    #[test]
+    fn test_tokenize_number_too_large() {
+        let error = tokenize("1d99999999999").unwrap_err();
+        assert_eq!(error.position, 2);
+    }
+
+    #[test]
+    fn test_tokenize_number_above_i32_is_too_large() {
+        assert!(tokenize("1d2147483647").is_ok());
+        let error = tokenize("1d4000000000").unwrap_err();
+        assert_eq!(error.position, 2);
+        assert_eq!(error.message, "number 4000000000 is too large");
+    }
+}
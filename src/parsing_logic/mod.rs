@@ -0,0 +1,6 @@
+pub mod ast;
+pub mod evaluation;
+pub mod fraction;
+pub mod lexer;
+pub mod parse_error;
+pub mod parser;
@@ -0,0 +1,33 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub position: usize, // byte offset into the expression
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+
+    // renders the expression with a caret under the offending character
+    pub fn pointer(&self, expression: &str) -> String {
+        // the caret goes under the character, however many bytes the ones before it take
+        let column = expression
+            .get(..self.position)
+            .map_or(self.position, |before| before.chars().count());
+        format!("{}\n{}^", expression, " ".repeat(column))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,496 @@
+use crate::enums::Operator;
+use crate::parsing_logic::ast::{
+    BinaryOperator, DiceGroup, Expr, ExprKind, Modifier, ModifierKind, RollExpression,
+};
+use crate::parsing_logic::lexer::{tokenize, Comparison, Keyword, Token, TokenKind};
+use crate::parsing_logic::parse_error::ParseError;
+
+// sum      := product (("+" | "-") product)*
+// product  := unary (("*" | "/") unary)*
+// unary    := ("+" | "-") unary | primary
+// primary  := number | dice | "(" sum ")" | ("floor" | "ceil" | "round") "(" sum ")"
+// dice     := [number] "d" number modifier*
+// modifier := keyword [comparison] [number]
+pub fn parse_expression(input: &str) -> Result<RollExpression, ParseError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        previous_end: 0,
+        next_group: 1,
+    };
+    let root = parser.parse_sum()?;
+    let token = parser.peek();
+    if token.kind != TokenKind::End {
+        let message = match token.kind {
+            TokenKind::RightParen => "unmatched ')'",
+            _ => "expected '+', '-', '*' or '/'",
+        };
+        return Err(ParseError::new(token.position, message));
+    }
+    Ok(RollExpression {
+        source: input.to_string(),
+        root,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    previous_end: usize,
+    next_group: i32,
+}
+
+impl Parser {
+    fn peek(&self) -> Token {
+        self.tokens[self.index]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek();
+        if token.kind != TokenKind::End {
+            self.index += 1;
+            self.previous_end = token.end;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, message: &str) -> Result<Token, ParseError> {
+        let token = self.advance();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(ParseError::new(token.position, message))
+        }
+    }
+
+    fn node(&self, kind: ExprKind, start: usize) -> Expr {
+        Expr {
+            kind,
+            start,
+            end: self.previous_end,
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_product()?;
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Plus => BinaryOperator::Add,
+                TokenKind::Minus => BinaryOperator::Subtract,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.parse_product()?;
+            let start = lhs.start;
+            lhs = self.node(
+                ExprKind::Binary(operator, Box::new(lhs), Box::new(rhs)),
+                start,
+            );
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Star => BinaryOperator::Multiply,
+                TokenKind::Slash => BinaryOperator::Divide,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.parse_unary()?;
+            if operator == BinaryOperator::Divide && rhs.kind == ExprKind::Number(0) {
+                return Err(ParseError::new(rhs.start, "division by zero"));
+            }
+            let start = lhs.start;
+            lhs = self.node(
+                ExprKind::Binary(operator, Box::new(lhs), Box::new(rhs)),
+                start,
+            );
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek();
+        match token.kind {
+            TokenKind::Plus => {
+                self.advance();
+                let mut inner = self.parse_unary()?;
+                inner.start = token.position;
+                Ok(inner)
+            }
+            TokenKind::Minus => {
+                self.advance();
+                let inner = self.parse_unary()?;
+                Ok(self.node(ExprKind::Negate(Box::new(inner)), token.position))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek();
+        let rounding = match token.kind {
+            TokenKind::LeftParen => {
+                self.advance();
+                let mut inner = self.parse_sum()?;
+                self.expect(TokenKind::RightParen, "expected ')'")?;
+                inner.start = token.position;
+                inner.end = self.previous_end;
+                return Ok(inner);
+            }
+            TokenKind::Function(rounding) => rounding,
+            _ => return self.parse_dice_or_number(),
+        };
+        self.advance();
+        self.expect(
+            TokenKind::LeftParen,
+            "expected '(' after the rounding function",
+        )?;
+        let inner = self.parse_sum()?;
+        self.expect(TokenKind::RightParen, "expected ')'")?;
+        Ok(self.node(
+            ExprKind::Rounding(rounding, Box::new(inner)),
+            token.position,
+        ))
+    }
+
+    fn parse_dice_or_number(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek();
+        let dice_count = match token.kind {
+            TokenKind::Number(number) => {
+                self.advance();
+                if self.peek().kind != TokenKind::Keyword(Keyword::Dice) {
+                    return Ok(self.node(ExprKind::Number(number), token.position));
+                }
+                number
+            }
+            // "d20" is shorthand for "1d20"
+            TokenKind::Keyword(Keyword::Dice) => 1,
+            _ => {
+                return Err(ParseError::new(
+                    token.position,
+                    "expected a number, a dice roll or '('",
+                ))
+            }
+        };
+        self.advance();
+
+        let size_token = self.advance();
+        let dice_size = match size_token.kind {
+            TokenKind::Number(0) => {
+                return Err(ParseError::new(
+                    size_token.position,
+                    "dice must have at least one side",
+                ))
+            }
+            TokenKind::Number(number) => number,
+            _ => {
+                return Err(ParseError::new(
+                    size_token.position,
+                    "expected the number of sides after 'd'",
+                ))
+            }
+        };
+
+        let mut modifiers = vec![];
+        while let TokenKind::Keyword(keyword) = self.peek().kind {
+            modifiers.push(self.parse_modifier(keyword)?);
+        }
+
+        let group = self.next_group;
+        self.next_group += 1;
+        Ok(self.node(
+            ExprKind::Dice(DiceGroup {
+                group,
+                dice_count,
+                dice_size,
+                modifiers,
+            }),
+            token.position,
+        ))
+    }
+
+    fn parse_modifier(&mut self, keyword: Keyword) -> Result<Modifier, ParseError> {
+        let position = self.advance().position;
+        let kind = match keyword {
+            Keyword::Keep | Keyword::KeepHigh => ModifierKind::KeepHigh(self.parse_count()?),
+            Keyword::KeepLow => ModifierKind::KeepLow(self.parse_count()?),
+            Keyword::DropHigh => ModifierKind::DropHigh(self.parse_count()?),
+            // a bare "d" after a dice group drops the lowest, same as "dl"
+            Keyword::Dice | Keyword::DropLow => ModifierKind::DropLow(self.parse_count()?),
+            Keyword::ReRoll => ModifierKind::ReRoll(self.parse_target()?),
+            Keyword::ReRollRecursively => ModifierKind::ReRollRecursively(self.parse_target()?),
+            Keyword::Explode => ModifierKind::Explode(self.parse_target()?),
+            Keyword::ExplodeOnce => ModifierKind::ExplodeOnce(self.parse_target()?),
+            Keyword::Min => ModifierKind::Min(self.parse_required_number()?),
+            Keyword::Max => ModifierKind::Max(self.parse_required_number()?),
+            Keyword::CountSuccess => ModifierKind::CountSuccess(self.parse_required_target()?),
+            Keyword::CountFailure => ModifierKind::CountFailure(self.parse_required_target()?),
+            Keyword::SubtractFailure => {
+                ModifierKind::SubtractFailure(self.parse_required_target()?)
+            }
+            Keyword::DeductFailure => ModifierKind::DeductFailure(self.parse_count()?),
+            Keyword::Even => ModifierKind::Even,
+            Keyword::Odd => ModifierKind::Odd,
+            Keyword::MarginOfSuccess => {
+                ModifierKind::MarginOfSuccess(self.parse_required_number()?)
+            }
+        };
+        Ok(Modifier { kind, position })
+    }
+
+    // optional number that defaults to 1, eg: "kh" is "kh1"
+    fn parse_count(&mut self) -> Result<u32, ParseError> {
+        match self.peek().kind {
+            TokenKind::Number(_) => self.parse_required_number(),
+            _ => Ok(1),
+        }
+    }
+
+    fn parse_required_number(&mut self) -> Result<u32, ParseError> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::Number(0) => {
+                Err(ParseError::new(token.position, "number must be at least 1"))
+            }
+            TokenKind::Number(number) => Ok(number),
+            _ => Err(ParseError::new(token.position, "expected a number")),
+        }
+    }
+
+    // "=8", ">=8", "<3" or a bare "8" which means "=8"
+    fn parse_target(&mut self) -> Result<Option<Operator>, ParseError> {
+        match self.peek().kind {
+            TokenKind::Comparison(_) | TokenKind::Number(_) => {
+                self.parse_required_target().map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_required_target(&mut self) -> Result<Operator, ParseError> {
+        let comparison = match self.peek().kind {
+            TokenKind::Comparison(comparison) => {
+                self.advance();
+                comparison
+            }
+            _ => Comparison::Eq,
+        };
+        let token = self.advance();
+        let number = match token.kind {
+            TokenKind::Number(number) => number,
+            _ => {
+                return Err(ParseError::new(
+                    token.position,
+                    "expected a comparison target, eg: =6, >=5, <3",
+                ))
+            }
+        };
+        Ok(match comparison {
+            Comparison::Eq => Operator::Eq(number),
+            Comparison::Gt => Operator::Gt(number),
+            Comparison::Gte => Operator::Gte(number),
+            Comparison::Lt => Operator::Lt(number),
+            Comparison::Lte => Operator::Lte(number),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing_logic::ast::FlatModifier;
+
+    fn dice(expression: &RollExpression, index: usize) -> &DiceGroup {
+        expression.dice_groups()[index].1
+    }
+
+    fn modifier_kinds(expression: &RollExpression) -> Vec<ModifierKind> {
+        dice(expression, 0)
+            .modifiers
+            .iter()
+            .map(|m| m.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_dice_and_modifiers() {
+        let expression = parse_expression("1d20+5-1d4+3").unwrap();
+        let groups = expression.dice_groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].1.dice_count, 1);
+        assert_eq!(groups[0].1.dice_size, 20);
+        assert_eq!(groups[0].1.group, 1);
+        assert_eq!(groups[1].0, -1);
+        assert_eq!(groups[1].1.dice_size, 4);
+        assert_eq!(groups[1].1.group, 2);
+        assert_eq!(
+            expression.flat_modifiers(),
+            vec![FlatModifier::new(5, 5, 1), FlatModifier::new(3, 11, 2)]
+        );
+        assert!(expression.is_additive());
+    }
+
+    #[test]
+    fn test_parse_keeps_every_flat_modifier() {
+        let values = |input: &str| -> Vec<i32> {
+            parse_expression(input)
+                .unwrap()
+                .flat_modifiers()
+                .iter()
+                .map(|modifier| modifier.value)
+                .collect()
+        };
+        assert_eq!(values("1d20+5+2"), vec![5, 2]);
+        assert_eq!(values("1d20+5-1d4+3"), vec![5, 3]);
+        assert_eq!(values("3-1d6-(2-1)"), vec![3, -2, 1]);
+        // numbers inside multiplication or rounding are not flat modifiers
+        assert_eq!(values("1d8*2+4"), vec![4]);
+        assert_eq!(values("floor(1d8/2)-1"), vec![-1]);
+    }
+
+    #[test]
+    fn test_parse_leading_sign_and_implicit_count() {
+        let expression = parse_expression("-d8 + 2").unwrap();
+        assert_eq!(expression.dice_groups()[0].0, -1);
+        assert_eq!(dice(&expression, 0).dice_count, 1);
+        assert_eq!(dice(&expression, 0).dice_size, 8);
+    }
+
+    #[test]
+    fn test_parse_keep_and_drop() {
+        let expression = parse_expression("4d6kh3").unwrap();
+        assert_eq!(modifier_kinds(&expression), vec![ModifierKind::KeepHigh(3)]);
+
+        let expression = parse_expression("4d6d1").unwrap();
+        assert_eq!(modifier_kinds(&expression), vec![ModifierKind::DropLow(1)]);
+
+        let expression = parse_expression("2d20kl").unwrap();
+        assert_eq!(modifier_kinds(&expression), vec![ModifierKind::KeepLow(1)]);
+    }
+
+    #[test]
+    fn test_parse_reroll_and_explode() {
+        let expression = parse_expression("1d20r1").unwrap();
+        assert_eq!(
+            modifier_kinds(&expression),
+            vec![ModifierKind::ReRoll(Some(Operator::Eq(1)))]
+        );
+
+        let expression = parse_expression("3d8x8xo").unwrap();
+        assert_eq!(
+            modifier_kinds(&expression),
+            vec![
+                ModifierKind::Explode(Some(Operator::Eq(8))),
+                ModifierKind::ExplodeOnce(None),
+            ]
+        );
+
+        let expression = parse_expression("1d6rr<3").unwrap();
+        assert_eq!(
+            modifier_kinds(&expression),
+            vec![ModifierKind::ReRollRecursively(Some(Operator::Lt(3)))]
+        );
+    }
+
+    #[test]
+    fn test_parse_success_counting() {
+        let expression = parse_expression("10d10cs>=8df2").unwrap();
+        assert_eq!(
+            modifier_kinds(&expression),
+            vec![
+                ModifierKind::CountSuccess(Operator::Gte(8)),
+                ModifierKind::DeductFailure(2),
+            ]
+        );
+
+        let expression = parse_expression("10d20evenodd ms5").unwrap();
+        assert_eq!(
+            modifier_kinds(&expression),
+            vec![
+                ModifierKind::Even,
+                ModifierKind::Odd,
+                ModifierKind::MarginOfSuccess(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dice_group_spans() {
+        let expression = parse_expression("2d20kh + (1d4) * 3").unwrap();
+        assert_eq!(expression.dice_group_spans(), vec![(1, 0, 6), (2, 9, 14)]);
+    }
+
+    #[test]
+    fn test_parse_modifier_positions() {
+        let expression = parse_expression("2d20 + 1d4kh1").unwrap();
+        assert_eq!(dice(&expression, 1).modifiers[0].position, 10);
+        match &expression.root.kind {
+            ExprKind::Binary(_, _, rhs) => assert_eq!((rhs.start, rhs.end), (7, 13)),
+            _ => panic!("Expected a binary expression"),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_are_positioned() {
+        assert_eq!(parse_expression("1d").unwrap_err().position, 2);
+        assert_eq!(parse_expression("1d0").unwrap_err().position, 2);
+        assert_eq!(parse_expression("1d20+").unwrap_err().position, 5);
+        assert_eq!(parse_expression("1d20 5").unwrap_err().position, 5);
+        assert_eq!(parse_expression("10d10cs").unwrap_err().position, 7);
+        assert_eq!(parse_expression("4d6kh0").unwrap_err().position, 5);
+        assert_eq!(parse_expression("").unwrap_err().position, 0);
+        assert_eq!(parse_expression("(1d8+4").unwrap_err().position, 6);
+        assert_eq!(parse_expression("1d8+4)").unwrap_err().position, 5);
+        assert_eq!(parse_expression("2d6/0").unwrap_err().position, 4);
+        assert_eq!(parse_expression("floor 2d6").unwrap_err().position, 6);
+    }
+
+    #[test]
+    fn test_parse_does_not_ignore_trailing_text() {
+        let error = parse_expression("1d20+5 hello").unwrap_err();
+        assert_eq!(error.position, 7);
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expression = parse_expression("1d8+4*2").unwrap();
+        match &expression.root.kind {
+            ExprKind::Binary(BinaryOperator::Add, _, rhs) => {
+                assert!(matches!(
+                    rhs.kind,
+                    ExprKind::Binary(BinaryOperator::Multiply, _, _)
+                ))
+            }
+            _ => panic!("Expected an addition at the root"),
+        }
+        assert!(expression.flat_modifiers().is_empty());
+        assert!(!expression.is_additive());
+    }
+
+    #[test]
+    fn test_parse_parentheses_and_rounding() {
+        let expression = parse_expression("floor((1d8 + 4) * 2 / 3)").unwrap();
+        match &expression.root.kind {
+            ExprKind::Rounding(crate::parsing_logic::ast::Rounding::Floor, inner) => {
+                assert_eq!(expression.text(inner), "(1d8 + 4) * 2 / 3")
+            }
+            _ => panic!("Expected a rounding function at the root"),
+        }
+        assert_eq!(dice(&expression, 0).dice_size, 8);
+    }
+
+    #[test]
+    fn test_parse_signs_through_parentheses() {
+        let expression = parse_expression("10-(1d6-1d4)").unwrap();
+        let signs: Vec<i32> = expression
+            .dice_groups()
+            .iter()
+            .map(|(sign, _)| *sign)
+            .collect();
+        assert_eq!(signs, vec![-1, 1]);
+        assert_eq!(expression.flat_modifiers(), vec![FlatModifier::new(10, 0, 0)]);
+    }
+}
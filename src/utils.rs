@@ -1,27 +1,36 @@
-use crate::dice_rolling_logic::roll_command::InitialDiceRollResult;
+use crate::dice_rolling_logic::result_keeping_rules::process_groups;
+use crate::dice_rolling_logic::roll_command::{InitialDiceRollResult, RollLimits};
+use crate::dice_rolling_logic::success_counting_rules::count_successes;
 use crate::display_logic::builders::{
     build_dice_roll_commands, build_result_keeping_rules, build_success_counting_rules,
 };
+use crate::display_logic::renderer::{RenderContext, Renderer};
+use crate::display_logic::table_renderer::TableRenderer;
 use crate::enums::Operator;
+use crate::errors::DiceError;
+use crate::parsing_logic::parser::parse_expression;
 use regex::Regex;
 
-pub fn yn_tf_to_bool(value: Option<String>) -> bool {
+pub fn yn_tf_to_bool(value: Option<String>) -> Result<bool, DiceError> {
     if let Some(value) = value {
         let lowercased = value.to_lowercase();
         if lowercased == "y" || lowercased == "t" {
-            true
+            Ok(true)
         } else if lowercased == "n" || lowercased == "f" {
-            false
+            Ok(false)
         } else {
-            unreachable!()
+            Err(DiceError::InvalidOption(format!(
+                "'{}' isn't one of Y/N/T/F or y/n/t/f",
+                value
+            )))
         }
     } else {
-        false
+        Ok(false)
     }
 }
 
-pub fn parse_operator(input: &str) -> Option<Operator> {
-    let number = parse_number(input);
+pub fn parse_operator(input: &str) -> Result<Operator, DiceError> {
+    let number = parse_number(input)?;
     let lowercased = input.to_lowercase();
     let res = if lowercased.contains("eq") {
         Operator::Eq(number)
@@ -34,10 +43,10 @@ pub fn parse_operator(input: &str) -> Option<Operator> {
     } else if lowercased.contains("gt") {
         Operator::Gt(number)
     } else {
-        unreachable!()
+        return Err(invalid_comparison(input));
     };
 
-    Some(res)
+    Ok(res)
 }
 
 pub fn apply_operator(operator: Operator, roll_value: &u32) -> bool {
@@ -50,39 +59,56 @@ pub fn apply_operator(operator: Operator, roll_value: &u32) -> bool {
     }
 }
 
-pub fn parse_number(input: &str) -> u32 {
+pub fn parse_number(input: &str) -> Result<u32, DiceError> {
     let number_part = Regex::new(r"\d+").unwrap();
-    let number = number_part.find(input).unwrap().as_str().parse().unwrap();
-    number
+    number_part
+        .find(input)
+        .and_then(|number| number.as_str().parse().ok())
+        .ok_or_else(|| invalid_comparison(input))
 }
 
-pub trait VerboseTableDisplay {
-    fn verbose_display(self);
-}
-
-pub trait AbridgedTableDisplay {
-    fn abridged_display(self);
+fn invalid_comparison(input: &str) -> DiceError {
+    DiceError::InvalidOption(format!(
+        "'{}' isn't a number comparison, eg: eq10, lt10, gt10, gte10, lte10",
+        input
+    ))
 }
 
 pub fn _sample_for_testing() {
-    let res = build_dice_roll_commands("10d20".parse().unwrap(), None, None, None, None);
-    let commands = res.0;
-    let modifier = res.1;
-    let mut initial_results: Vec<InitialDiceRollResult> = vec![];
-    for command in commands {
-        initial_results.append(&mut command.roll_dice())
-    }
-    let result_keeping_rules = build_result_keeping_rules(None, None, None, None, None, None);
-    let success_keeping_rules = build_success_counting_rules(
+    let expression = parse_expression("10d20").unwrap();
+    let result_keeping_rules =
+        build_result_keeping_rules(None, None, None, None, None, None, None).unwrap();
+    let success_counting_rules = build_success_counting_rules(
         None,
         Some("lte10".parse().unwrap()),
         None,
         None,
         Some(1),
         None,
+    )
+    .unwrap();
+    let commands = build_dice_roll_commands(
+        &expression,
+        None,
         None,
-    );
-    let mut secondary_results = result_keeping_rules.process_results(&mut initial_results);
-    let final_results = success_keeping_rules.count_successes(&mut secondary_results, modifier);
-    final_results.verbose_display()
+        None,
+        None,
+        result_keeping_rules,
+        success_counting_rules,
+        RollLimits::default(),
+    )
+    .unwrap();
+    let flat_modifiers = expression.flat_modifiers();
+    let mut rng = rand::rng();
+    let mut initial_results: Vec<InitialDiceRollResult> = vec![];
+    for command in commands {
+        initial_results.append(&mut command.roll_dice(&mut rng).unwrap())
+    }
+    let mut secondary_results = process_groups(&mut initial_results).unwrap();
+    let final_results = count_successes(&mut secondary_results, flat_modifiers, 0).unwrap();
+    print!(
+        "{}",
+        TableRenderer::default()
+            .render(&final_results, &RenderContext::new(&expression, None, &[]))
+    )
 }